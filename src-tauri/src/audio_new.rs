@@ -1,6 +1,7 @@
+use crate::dsp::{ChannelLevels, ChannelMixerParams, CompressorParams, ConvolverParams, CrossfeedParams, EffectChain, KaraokeParams, LimiterParams, LoudnessParams, ParametricEqParams, SpectrumFrame, SpectrumSettings, StereoWidthParams};
 use crate::symphonia_player::SymphoniaPlayer;
 
-pub use crate::symphonia_player::{TrackMetadata, AlbumArtwork};
+pub use crate::symphonia_player::{TrackMetadata, AlbumArtwork, VolumeCurve};
 
 pub struct AudioPlayer {
     player: SymphoniaPlayer,
@@ -33,10 +34,130 @@ impl AudioPlayer {
         self.player.set_volume(volume);
     }
 
+    pub fn set_volume_curve(&self, curve: VolumeCurve) -> Result<(), String> {
+        self.player.set_volume_curve(curve)
+    }
+
+    pub fn get_volume_curve(&self) -> VolumeCurve {
+        self.player.get_volume_curve()
+    }
+
     pub fn seek(&self, position: f32) -> Result<(), String> {
         self.player.seek(position)
     }
 
+    pub fn set_channel_mixer(&self, params: ChannelMixerParams) {
+        self.player.set_channel_mixer(params);
+    }
+
+    pub fn get_channel_mixer(&self) -> ChannelMixerParams {
+        self.player.get_channel_mixer()
+    }
+
+    pub fn set_crossfeed(&self, params: Option<CrossfeedParams>) -> Result<(), String> {
+        self.player.set_crossfeed(params)
+    }
+
+    pub fn apply_crossfeed(effects: &mut EffectChain, params: Option<CrossfeedParams>) -> Result<(), String> {
+        SymphoniaPlayer::apply_crossfeed(effects, params)
+    }
+
+    pub fn set_compressor(&self, params: Option<CompressorParams>) {
+        self.player.set_compressor(params);
+    }
+
+    pub fn get_compressor(&self) -> Option<CompressorParams> {
+        self.player.get_compressor()
+    }
+
+    pub fn set_loudness(&self, params: Option<LoudnessParams>) {
+        self.player.set_loudness(params);
+    }
+
+    pub fn get_loudness(&self) -> Option<LoudnessParams> {
+        self.player.get_loudness()
+    }
+
+    pub fn set_karaoke(&self, params: Option<KaraokeParams>) {
+        self.player.set_karaoke(params);
+    }
+
+    pub fn get_karaoke(&self) -> Option<KaraokeParams> {
+        self.player.get_karaoke()
+    }
+
+    pub fn set_stereo_width(&self, params: Option<StereoWidthParams>) {
+        self.player.set_stereo_width(params);
+    }
+
+    pub fn get_stereo_width(&self) -> Option<StereoWidthParams> {
+        self.player.get_stereo_width()
+    }
+
+    pub fn set_convolver(&self, params: Option<ConvolverParams>) -> Result<(), String> {
+        self.player.set_convolver(params)
+    }
+
+    pub fn get_convolver(&self) -> Option<ConvolverParams> {
+        self.player.get_convolver()
+    }
+
+    pub fn set_limiter(&self, params: LimiterParams) {
+        self.player.set_limiter(params);
+    }
+
+    pub fn get_limiter(&self) -> LimiterParams {
+        self.player.get_limiter()
+    }
+
+    pub fn set_ab_loop(&self, start: f32, end: f32) -> Result<(), String> {
+        self.player.set_ab_loop(start, end)
+    }
+
+    pub fn clear_ab_loop(&self) {
+        self.player.clear_ab_loop();
+    }
+
+    pub fn set_device_listener(&self, listener: impl Fn(&str, &mut EffectChain) + Send + 'static) {
+        self.player.set_device_listener(listener);
+    }
+
+    pub fn set_spectrum_listener(&self, listener: impl Fn(&SpectrumFrame) + Send + 'static) {
+        self.player.set_spectrum_listener(listener);
+    }
+
+    pub fn set_spectrum_settings(&self, settings: SpectrumSettings) -> Result<(), String> {
+        self.player.set_spectrum_settings(settings)
+    }
+
+    pub fn get_spectrum_settings(&self) -> SpectrumSettings {
+        self.player.get_spectrum_settings()
+    }
+
+    pub fn set_level_listener(&self, listener: impl Fn(&[ChannelLevels]) + Send + 'static) {
+        self.player.set_level_listener(listener);
+    }
+
+    pub fn get_levels(&self) -> Vec<ChannelLevels> {
+        self.player.get_levels()
+    }
+
+    pub fn reset_clip_counters(&self) {
+        self.player.reset_clip_counters();
+    }
+
+    pub fn get_output_device(&self) -> Option<String> {
+        self.player.get_output_device()
+    }
+
+    pub fn output_devices() -> Result<Vec<String>, String> {
+        SymphoniaPlayer::output_devices()
+    }
+
+    pub fn with_effects<R>(&self, f: impl FnOnce(&mut EffectChain) -> R) -> R {
+        self.player.with_effects(f)
+    }
+
     pub fn get_current_time(&self) -> f32 {
         self.player.get_current_time()
     }
@@ -69,4 +190,12 @@ impl AudioPlayer {
     pub fn enable_equalizer(&self, enabled: bool) -> Result<(), String> {
         self.player.enable_equalizer(enabled)
     }
-}
\ No newline at end of file
+
+    pub fn set_parametric_eq(&self, params: ParametricEqParams) -> Result<(), String> {
+        self.player.set_parametric_eq(params)
+    }
+
+    pub fn get_parametric_eq(&self) -> ParametricEqParams {
+        self.player.get_parametric_eq()
+    }
+}
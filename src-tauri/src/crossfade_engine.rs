@@ -1,3 +1,5 @@
+use crate::tempo::TempoEstimate;
+use crate::time_stretch::{StretchControl, TimeStretch};
 use rodio::{OutputStream, OutputStreamHandle, Sink, Source};
 use std::sync::{Arc, Mutex};
 use std::time::{Duration, Instant};
@@ -11,10 +13,16 @@ use std::fs::File;
 use std::thread;
 use std::sync::mpsc::{self, Receiver, Sender};
 
+/// Largest tempo change applied to beat-match the incoming track (10%).
+const MAX_TEMPO_CHANGE: f32 = 0.1;
+
+/// Time a beat-matched track takes to return to its own tempo after the crossfade.
+const TEMPO_RELEASE_SECS: f32 = 8.0;
+
 #[derive(Debug, Clone)]
 pub enum CrossfadeCommand {
     Play(String),
-    PlayWithCrossfade(String, f32), // path, crossfade_duration_seconds
+    PlayWithCrossfade(String, f32, Option<BeatSync>), // path, crossfade_duration_seconds, tempo of both tracks
     Pause,
     Resume,
     Stop,
@@ -22,6 +30,50 @@ pub enum CrossfadeCommand {
     SetVolume(f32),
     SetCrossfadeDuration(f32),
     EnableCrossfade(bool),
+    SetBeatMatch(bool),
+}
+
+/// Tempo and beat grid of the outgoing and incoming tracks, for beat-matched crossfades.
+#[derive(Debug, Clone)]
+pub struct BeatSync {
+    outgoing: TempoEstimate,
+    incoming: TempoEstimate,
+}
+
+impl BeatSync {
+    /// Pairs the tempos of both tracks, if both include a beat grid.
+    pub fn new(outgoing: TempoEstimate, incoming: TempoEstimate) -> Option<Self> {
+        (outgoing.offset.is_some() && incoming.offset.is_some()).then_some(Self { outgoing, incoming })
+    }
+
+    /// Tempo of the incoming track closest to the outgoing one, counting
+    /// half and double time.
+    fn incoming_bpm(&self) -> f32 {
+        [0.5, 1.0, 2.0]
+            .iter()
+            .map(|factor| self.incoming.bpm * factor)
+            .min_by(|a, b| (self.outgoing.bpm / a).ln().abs().total_cmp(&(self.outgoing.bpm / b).ln().abs()))
+            .unwrap_or(self.incoming.bpm)
+    }
+
+    /// Rate the incoming track has to play at to match the outgoing tempo,
+    /// unless that would change it too audibly.
+    fn rate(&self) -> Option<f32> {
+        if self.outgoing.bpm <= 0.0 || self.incoming.bpm <= 0.0 {
+            return None;
+        }
+        let rate = self.outgoing.bpm / self.incoming_bpm();
+        ((rate - 1.0).abs() <= MAX_TEMPO_CHANGE).then_some(rate)
+    }
+
+    /// Where to start the incoming track, in seconds, so its beats fall on
+    /// those of the outgoing track playing at `outgoing_position`.
+    fn start(&self, outgoing_position: f32) -> f32 {
+        // Both offsets are present, see `new`
+        let (outgoing_offset, incoming_offset) = (self.outgoing.offset.unwrap_or(0.0), self.incoming.offset.unwrap_or(0.0));
+        let phase = ((outgoing_position - outgoing_offset) * self.outgoing.bpm / 60.0).rem_euclid(1.0);
+        incoming_offset + phase * 60.0 / self.incoming_bpm()
+    }
 }
 
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
@@ -29,6 +81,10 @@ pub struct CrossfadeConfig {
     pub enabled: bool,
     pub duration_seconds: f32,
     pub curve_type: CrossfadeCurve,
+    /// Time-stretch the incoming track to the outgoing tempo and line up
+    /// their beats when both tempos are known.
+    #[serde(default)]
+    pub beat_match: bool,
 }
 
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
@@ -45,6 +101,7 @@ impl Default for CrossfadeConfig {
             enabled: false,
             duration_seconds: 3.0,
             curve_type: CrossfadeCurve::EqualPower,
+            beat_match: false,
         }
     }
 }
@@ -66,6 +123,22 @@ struct AudioSource {
     duration: f64,
     start_time: Instant,
     is_primary: bool,
+    /// Tempo control of a beat-matched source, which also tracks its position.
+    stretch: Option<StretchControl>,
+    /// Rate the source was matched at, until it has eased back to its own tempo.
+    matched_rate: Option<f32>,
+}
+
+impl AudioSource {
+    /// Position in the track, in seconds. A sink only counts the time it has
+    /// played, which for a stretched source misses the beat-align seek and
+    /// runs at the matched rate, so those report the stretcher's position.
+    fn position(&self) -> f64 {
+        match &self.stretch {
+            Some(control) => control.position(),
+            None => self.sink.get_pos().as_secs_f64(),
+        }
+    }
 }
 
 pub struct CrossfadeAudioPlayer {
@@ -123,7 +196,7 @@ impl CrossfadeAudioPlayer {
                     CrossfadeCommand::Play(path) => {
                         Self::stop_current_playback(&mut current_source, &mut crossfade_source);
                         
-                        if let Ok(sink) = Self::create_audio_source(&stream_handle, &path) {
+                        if let Ok(sink) = Self::create_audio_source(&stream_handle, &path, None) {
                             let duration = Self::get_file_duration(&path).unwrap_or(0.0);
                             
                             if let Ok(mut info) = track_info.lock() {
@@ -142,11 +215,13 @@ impl CrossfadeAudioPlayer {
                                 duration,
                                 start_time: Instant::now(),
                                 is_primary: true,
+                                stretch: None,
+                                matched_rate: None,
                             });
                         }
                     },
                     
-                    CrossfadeCommand::PlayWithCrossfade(path, crossfade_duration) => {
+                    CrossfadeCommand::PlayWithCrossfade(path, crossfade_duration, beat_sync) => {
                         let crossfade_enabled = {
                             let config = crossfade_config.lock().unwrap();
                             config.enabled
@@ -156,7 +231,7 @@ impl CrossfadeAudioPlayer {
                             // If crossfade is disabled, just play normally
                             Self::stop_current_playback(&mut current_source, &mut crossfade_source);
                             
-                            if let Ok(sink) = Self::create_audio_source(&stream_handle, &path) {
+                            if let Ok(sink) = Self::create_audio_source(&stream_handle, &path, None) {
                                 let duration = Self::get_file_duration(&path).unwrap_or(0.0);
                                 
                                 if let Ok(mut info) = track_info.lock() {
@@ -175,15 +250,29 @@ impl CrossfadeAudioPlayer {
                                     duration,
                                     start_time: Instant::now(),
                                     is_primary: true,
+                                    stretch: None,
+                                    matched_rate: None,
                                 });
                             }
                             continue;
                         }
 
                         if let Some(ref mut current) = current_source {
-                            // Start crossfade
-                            if let Ok(new_sink) = Self::create_audio_source(&stream_handle, &path) {
+                            // Start crossfade, stretched to the outgoing tempo when beat matching
+                            let matched_rate = beat_sync.as_ref().and_then(BeatSync::rate);
+                            let stretch = matched_rate.map(StretchControl::new);
+                            if let Ok(new_sink) = Self::create_audio_source(&stream_handle, &path, stretch.as_ref()) {
                                 let duration = Self::get_file_duration(&path).unwrap_or(0.0);
+
+                                // Start the new source at low volume
+                                new_sink.set_volume(0.0);
+
+                                if let (Some(sync), Some(_)) = (&beat_sync, &stretch) {
+                                    let start = sync.start(current.position() as f32);
+                                    if let Err(e) = new_sink.try_seek(Duration::from_secs_f32(start)) {
+                                        eprintln!("Failed to align beats: {}", e);
+                                    }
+                                }
                                 
                                 if let Ok(mut info) = track_info.lock() {
                                     info.crossfade_active = true;
@@ -191,22 +280,21 @@ impl CrossfadeAudioPlayer {
                                     info.next_track = Some(path.clone());
                                 }
                                 
-                                // Start the new source at low volume
-                                new_sink.set_volume(0.0);
-                                
                                 crossfade_source = Some(AudioSource {
                                     sink: new_sink,
                                     file_path: path,
                                     duration,
                                     start_time: Instant::now(),
                                     is_primary: false,
+                                    stretch,
+                                    matched_rate,
                                 });
                                 
                                 crossfade_start_time = Some(Instant::now());
                             }
                         } else {
                             // No current source, just play normally
-                            if let Ok(sink) = Self::create_audio_source(&stream_handle, &path) {
+                            if let Ok(sink) = Self::create_audio_source(&stream_handle, &path, None) {
                                 let duration = Self::get_file_duration(&path).unwrap_or(0.0);
                                 
                                 if let Ok(mut info) = track_info.lock() {
@@ -225,6 +313,8 @@ impl CrossfadeAudioPlayer {
                                     duration,
                                     start_time: Instant::now(),
                                     is_primary: true,
+                                    stretch: None,
+                                    matched_rate: None,
                                 });
                             }
                         }
@@ -287,6 +377,12 @@ impl CrossfadeAudioPlayer {
                             config.enabled = enabled;
                         }
                     },
+
+                    CrossfadeCommand::SetBeatMatch(enabled) => {
+                        if let Ok(mut config) = crossfade_config.lock() {
+                            config.beat_match = enabled;
+                        }
+                    },
                     
                     _ => {},
                 }
@@ -328,7 +424,7 @@ impl CrossfadeAudioPlayer {
                         info.current_track = info.next_track.clone();
                         info.next_track = None;
                         info.duration = crossfade.duration;
-                        info.current_time = 0.0;
+                        info.current_time = crossfade.position();
                     }
                     
                     // Stop the old source
@@ -342,12 +438,33 @@ impl CrossfadeAudioPlayer {
                             duration: new_current.duration,
                             start_time: Instant::now(),
                             is_primary: true,
+                            stretch: new_current.stretch,
+                            matched_rate: new_current.matched_rate,
                         });
                     }
                     crossfade_start_time = None;
                 }
             }
 
+            // Ease a beat-matched track back to its own tempo once it plays alone
+            if crossfade_start_time.is_none() {
+                if let Some(ref mut current) = current_source {
+                    if let (Some(control), Some(rate)) = (&current.stretch, current.matched_rate) {
+                        let progress = (current.start_time.elapsed().as_secs_f32() / TEMPO_RELEASE_SECS).min(1.0);
+                        control.set_rate(rate + (1.0 - rate) * progress);
+                        if progress >= 1.0 {
+                            current.matched_rate = None;
+                        }
+                    }
+                }
+            }
+
+            if let Some(ref current) = current_source {
+                if let Ok(mut info) = track_info.lock() {
+                    info.current_time = current.position();
+                }
+            }
+
             thread::sleep(Duration::from_millis(50));
         }
     }
@@ -381,7 +498,7 @@ impl CrossfadeAudioPlayer {
         (fade_out * master_volume, fade_in * master_volume)
     }
 
-    fn create_audio_source(stream_handle: &OutputStreamHandle, file_path: &str) -> Result<Sink, String> {
+    fn create_audio_source(stream_handle: &OutputStreamHandle, file_path: &str, stretch: Option<&StretchControl>) -> Result<Sink, String> {
         let file = File::open(file_path).map_err(|e| format!("Failed to open file: {}", e))?;
         let mss = MediaSourceStream::new(Box::new(file), Default::default());
         
@@ -422,7 +539,10 @@ impl CrossfadeAudioPlayer {
         let file_for_source = File::open(file_path).map_err(|e| format!("Failed to open file for source: {}", e))?;
         let source = rodio::Decoder::new(file_for_source).map_err(|e| format!("Failed to create decoder: {}", e))?;
         
-        sink.append(source);
+        match stretch {
+            Some(control) => sink.append(TimeStretch::new(source.convert_samples::<f32>(), control.clone())),
+            None => sink.append(source),
+        }
         sink.pause(); // Start paused
         
         Ok(sink)
@@ -453,13 +573,15 @@ impl CrossfadeAudioPlayer {
             .map_err(|e| format!("Failed to send play command: {}", e))
     }
 
-    pub fn play_with_crossfade(&self, path: &str, crossfade_duration: Option<f32>) -> Result<(), String> {
+    /// Crossfades into `path`; given `beat_sync`, the incoming track is
+    /// time-stretched and started in phase with the outgoing one.
+    pub fn play_with_crossfade(&self, path: &str, crossfade_duration: Option<f32>, beat_sync: Option<BeatSync>) -> Result<(), String> {
         let duration = crossfade_duration.unwrap_or_else(|| {
             self.crossfade_config.lock().unwrap().duration_seconds
         });
         
         self.command_sender
-            .send(CrossfadeCommand::PlayWithCrossfade(path.to_string(), duration))
+            .send(CrossfadeCommand::PlayWithCrossfade(path.to_string(), duration, beat_sync))
             .map_err(|e| format!("Failed to send crossfade play command: {}", e))
     }
 
@@ -487,6 +609,10 @@ impl CrossfadeAudioPlayer {
         let _ = self.command_sender.send(CrossfadeCommand::EnableCrossfade(enabled));
     }
 
+    pub fn set_beat_match(&self, enabled: bool) {
+        let _ = self.command_sender.send(CrossfadeCommand::SetBeatMatch(enabled));
+    }
+
     pub fn get_track_info(&self) -> CrossfadeTrackInfo {
         self.track_info.lock().unwrap().clone()
     }
@@ -0,0 +1,144 @@
+use std::fs::File;
+use std::path::Path;
+use symphonia::core::audio::SampleBuffer;
+use symphonia::core::codecs::{Decoder, DecoderOptions, CODEC_TYPE_NULL};
+use symphonia::core::errors::Error;
+use symphonia::core::formats::{FormatOptions, FormatReader};
+use symphonia::core::io::MediaSourceStream;
+use symphonia::core::meta::MetadataOptions;
+use symphonia::core::probe::Hint;
+
+/// Format of a decoded audio stream.
+#[derive(Debug, Clone, Copy)]
+pub struct StreamInfo {
+    pub sample_rate: u32,
+    pub channels: usize,
+    /// Total length in frames, when the container reports it.
+    pub frames: Option<u64>,
+    /// Bit depth of the stored samples, for PCM-based codecs.
+    pub bits_per_sample: Option<u32>,
+}
+
+/// A whole file decoded into memory, one sample vector per channel.
+pub struct DecodedAudio {
+    pub sample_rate: u32,
+    pub channels: Vec<Vec<f32>>,
+}
+
+/// Format reader and decoder for a file's first audio track, plus the track id.
+pub type OpenTrack = (Box<dyn FormatReader>, Box<dyn Decoder>, u32);
+
+/// Opens `path` and creates a decoder for its first audio track.
+pub fn open_track(path: &str) -> Result<OpenTrack, String> {
+    open_track_with_options(path, &Default::default())
+}
+
+/// Like `open_track`, with control over decoder options such as verification.
+pub fn open_track_with_options(path: &str, dec_opts: &DecoderOptions) -> Result<OpenTrack, String> {
+    let file = File::open(path).map_err(|e| format!("Failed to open file: {}", e))?;
+    let media_source = MediaSourceStream::new(Box::new(file), Default::default());
+
+    // Create a probe hint using the file's extension
+    let mut hint = Hint::new();
+    if let Some(ext_str) = Path::new(path).extension().and_then(|e| e.to_str()) {
+        hint.with_extension(ext_str);
+    }
+
+    let meta_opts: MetadataOptions = Default::default();
+    let fmt_opts: FormatOptions = Default::default();
+
+    let probed = symphonia::default::get_probe()
+        .format(&hint, media_source, &fmt_opts, &meta_opts)
+        .map_err(|e| format!("Unsupported format: {}", e))?;
+
+    let format = probed.format;
+
+    let track = format
+        .tracks()
+        .iter()
+        .find(|t| t.codec_params.codec != CODEC_TYPE_NULL)
+        .ok_or("No supported audio tracks")?;
+
+    let track_id = track.id;
+
+    let decoder = symphonia::default::get_codecs()
+        .make(&track.codec_params, dec_opts)
+        .map_err(|e| format!("Unsupported codec: {}", e))?;
+
+    Ok((format, decoder, track_id))
+}
+
+/// Decodes `path` from start to end, handing each packet to `on_block` as
+/// interleaved f32 samples. Decoding stops early when `on_block` returns false.
+pub fn decode_interleaved(path: &str, mut on_block: impl FnMut(&StreamInfo, &[f32]) -> bool) -> Result<StreamInfo, String> {
+    let (mut format, mut decoder, track_id) = open_track(path)?;
+
+    let codec_params = &decoder.codec_params();
+    let mut info = StreamInfo {
+        sample_rate: codec_params.sample_rate.unwrap_or(44100),
+        channels: codec_params.channels.map(|c| c.count()).unwrap_or(2),
+        frames: codec_params.n_frames,
+        bits_per_sample: codec_params.bits_per_sample,
+    };
+    let mut sample_buf: Option<SampleBuffer<f32>> = None;
+
+    loop {
+        let packet = match format.next_packet() {
+            Ok(packet) => packet,
+            Err(Error::IoError(err)) if err.kind() == std::io::ErrorKind::UnexpectedEof => break,
+            Err(Error::ResetRequired) => break,
+            Err(err) => return Err(format!("Failed to read packet: {}", err)),
+        };
+        if packet.track_id() != track_id {
+            continue;
+        }
+
+        let audio_buf = match decoder.decode(&packet) {
+            Ok(audio_buf) => audio_buf,
+            Err(Error::DecodeError(err)) => {
+                eprintln!("Decode error: {}", err);
+                continue;
+            }
+            Err(err) => return Err(format!("Decoder error: {}", err)),
+        };
+
+        let spec = *audio_buf.spec();
+        info.sample_rate = spec.rate;
+        info.channels = spec.channels.count();
+
+        let buffer = match &mut sample_buf {
+            Some(buffer) if buffer.capacity() >= audio_buf.capacity() * info.channels => buffer,
+            _ => sample_buf.insert(SampleBuffer::new(audio_buf.capacity() as u64, spec)),
+        };
+        buffer.copy_interleaved_ref(audio_buf);
+
+        if !on_block(&info, buffer.samples()) {
+            break;
+        }
+    }
+
+    Ok(info)
+}
+
+/// Decodes a whole file into memory. Meant for short material such as
+/// impulse responses; long tracks should be streamed with `decode_interleaved`.
+pub fn decode_file(path: &str) -> Result<DecodedAudio, String> {
+    let mut channels: Vec<Vec<f32>> = Vec::new();
+
+    let info = decode_interleaved(path, |info, samples| {
+        if channels.len() != info.channels {
+            channels.resize(info.channels, Vec::new());
+        }
+        for frame in samples.chunks_exact(info.channels) {
+            for (channel, sample) in channels.iter_mut().zip(frame) {
+                channel.push(*sample);
+            }
+        }
+        true
+    })?;
+
+    Ok(DecodedAudio {
+        sample_rate: info.sample_rate,
+        channels,
+    })
+}
@@ -0,0 +1,286 @@
+use std::f64::consts::PI;
+
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FilterType {
+    Peak,
+    LowShelf,
+    HighShelf,
+    LowPass,
+    HighPass,
+    Notch,
+}
+
+/// Normalized biquad coefficients (a0 = 1), following the RBJ audio EQ cookbook.
+#[derive(Debug, Clone, Copy)]
+pub struct Coefficients {
+    b0: f64,
+    b1: f64,
+    b2: f64,
+    a1: f64,
+    a2: f64,
+}
+
+impl Coefficients {
+    pub fn identity() -> Self {
+        Self { b0: 1.0, b1: 0.0, b2: 0.0, a1: 0.0, a2: 0.0 }
+    }
+
+    pub fn design(filter_type: FilterType, frequency: f32, gain_db: f32, q: f32, sample_rate: f32) -> Self {
+        let sample_rate = sample_rate as f64;
+        let frequency = (frequency as f64).clamp(1.0, sample_rate * 0.499);
+        let q = (q as f64).max(0.01);
+        let a = 10f64.powf(gain_db as f64 / 40.0);
+        let w0 = 2.0 * PI * frequency / sample_rate;
+        let (sin_w0, cos_w0) = w0.sin_cos();
+        let alpha = sin_w0 / (2.0 * q);
+
+        let (b0, b1, b2, a0, a1, a2) = match filter_type {
+            FilterType::Peak => (
+                1.0 + alpha * a,
+                -2.0 * cos_w0,
+                1.0 - alpha * a,
+                1.0 + alpha / a,
+                -2.0 * cos_w0,
+                1.0 - alpha / a,
+            ),
+            FilterType::LowShelf => {
+                let sqrt_a = 2.0 * a.sqrt() * alpha;
+                (
+                    a * ((a + 1.0) - (a - 1.0) * cos_w0 + sqrt_a),
+                    2.0 * a * ((a - 1.0) - (a + 1.0) * cos_w0),
+                    a * ((a + 1.0) - (a - 1.0) * cos_w0 - sqrt_a),
+                    (a + 1.0) + (a - 1.0) * cos_w0 + sqrt_a,
+                    -2.0 * ((a - 1.0) + (a + 1.0) * cos_w0),
+                    (a + 1.0) + (a - 1.0) * cos_w0 - sqrt_a,
+                )
+            }
+            FilterType::HighShelf => {
+                let sqrt_a = 2.0 * a.sqrt() * alpha;
+                (
+                    a * ((a + 1.0) + (a - 1.0) * cos_w0 + sqrt_a),
+                    -2.0 * a * ((a - 1.0) + (a + 1.0) * cos_w0),
+                    a * ((a + 1.0) + (a - 1.0) * cos_w0 - sqrt_a),
+                    (a + 1.0) - (a - 1.0) * cos_w0 + sqrt_a,
+                    2.0 * ((a - 1.0) - (a + 1.0) * cos_w0),
+                    (a + 1.0) - (a - 1.0) * cos_w0 - sqrt_a,
+                )
+            }
+            FilterType::LowPass => (
+                (1.0 - cos_w0) / 2.0,
+                1.0 - cos_w0,
+                (1.0 - cos_w0) / 2.0,
+                1.0 + alpha,
+                -2.0 * cos_w0,
+                1.0 - alpha,
+            ),
+            FilterType::HighPass => (
+                (1.0 + cos_w0) / 2.0,
+                -(1.0 + cos_w0),
+                (1.0 + cos_w0) / 2.0,
+                1.0 + alpha,
+                -2.0 * cos_w0,
+                1.0 - alpha,
+            ),
+            FilterType::Notch => (1.0, -2.0 * cos_w0, 1.0, 1.0 + alpha, -2.0 * cos_w0, 1.0 - alpha),
+        };
+
+        Self {
+            b0: b0 / a0,
+            b1: b1 / a0,
+            b2: b2 / a0,
+            a1: a1 / a0,
+            a2: a2 / a0,
+        }
+    }
+
+    /// Gain of the filter at `frequency`, in dB.
+    pub fn magnitude_db(&self, frequency: f64, sample_rate: f64) -> f64 {
+        let w = 2.0 * PI * frequency / sample_rate;
+        let (sin_w, cos_w) = w.sin_cos();
+        let (sin_2w, cos_2w) = (2.0 * w).sin_cos();
+
+        // Evaluate numerator and denominator on the unit circle, z^-1 = e^-jw
+        let num_re = self.b0 + self.b1 * cos_w + self.b2 * cos_2w;
+        let num_im = -(self.b1 * sin_w + self.b2 * sin_2w);
+        let den_re = 1.0 + self.a1 * cos_w + self.a2 * cos_2w;
+        let den_im = -(self.a1 * sin_w + self.a2 * sin_2w);
+
+        let magnitude_sq = (num_re * num_re + num_im * num_im) / (den_re * den_re + den_im * den_im);
+        10.0 * magnitude_sq.max(1e-20).log10()
+    }
+}
+
+/// Transposed direct form II biquad with independent state per channel.
+#[derive(Debug, Clone)]
+pub struct Biquad {
+    coefficients: Coefficients,
+    state: Vec<[f64; 2]>,
+}
+
+impl Biquad {
+    pub fn new(coefficients: Coefficients, channels: usize) -> Self {
+        Self {
+            coefficients,
+            state: vec![[0.0; 2]; channels],
+        }
+    }
+
+    pub fn coefficients(&self) -> &Coefficients {
+        &self.coefficients
+    }
+
+    /// Swaps in new coefficients while keeping the filter state, so parameter
+    /// changes don't restart the filter.
+    pub fn set_coefficients(&mut self, coefficients: Coefficients) {
+        self.coefficients = coefficients;
+    }
+
+    pub fn reset(&mut self) {
+        self.state.iter_mut().for_each(|s| *s = [0.0; 2]);
+    }
+
+    #[inline]
+    pub fn process_sample(&mut self, input: f32, channel: usize) -> f32 {
+        let c = &self.coefficients;
+        let s = &mut self.state[channel];
+        let x = input as f64;
+        let y = c.b0 * x + s[0];
+        s[0] = c.b1 * x - c.a1 * y + s[1];
+        s[1] = c.b2 * x - c.a2 * y;
+        y as f32
+    }
+
+    pub fn process(&mut self, samples: &mut [f32], channels: usize) {
+        for frame in samples.chunks_exact_mut(channels) {
+            for (channel, sample) in frame.iter_mut().enumerate().take(self.state.len()) {
+                *sample = self.process_sample(*sample, channel);
+            }
+        }
+    }
+}
+
+/// Fourth-order Linkwitz-Riley filter built from two cascaded Butterworth
+/// sections. Lowpass and highpass outputs at the same frequency sum to an
+/// allpass, which makes them suitable for band splitting.
+#[derive(Debug, Clone)]
+pub struct LinkwitzRiley {
+    sections: [Biquad; 2],
+}
+
+impl LinkwitzRiley {
+    pub fn new(filter_type: FilterType, frequency: f32, sample_rate: f32, channels: usize) -> Self {
+        let coefficients = Coefficients::design(filter_type, frequency, 0.0, std::f32::consts::FRAC_1_SQRT_2, sample_rate);
+        let section = Biquad::new(coefficients, channels);
+        Self {
+            sections: [section.clone(), section],
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.sections.iter_mut().for_each(Biquad::reset);
+    }
+
+    #[inline]
+    pub fn process_sample(&mut self, input: f32, channel: usize) -> f32 {
+        let first = self.sections[0].process_sample(input, channel);
+        self.sections[1].process_sample(first, channel)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE_RATE: f32 = 48000.0;
+
+    /// Level change of a sine at `frequency` after `process`, in dB, measured
+    /// once the filter has settled.
+    fn measured_gain_db(frequency: f32, mut process: impl FnMut(f32) -> f32) -> f64 {
+        let settle = SAMPLE_RATE as usize / 2;
+        let (mut input, mut output) = (0.0f64, 0.0f64);
+        for i in 0..settle * 2 {
+            let x = 0.5 * (2.0 * std::f32::consts::PI * frequency * i as f32 / SAMPLE_RATE).sin();
+            let y = process(x);
+            if i >= settle {
+                input += (x * x) as f64;
+                output += (y * y) as f64;
+            }
+        }
+        10.0 * (output / input).log10()
+    }
+
+    #[test]
+    fn designs_hit_their_target_gains() {
+        let design = |filter_type, gain_db| Coefficients::design(filter_type, 1000.0, gain_db, std::f32::consts::FRAC_1_SQRT_2, SAMPLE_RATE);
+        let at = |c: &Coefficients, frequency: f64| c.magnitude_db(frequency, SAMPLE_RATE as f64);
+
+        let peak = design(FilterType::Peak, 6.0);
+        assert!((at(&peak, 1000.0) - 6.0).abs() < 1e-6);
+        assert!(at(&peak, 20.0).abs() < 0.05 && at(&peak, 20000.0).abs() < 0.1);
+
+        let low_shelf = design(FilterType::LowShelf, -9.0);
+        assert!((at(&low_shelf, 10.0) + 9.0).abs() < 0.05);
+        assert!((at(&low_shelf, 1000.0) + 4.5).abs() < 1e-6, "shelf midpoint");
+        assert!(at(&low_shelf, 20000.0).abs() < 0.05);
+
+        let high_shelf = design(FilterType::HighShelf, 4.0);
+        assert!(at(&high_shelf, 10.0).abs() < 0.05);
+        assert!((at(&high_shelf, 20000.0) - 4.0).abs() < 0.1);
+
+        // Butterworth sections are 3 dB down at their corner
+        let low_pass = design(FilterType::LowPass, 0.0);
+        assert!((at(&low_pass, 1000.0) + 3.0103).abs() < 1e-3);
+        assert!(at(&low_pass, 20.0).abs() < 1e-3);
+        assert!(at(&low_pass, 10000.0) < -38.0);
+
+        let high_pass = design(FilterType::HighPass, 0.0);
+        assert!((at(&high_pass, 1000.0) + 3.0103).abs() < 1e-3);
+        assert!(at(&high_pass, 20000.0).abs() < 1e-3);
+        assert!(at(&high_pass, 100.0) < -38.0);
+
+        let notch = design(FilterType::Notch, 0.0);
+        assert!(at(&notch, 1000.0) < -60.0);
+        assert!(at(&notch, 50.0).abs() < 0.05);
+    }
+
+    #[test]
+    fn processing_matches_the_computed_response() {
+        let coefficients = Coefficients::design(FilterType::Peak, 2500.0, -8.0, 2.0, SAMPLE_RATE);
+        for frequency in [200.0f32, 2000.0, 2500.0, 4000.0] {
+            let mut biquad = Biquad::new(coefficients, 1);
+            let measured = measured_gain_db(frequency, |x| biquad.process_sample(x, 0));
+            let expected = coefficients.magnitude_db(frequency as f64, SAMPLE_RATE as f64);
+            assert!((measured - expected).abs() < 0.05, "{} Hz: measured {} dB, expected {} dB", frequency, measured, expected);
+        }
+    }
+
+    #[test]
+    fn linkwitz_riley_bands_sum_flat() {
+        let mut low = LinkwitzRiley::new(FilterType::LowPass, 800.0, SAMPLE_RATE, 1);
+        let mut high = LinkwitzRiley::new(FilterType::HighPass, 800.0, SAMPLE_RATE, 1);
+        // Each band is 6 dB down at the crossover, and they add in phase
+        assert!((measured_gain_db(800.0, |x| low.process_sample(x, 0)) + 6.02).abs() < 0.05);
+        assert!((measured_gain_db(800.0, |x| high.process_sample(x, 0)) + 6.02).abs() < 0.05);
+
+        for frequency in [50.0f32, 400.0, 800.0, 1600.0, 10000.0] {
+            low.reset();
+            high.reset();
+            let sum = measured_gain_db(frequency, |x| low.process_sample(x, 0) + high.process_sample(x, 0));
+            assert!(sum.abs() < 0.01, "{} Hz: bands sum to {} dB", frequency, sum);
+        }
+    }
+
+    #[test]
+    fn channels_keep_separate_state() {
+        let coefficients = Coefficients::design(FilterType::LowPass, 500.0, 0.0, 0.7, SAMPLE_RATE);
+        let mut stereo = Biquad::new(coefficients, 2);
+        let mut mono = Biquad::new(coefficients, 1);
+        let mut samples: Vec<f32> = (0..512).flat_map(|i| [((i % 7) as f32 - 3.0) / 3.0, 0.0]).collect();
+        let left: Vec<f32> = samples.iter().step_by(2).map(|x| mono.process_sample(*x, 0)).collect();
+
+        stereo.process(&mut samples, 2);
+        assert!(samples.iter().skip(1).step_by(2).all(|y| *y == 0.0));
+        assert_eq!(samples.iter().step_by(2).copied().collect::<Vec<_>>(), left);
+    }
+}
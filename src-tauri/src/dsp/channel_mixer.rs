@@ -0,0 +1,159 @@
+use super::AudioEffect;
+use serde_json::Value;
+
+/// Time constant for balance changes, so moving the slider doesn't click.
+const SMOOTHING_SECS: f32 = 0.01;
+
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct ChannelMixerParams {
+    /// -1.0 is fully left, 1.0 fully right.
+    pub balance: f32,
+    pub mono: bool,
+    pub swap_channels: bool,
+}
+
+/// Balance, mono downmix and left/right swap for the first two channels.
+pub struct ChannelMixer {
+    params: ChannelMixerParams,
+    gains: [f32; 2],
+    coefficient: f32,
+}
+
+impl Default for ChannelMixer {
+    fn default() -> Self {
+        Self {
+            params: ChannelMixerParams::default(),
+            gains: [1.0, 1.0],
+            coefficient: 0.0,
+        }
+    }
+}
+
+impl ChannelMixer {
+    pub fn mixer_params(&self) -> &ChannelMixerParams {
+        &self.params
+    }
+
+    pub fn set_mixer_params(&mut self, mut params: ChannelMixerParams) {
+        params.balance = params.balance.clamp(-1.0, 1.0);
+        self.params = params;
+    }
+
+    /// Balance attenuates the opposite side only, so centred material keeps its level.
+    fn target_gains(&self) -> [f32; 2] {
+        let balance = self.params.balance;
+        [(1.0 - balance).min(1.0), (1.0 + balance).min(1.0)]
+    }
+}
+
+impl AudioEffect for ChannelMixer {
+    fn kind(&self) -> &'static str {
+        "channel_mixer"
+    }
+
+    fn prepare(&mut self, sample_rate: f32, _channels: usize) {
+        self.coefficient = (-1.0 / (SMOOTHING_SECS * sample_rate)).exp();
+    }
+
+    fn process(&mut self, samples: &mut [f32], channels: usize) {
+        if channels < 2 {
+            return;
+        }
+
+        let target = self.target_gains();
+        for frame in samples.chunks_exact_mut(channels) {
+            let (mut left, mut right) = (frame[0], frame[1]);
+
+            if self.params.swap_channels {
+                std::mem::swap(&mut left, &mut right);
+            }
+            if self.params.mono {
+                let mid = (left + right) * 0.5;
+                left = mid;
+                right = mid;
+            }
+
+            for (gain, target) in self.gains.iter_mut().zip(target) {
+                *gain = target + (*gain - target) * self.coefficient;
+            }
+            frame[0] = left * self.gains[0];
+            frame[1] = right * self.gains[1];
+        }
+    }
+
+    fn reset(&mut self) {
+        self.gains = self.target_gains();
+    }
+
+    fn params(&self) -> Value {
+        serde_json::to_value(&self.params).unwrap_or(Value::Null)
+    }
+
+    fn set_params(&mut self, params: Value) -> Result<(), String> {
+        let params = serde_json::from_value(params).map_err(|e| format!("Invalid channel mixer parameters: {}", e))?;
+        self.set_mixer_params(params);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mixer(params: ChannelMixerParams) -> ChannelMixer {
+        let mut mixer = ChannelMixer::default();
+        mixer.prepare(48000.0, 2);
+        mixer.set_mixer_params(params);
+        mixer.reset();
+        mixer
+    }
+
+    #[test]
+    fn swaps_and_downmixes() {
+        let mut swap = mixer(ChannelMixerParams { swap_channels: true, ..Default::default() });
+        let mut samples = [0.5, -0.25, 0.1, 0.2];
+        swap.process(&mut samples, 2);
+        assert_eq!(samples, [-0.25, 0.5, 0.2, 0.1]);
+
+        let mut mono = mixer(ChannelMixerParams { mono: true, ..Default::default() });
+        let mut samples = [0.5, -0.25];
+        mono.process(&mut samples, 2);
+        assert_eq!(samples, [0.125, 0.125]);
+    }
+
+    #[test]
+    fn balance_only_attenuates_the_far_side() {
+        let mut mixer = mixer(ChannelMixerParams { balance: 0.5, ..Default::default() });
+        let mut samples = [1.0, 1.0];
+        mixer.process(&mut samples, 2);
+        assert_eq!(samples, [0.5, 1.0]);
+
+        mixer.set_mixer_params(ChannelMixerParams { balance: -3.0, ..Default::default() });
+        assert_eq!(mixer.mixer_params().balance, -1.0);
+    }
+
+    #[test]
+    fn balance_changes_are_smoothed() {
+        let mut mixer = mixer(ChannelMixerParams::default());
+        mixer.set_mixer_params(ChannelMixerParams { balance: 1.0, ..Default::default() });
+
+        let mut samples = vec![1.0; 2 * 4800];
+        mixer.process(&mut samples, 2);
+        assert!(samples[0] > 0.9, "no jump: {}", samples[0]);
+        assert!(samples[samples.len() - 2] < 0.001);
+        assert!(samples.iter().skip(1).step_by(2).all(|right| *right == 1.0));
+    }
+
+    #[test]
+    fn leaves_other_channels_and_mono_streams_alone() {
+        let mut mixer = mixer(ChannelMixerParams { swap_channels: true, mono: true, balance: 1.0 });
+        let mut samples = [0.2, 0.4, 0.7, -0.7];
+        mixer.process(&mut samples, 4);
+        assert_eq!(&samples[2..], [0.7, -0.7]);
+
+        let mut samples = [0.3, 0.6];
+        mixer.process(&mut samples, 1);
+        assert_eq!(samples, [0.3, 0.6]);
+    }
+}
@@ -0,0 +1,207 @@
+use super::AudioEffect;
+use serde_json::Value;
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct CompressorParams {
+    pub threshold_db: f32,
+    pub ratio: f32,
+    pub attack_ms: f32,
+    pub release_ms: f32,
+    pub makeup_db: f32,
+    /// Width of the soft knee around the threshold.
+    pub knee_db: f32,
+}
+
+impl Default for CompressorParams {
+    fn default() -> Self {
+        Self {
+            threshold_db: -20.0,
+            ratio: 4.0,
+            attack_ms: 10.0,
+            release_ms: 200.0,
+            makeup_db: 0.0,
+            knee_db: 6.0,
+        }
+    }
+}
+
+impl CompressorParams {
+    /// Heavy, slow compression that lifts quiet passages for late-night listening.
+    pub fn night_mode() -> Self {
+        Self {
+            threshold_db: -32.0,
+            ratio: 6.0,
+            attack_ms: 5.0,
+            release_ms: 300.0,
+            makeup_db: 12.0,
+            knee_db: 10.0,
+        }
+    }
+
+    fn normalized(mut self) -> Self {
+        self.threshold_db = self.threshold_db.clamp(-60.0, 0.0);
+        self.ratio = self.ratio.clamp(1.0, 50.0);
+        self.attack_ms = self.attack_ms.clamp(0.1, 500.0);
+        self.release_ms = self.release_ms.clamp(5.0, 5000.0);
+        self.makeup_db = self.makeup_db.clamp(0.0, 30.0);
+        self.knee_db = self.knee_db.clamp(0.0, 24.0);
+        self
+    }
+}
+
+/// Feed-forward, stereo-linked compressor with a soft knee.
+pub struct Compressor {
+    params: CompressorParams,
+    sample_rate: f32,
+    attack: f32,
+    release: f32,
+    // Smoothed gain reduction in dB (zero or negative)
+    envelope: f32,
+}
+
+impl Default for Compressor {
+    fn default() -> Self {
+        let mut compressor = Self {
+            params: CompressorParams::default(),
+            sample_rate: 44100.0,
+            attack: 0.0,
+            release: 0.0,
+            envelope: 0.0,
+        };
+        compressor.update_coefficients();
+        compressor
+    }
+}
+
+impl Compressor {
+    pub fn compressor_params(&self) -> &CompressorParams {
+        &self.params
+    }
+
+    pub fn set_compressor_params(&mut self, params: CompressorParams) {
+        self.params = params.normalized();
+        self.update_coefficients();
+    }
+
+    fn update_coefficients(&mut self) {
+        self.attack = (-1.0 / (self.params.attack_ms * 0.001 * self.sample_rate)).exp();
+        self.release = (-1.0 / (self.params.release_ms * 0.001 * self.sample_rate)).exp();
+    }
+
+    fn gain_reduction_db(&self, level_db: f32) -> f32 {
+        let CompressorParams { threshold_db, ratio, knee_db, .. } = self.params;
+        let over = level_db - threshold_db;
+        let slope = 1.0 / ratio - 1.0;
+
+        if 2.0 * over < -knee_db {
+            0.0
+        } else if knee_db > 0.0 && 2.0 * over.abs() <= knee_db {
+            slope * (over + knee_db / 2.0).powi(2) / (2.0 * knee_db)
+        } else {
+            slope * over
+        }
+    }
+}
+
+impl AudioEffect for Compressor {
+    fn kind(&self) -> &'static str {
+        "compressor"
+    }
+
+    fn prepare(&mut self, sample_rate: f32, _channels: usize) {
+        self.sample_rate = sample_rate;
+        self.update_coefficients();
+    }
+
+    fn process(&mut self, samples: &mut [f32], channels: usize) {
+        let makeup_db = self.params.makeup_db;
+
+        for frame in samples.chunks_exact_mut(channels) {
+            let peak = frame.iter().fold(0f32, |peak, sample| peak.max(sample.abs()));
+            let level_db = 20.0 * peak.max(1e-9).log10();
+            let target = self.gain_reduction_db(level_db);
+
+            let coefficient = if target < self.envelope { self.attack } else { self.release };
+            self.envelope = target + (self.envelope - target) * coefficient;
+
+            let gain = 10f32.powf((self.envelope + makeup_db) / 20.0);
+            for sample in frame {
+                *sample *= gain;
+            }
+        }
+    }
+
+    fn reset(&mut self) {
+        self.envelope = 0.0;
+    }
+
+    fn params(&self) -> Value {
+        serde_json::to_value(&self.params).unwrap_or(Value::Null)
+    }
+
+    fn set_params(&mut self, params: Value) -> Result<(), String> {
+        let params = serde_json::from_value(params).map_err(|e| format!("Invalid compressor parameters: {}", e))?;
+        self.set_compressor_params(params);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn compressor(params: CompressorParams) -> Compressor {
+        let mut compressor = Compressor::default();
+        compressor.prepare(48000.0, 2);
+        compressor.set_compressor_params(params);
+        compressor
+    }
+
+    #[test]
+    fn hard_knee_is_finite_at_threshold() {
+        let compressor = compressor(CompressorParams { knee_db: 0.0, ..Default::default() });
+        assert_eq!(compressor.gain_reduction_db(-20.0), 0.0);
+        assert_eq!(compressor.gain_reduction_db(-30.0), 0.0);
+        assert!((compressor.gain_reduction_db(-8.0) - -9.0).abs() < 1e-5);
+    }
+
+    #[test]
+    fn soft_knee_meets_both_slopes() {
+        let compressor = compressor(CompressorParams::default());
+        let (threshold, knee) = (-20.0, 6.0);
+        assert!(compressor.gain_reduction_db(threshold - knee / 2.0).abs() < 1e-5);
+        let above = compressor.gain_reduction_db(threshold + knee / 2.0);
+        assert!((above - (0.25 - 1.0) * knee / 2.0).abs() < 1e-5);
+
+        // Reduction never shrinks as the level rises through the knee
+        let mut previous = 0.0;
+        for step in 0..=60 {
+            let reduction = compressor.gain_reduction_db(threshold - knee + step as f32 * 0.2);
+            assert!(reduction <= previous + 1e-6);
+            previous = reduction;
+        }
+    }
+
+    #[test]
+    fn steady_tone_settles_to_the_static_curve() {
+        let mut compressor = compressor(CompressorParams { knee_db: 0.0, ..Default::default() });
+        // A 0 dBFS square wave: 20 dB over, so 15 dB of reduction at 4:1
+        let mut samples: Vec<f32> = (0..96000).map(|i| if (i / 2) % 48 < 24 { 1.0 } else { -1.0 }).collect();
+        compressor.process(&mut samples, 2);
+
+        let expected = 10f32.powf(-15.0 / 20.0);
+        let last = samples.last().unwrap().abs();
+        assert!((last - expected).abs() < 1e-3, "settled at {}, expected {}", last, expected);
+    }
+
+    #[test]
+    fn quiet_signal_only_gets_makeup_gain() {
+        let mut compressor = compressor(CompressorParams { makeup_db: 6.0, ..Default::default() });
+        let mut samples = vec![0.01; 4800];
+        compressor.process(&mut samples, 2);
+
+        let makeup = 10f32.powf(6.0 / 20.0);
+        assert!(samples.iter().all(|s| (s - 0.01 * makeup).abs() < 1e-6));
+    }
+}
@@ -0,0 +1,441 @@
+use super::resample::resample;
+use super::AudioEffect;
+use crate::decode;
+use realfft::num_complex::Complex;
+use realfft::{ComplexToReal, RealFftPlanner, RealToComplex};
+use serde_json::Value;
+use std::sync::Arc;
+
+/// Partition length in frames; also the latency of the convolver.
+const BLOCK_SIZE: usize = 512;
+
+/// Longest impulse response accepted, in seconds.
+const MAX_IR_SECS: usize = 10;
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct ConvolverParams {
+    /// WAV (or any decodable) impulse response; empty for none.
+    pub path: String,
+    pub gain_db: f32,
+    /// Share of the convolved signal in the output, for reverbs.
+    pub mix: f32,
+}
+
+impl Default for ConvolverParams {
+    fn default() -> Self {
+        Self {
+            path: String::new(),
+            gain_db: 0.0,
+            mix: 1.0,
+        }
+    }
+}
+
+/// Impulse response from one input channel to one output channel, split into
+/// FFT-size partitions.
+struct ConvolutionPath {
+    input: usize,
+    output: usize,
+    partitions: Vec<Vec<Complex<f32>>>,
+}
+
+/// The impulse response resampled and partitioned for one stream format.
+struct Kernel {
+    sample_rate: u32,
+    channels: usize,
+    paths: Vec<ConvolutionPath>,
+    partition_count: usize,
+    /// Spectrum of the first path's whole impulse, for `response_db`.
+    response: Vec<Complex<f32>>,
+}
+
+impl Kernel {
+    fn build(ir: &decode::DecodedAudio, sample_rate: u32, channels: usize) -> Self {
+        let mut planner = RealFftPlanner::<f32>::new();
+        let forward = planner.plan_fft_forward(BLOCK_SIZE * 2);
+        let mut scratch = forward.make_scratch_vec();
+
+        let impulses: Vec<Vec<f32>> = ir
+            .channels
+            .iter()
+            .map(|channel| resample(channel, ir.sample_rate, sample_rate))
+            .collect();
+        let routing = Convolver::routing(impulses.len(), channels);
+
+        let paths = routing
+            .iter()
+            .map(|&(input, output, ir_channel)| {
+                let partitions = impulses[ir_channel]
+                    .chunks(BLOCK_SIZE)
+                    .map(|chunk| {
+                        let mut time = vec![0.0; BLOCK_SIZE * 2];
+                        time[..chunk.len()].copy_from_slice(chunk);
+                        let mut spectrum = forward.make_output_vec();
+                        let _ = forward.process_with_scratch(&mut time, &mut spectrum, &mut scratch);
+                        spectrum
+                    })
+                    .collect();
+                ConvolutionPath { input, output, partitions }
+            })
+            .collect();
+
+        // One transform of the whole impulse, so the response can be looked up per bin
+        let response = match routing.first() {
+            Some(&(_, _, ir_channel)) => {
+                let impulse = &impulses[ir_channel];
+                let length = impulse.len().next_power_of_two().max(BLOCK_SIZE * 2);
+                let transform = planner.plan_fft_forward(length);
+                let mut time = vec![0.0; length];
+                time[..impulse.len()].copy_from_slice(impulse);
+                let mut spectrum = transform.make_output_vec();
+                let _ = transform.process(&mut time, &mut spectrum);
+                spectrum
+            }
+            None => Vec::new(),
+        };
+
+        Self {
+            sample_rate,
+            channels,
+            paths,
+            partition_count: impulses[0].len().div_ceil(BLOCK_SIZE),
+            response,
+        }
+    }
+}
+
+/// Uniformly partitioned overlap-save convolution with impulse responses
+/// loaded from disk.
+///
+/// Mono IRs are applied to every channel and stereo IRs per channel. Four
+/// channel IRs are treated as true stereo (L→L, L→R, R→L, R→R) on stereo
+/// output. IRs are resampled to the output rate when the stream is prepared;
+/// the result is kept per stream format, so preparing again is cheap.
+pub struct Convolver {
+    params: ConvolverParams,
+    ir: Option<decode::DecodedAudio>,
+    sample_rate: f32,
+    channels: usize,
+    forward: Arc<dyn RealToComplex<f32>>,
+    inverse: Arc<dyn ComplexToReal<f32>>,
+    kernel: Option<Arc<Kernel>>,
+    kernels: Vec<Arc<Kernel>>,
+    // Per input channel: the previous block, the block being filled and the
+    // spectra of past blocks (frequency-domain delay line)
+    previous: Vec<Vec<f32>>,
+    input: Vec<Vec<f32>>,
+    spectra: Vec<Vec<Vec<Complex<f32>>>>,
+    spectra_pos: usize,
+    // Per output channel: the wet block being played and the matching dry input
+    wet: Vec<Vec<f32>>,
+    dry: Vec<Vec<f32>>,
+    position: usize,
+    time: Vec<f32>,
+    accumulator: Vec<Complex<f32>>,
+    scratch: Vec<Complex<f32>>,
+}
+
+impl Default for Convolver {
+    fn default() -> Self {
+        let mut planner = RealFftPlanner::<f32>::new();
+        let forward = planner.plan_fft_forward(BLOCK_SIZE * 2);
+        let inverse = planner.plan_fft_inverse(BLOCK_SIZE * 2);
+        let scratch_len = forward.get_scratch_len().max(inverse.get_scratch_len());
+
+        Self {
+            params: ConvolverParams::default(),
+            ir: None,
+            sample_rate: 44100.0,
+            channels: 2,
+            forward,
+            inverse,
+            kernel: None,
+            kernels: Vec::new(),
+            previous: Vec::new(),
+            input: Vec::new(),
+            spectra: Vec::new(),
+            spectra_pos: 0,
+            wet: Vec::new(),
+            dry: Vec::new(),
+            position: 0,
+            time: vec![0.0; BLOCK_SIZE * 2],
+            accumulator: vec![Complex::default(); BLOCK_SIZE + 1],
+            scratch: vec![Complex::default(); scratch_len],
+        }
+    }
+}
+
+impl Convolver {
+    pub fn convolver_params(&self) -> &ConvolverParams {
+        &self.params
+    }
+
+    /// Creates a convolver with the impulse response already read from disk,
+    /// so only the cheap setup is left for when it joins the chain.
+    pub fn with_params(params: ConvolverParams) -> Result<Self, String> {
+        let mut convolver = Self::default();
+        convolver.apply_params(params)?;
+        Ok(convolver)
+    }
+
+    pub fn set_convolver_params(&mut self, params: ConvolverParams) -> Result<(), String> {
+        if self.apply_params(params)? {
+            self.rebuild();
+        }
+        Ok(())
+    }
+
+    fn kernel_for(&mut self, sample_rate: u32, channels: usize) -> Option<Arc<Kernel>> {
+        let ir = self.ir.as_ref()?;
+        if let Some(kernel) = self.kernels.iter().find(|k| k.sample_rate == sample_rate && k.channels == channels) {
+            return Some(Arc::clone(kernel));
+        }
+        let kernel = Arc::new(Kernel::build(ir, sample_rate, channels));
+        self.kernels.push(Arc::clone(&kernel));
+        Some(kernel)
+    }
+
+    /// Stores new settings, reading the impulse response if the path changed.
+    /// Returns whether the impulse response changed.
+    fn apply_params(&mut self, params: ConvolverParams) -> Result<bool, String> {
+        let params = ConvolverParams {
+            mix: params.mix.clamp(0.0, 1.0),
+            gain_db: params.gain_db.clamp(-48.0, 24.0),
+            ..params
+        };
+
+        let changed = params.path != self.params.path;
+        if changed {
+            self.ir = if params.path.is_empty() {
+                None
+            } else {
+                Some(Self::load_ir(&params.path)?)
+            };
+            self.kernels.clear();
+        }
+        self.params = params;
+        Ok(changed)
+    }
+
+    fn load_ir(path: &str) -> Result<decode::DecodedAudio, String> {
+        let ir = decode::decode_file(path).map_err(|e| format!("Failed to load impulse response: {}", e))?;
+
+        let length = ir.channels.first().map(|c| c.len()).unwrap_or(0);
+        if length == 0 {
+            return Err("Impulse response is empty".to_string());
+        }
+        if length > ir.sample_rate as usize * MAX_IR_SECS {
+            return Err(format!("Impulse response is longer than {} seconds", MAX_IR_SECS));
+        }
+        Ok(ir)
+    }
+
+    /// Which IR channel feeds which output channel from which input channel.
+    fn routing(ir_channels: usize, channels: usize) -> Vec<(usize, usize, usize)> {
+        if ir_channels == 4 && channels == 2 {
+            return vec![(0, 0, 0), (0, 1, 1), (1, 0, 2), (1, 1, 3)];
+        }
+        (0..channels)
+            .map(|channel| {
+                // Without true stereo output, use the direct paths of a true-stereo IR
+                let ir_channel = if ir_channels == 4 { (channel % 2) * 3 } else { channel % ir_channels };
+                (channel, channel, ir_channel)
+            })
+            .collect()
+    }
+
+    fn rebuild(&mut self) {
+        let channels = self.channels;
+        self.kernel = self.kernel_for(self.sample_rate as u32, channels);
+        let partition_count = self.kernel.as_ref().map_or(0, |kernel| kernel.partition_count);
+
+        self.previous = vec![vec![0.0; BLOCK_SIZE]; channels];
+        self.input = vec![vec![0.0; BLOCK_SIZE]; channels];
+        self.spectra = vec![vec![vec![Complex::default(); BLOCK_SIZE + 1]; partition_count]; channels];
+        self.wet = vec![vec![0.0; BLOCK_SIZE]; channels];
+        self.dry = vec![vec![0.0; BLOCK_SIZE]; channels];
+        self.reset();
+    }
+
+    /// Convolves the block that just filled up and makes it the next output block.
+    fn process_block(&mut self) {
+        let Some(kernel) = &self.kernel else {
+            return;
+        };
+        let partitions = kernel.partition_count;
+        self.spectra_pos = (self.spectra_pos + partitions - 1) % partitions;
+
+        for channel in 0..self.channels {
+            self.time[..BLOCK_SIZE].copy_from_slice(&self.previous[channel]);
+            self.time[BLOCK_SIZE..].copy_from_slice(&self.input[channel]);
+            self.previous[channel].copy_from_slice(&self.input[channel]);
+            let spectrum = &mut self.spectra[channel][self.spectra_pos];
+            let _ = self.forward.process_with_scratch(&mut self.time, spectrum, &mut self.scratch);
+        }
+
+        let scale = 1.0 / (BLOCK_SIZE * 2) as f32;
+        for output in 0..self.channels {
+            self.accumulator.iter_mut().for_each(|bin| *bin = Complex::default());
+
+            for path in kernel.paths.iter().filter(|path| path.output == output) {
+                let spectra = &self.spectra[path.input];
+                for (p, partition) in path.partitions.iter().enumerate() {
+                    let spectrum = &spectra[(self.spectra_pos + p) % partitions];
+                    for ((acc, x), h) in self.accumulator.iter_mut().zip(spectrum).zip(partition) {
+                        *acc += x * h;
+                    }
+                }
+            }
+
+            // Rounding leaves tiny imaginary parts the inverse transform rejects
+            self.accumulator[0].im = 0.0;
+            self.accumulator[BLOCK_SIZE].im = 0.0;
+            let wet = &mut self.wet[output];
+            match self.inverse.process_with_scratch(&mut self.accumulator, &mut self.time, &mut self.scratch) {
+                Ok(()) => {
+                    for (sample, value) in wet.iter_mut().zip(&self.time[BLOCK_SIZE..]) {
+                        *sample = value * scale;
+                    }
+                }
+                Err(_) => wet.fill(0.0),
+            }
+            self.dry[output].copy_from_slice(&self.input[output]);
+        }
+    }
+}
+
+impl AudioEffect for Convolver {
+    fn kind(&self) -> &'static str {
+        "convolver"
+    }
+
+    fn prepare(&mut self, sample_rate: f32, channels: usize) {
+        self.sample_rate = sample_rate;
+        self.channels = channels;
+        self.rebuild();
+    }
+
+    /// Resamples and partitions the impulse response for the stream format.
+    fn preload(&mut self, sample_rate: f32, channels: usize) {
+        self.kernel_for(sample_rate as u32, channels);
+    }
+
+    fn process(&mut self, samples: &mut [f32], channels: usize) {
+        if self.kernel.is_none() || channels != self.channels {
+            return;
+        }
+
+        let gain = 10f32.powf(self.params.gain_db / 20.0) * self.params.mix;
+        let dry_gain = 1.0 - self.params.mix;
+
+        for frame in samples.chunks_exact_mut(channels) {
+            for (channel, sample) in frame.iter_mut().enumerate() {
+                self.input[channel][self.position] = *sample;
+                *sample = self.wet[channel][self.position] * gain + self.dry[channel][self.position] * dry_gain;
+            }
+
+            self.position += 1;
+            if self.position == BLOCK_SIZE {
+                self.process_block();
+                self.position = 0;
+            }
+        }
+    }
+
+    fn reset(&mut self) {
+        for buffer in self.previous.iter_mut().chain(&mut self.input).chain(&mut self.wet).chain(&mut self.dry) {
+            buffer.fill(0.0);
+        }
+        for spectrum in self.spectra.iter_mut().flatten() {
+            spectrum.fill(Complex::default());
+        }
+        self.spectra_pos = 0;
+        self.position = 0;
+    }
+
+    fn latency(&self) -> usize {
+        if self.kernel.is_none() {
+            0
+        } else {
+            BLOCK_SIZE
+        }
+    }
+
+    fn response_db(&self, frequency: f64) -> f64 {
+        // Response of the first channel's direct path, including the dry share
+        let Some(kernel) = &self.kernel else {
+            return 0.0;
+        };
+
+        let bins = kernel.response.len() - 1;
+        let bin = ((frequency / (self.sample_rate as f64 / 2.0)) * bins as f64).round() as usize;
+        let value = kernel.response[bin.min(bins)];
+        let (re, im) = (value.re as f64, value.im as f64);
+
+        let gain = 10f64.powf(self.params.gain_db as f64 / 20.0) * self.params.mix as f64;
+        let dry = 1.0 - self.params.mix as f64;
+        let (re, im) = (re * gain + dry, im * gain);
+        10.0 * (re * re + im * im).max(1e-20).log10()
+    }
+
+    fn params(&self) -> Value {
+        serde_json::to_value(&self.params).unwrap_or(Value::Null)
+    }
+
+    fn set_params(&mut self, params: Value) -> Result<(), String> {
+        let params = serde_json::from_value(params).map_err(|e| format!("Invalid convolver parameters: {}", e))?;
+        self.set_convolver_params(params)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{sine, write_wav};
+
+    fn identity(name: &str) -> Convolver {
+        let mut impulse = vec![0.0; 64];
+        impulse[0] = 1.0;
+        let path = write_wav(name, 48000, 1, &impulse);
+        Convolver::with_params(ConvolverParams {
+            path: path.to_string_lossy().into_owned(),
+            ..Default::default()
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn identity_ir_passes_signal_through() {
+        let mut convolver = identity("convolver-identity");
+        convolver.prepare(48000.0, 2);
+        assert_eq!(convolver.latency(), BLOCK_SIZE);
+
+        let input: Vec<f32> = sine(1000.0, 48000, 0.1, 0.5).into_iter().flat_map(|s| [s, -s]).collect();
+        let mut output = input.clone();
+        for chunk in output.chunks_mut(300) {
+            convolver.process(chunk, 2);
+        }
+
+        let delay = BLOCK_SIZE * 2;
+        assert!(output[..delay].iter().all(|s| *s == 0.0));
+        for (out, original) in output[delay..].iter().zip(&input) {
+            assert!((out - original).abs() < 1e-3, "{} instead of {}", out, original);
+        }
+        assert!(convolver.response_db(1000.0).abs() < 0.01);
+    }
+
+    #[test]
+    fn preloaded_kernel_is_reused() {
+        let mut convolver = identity("convolver-preload");
+        convolver.preload(44100.0, 2);
+        let preloaded = Arc::clone(&convolver.kernels[0]);
+
+        convolver.prepare(44100.0, 2);
+        assert!(Arc::ptr_eq(convolver.kernel.as_ref().unwrap(), &preloaded));
+        convolver.prepare(48000.0, 2);
+        convolver.prepare(44100.0, 2);
+        assert!(Arc::ptr_eq(convolver.kernel.as_ref().unwrap(), &preloaded));
+        assert_eq!(convolver.kernels.len(), 2);
+    }
+}
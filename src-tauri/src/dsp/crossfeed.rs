@@ -0,0 +1,237 @@
+use super::AudioEffect;
+use serde_json::Value;
+use std::f64::consts::PI;
+
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CrossfeedPreset {
+    /// bs2b default: 700 Hz, 4.5 dB
+    Default,
+    /// Chu Moy's circuit: 700 Hz, 6 dB
+    ChuMoy,
+    /// Jan Meier's circuit: 650 Hz, 9.5 dB
+    JanMeier,
+    Custom,
+}
+
+impl CrossfeedPreset {
+    fn levels(self) -> Option<(f32, f32)> {
+        match self {
+            CrossfeedPreset::Default => Some((700.0, 4.5)),
+            CrossfeedPreset::ChuMoy => Some((700.0, 6.0)),
+            CrossfeedPreset::JanMeier => Some((650.0, 9.5)),
+            CrossfeedPreset::Custom => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct CrossfeedParams {
+    pub preset: CrossfeedPreset,
+    /// Cut frequency of the crossfeed lowpass; only used by the custom preset.
+    pub cutoff_hz: f32,
+    /// Level difference between direct and crossfed signal at low frequencies.
+    pub feed_db: f32,
+}
+
+impl Default for CrossfeedParams {
+    fn default() -> Self {
+        Self {
+            preset: CrossfeedPreset::Default,
+            cutoff_hz: 700.0,
+            feed_db: 4.5,
+        }
+    }
+}
+
+impl CrossfeedParams {
+    fn normalized(mut self) -> Self {
+        if let Some((cutoff_hz, feed_db)) = self.preset.levels() {
+            self.cutoff_hz = cutoff_hz;
+            self.feed_db = feed_db;
+        }
+        self.cutoff_hz = self.cutoff_hz.clamp(300.0, 2000.0);
+        self.feed_db = self.feed_db.clamp(1.0, 15.0);
+        self
+    }
+}
+
+/// Bauer stereophonic-to-binaural crossfeed, after Boris Mikhaylov's bs2b.
+///
+/// Each ear gets the opposite channel through a first-order lowpass, while the
+/// direct channel passes a matching high boost so the overall tone stays flat.
+pub struct Crossfeed {
+    params: CrossfeedParams,
+    sample_rate: f32,
+    a0_lo: f64,
+    b1_lo: f64,
+    a0_hi: f64,
+    a1_hi: f64,
+    b1_hi: f64,
+    gain: f64,
+    lowpass: [f64; 2],
+    highboost: [f64; 2],
+    previous: [f64; 2],
+}
+
+impl Default for Crossfeed {
+    fn default() -> Self {
+        let mut crossfeed = Self {
+            params: CrossfeedParams::default(),
+            sample_rate: 44100.0,
+            a0_lo: 0.0,
+            b1_lo: 0.0,
+            a0_hi: 1.0,
+            a1_hi: 0.0,
+            b1_hi: 0.0,
+            gain: 1.0,
+            lowpass: [0.0; 2],
+            highboost: [0.0; 2],
+            previous: [0.0; 2],
+        };
+        crossfeed.update_coefficients();
+        crossfeed
+    }
+}
+
+impl Crossfeed {
+    pub fn crossfeed_params(&self) -> &CrossfeedParams {
+        &self.params
+    }
+
+    pub fn set_crossfeed_params(&mut self, params: CrossfeedParams) {
+        self.params = params.normalized();
+        self.update_coefficients();
+    }
+
+    fn update_coefficients(&mut self) {
+        let feed = self.params.feed_db as f64;
+        let cutoff_lo = self.params.cutoff_hz as f64;
+        let sample_rate = self.sample_rate as f64;
+
+        let gain_lo_db = feed * -5.0 / 6.0 - 3.0;
+        let gain_hi_db = feed / 6.0 - 3.0;
+        let gain_lo = 10f64.powf(gain_lo_db / 20.0);
+        let gain_hi = 1.0 - 10f64.powf(gain_hi_db / 20.0);
+        let cutoff_hi = cutoff_lo * 2f64.powf((gain_lo_db - 20.0 * gain_hi.log10()) / 12.0);
+
+        let x = (-2.0 * PI * cutoff_lo / sample_rate).exp();
+        self.b1_lo = x;
+        self.a0_lo = gain_lo * (1.0 - x);
+
+        let x = (-2.0 * PI * cutoff_hi / sample_rate).exp();
+        self.b1_hi = x;
+        self.a0_hi = 1.0 - gain_hi * (1.0 - x);
+        self.a1_hi = -x;
+
+        self.gain = 1.0 / (1.0 - gain_hi + gain_lo);
+    }
+}
+
+impl AudioEffect for Crossfeed {
+    fn kind(&self) -> &'static str {
+        "crossfeed"
+    }
+
+    fn prepare(&mut self, sample_rate: f32, _channels: usize) {
+        self.sample_rate = sample_rate;
+        self.update_coefficients();
+    }
+
+    fn process(&mut self, samples: &mut [f32], channels: usize) {
+        if channels < 2 {
+            return;
+        }
+
+        for frame in samples.chunks_exact_mut(channels) {
+            let input = [frame[0] as f64, frame[1] as f64];
+            for (ch, x) in input.iter().enumerate() {
+                self.lowpass[ch] = self.a0_lo * x + self.b1_lo * self.lowpass[ch];
+                self.highboost[ch] = self.a0_hi * x + self.a1_hi * self.previous[ch] + self.b1_hi * self.highboost[ch];
+            }
+            self.previous = input;
+
+            frame[0] = ((self.highboost[0] + self.lowpass[1]) * self.gain) as f32;
+            frame[1] = ((self.highboost[1] + self.lowpass[0]) * self.gain) as f32;
+        }
+    }
+
+    fn reset(&mut self) {
+        self.lowpass = [0.0; 2];
+        self.highboost = [0.0; 2];
+        self.previous = [0.0; 2];
+    }
+
+    fn params(&self) -> Value {
+        serde_json::to_value(&self.params).unwrap_or(Value::Null)
+    }
+
+    fn set_params(&mut self, params: Value) -> Result<(), String> {
+        let params = serde_json::from_value(params).map_err(|e| format!("Invalid crossfeed parameters: {}", e))?;
+        self.set_crossfeed_params(params);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn prepared(params: CrossfeedParams) -> Crossfeed {
+        let mut crossfeed = Crossfeed::default();
+        crossfeed.prepare(48000.0, 2);
+        crossfeed.set_crossfeed_params(params);
+        crossfeed
+    }
+
+    /// Last frame after feeding a constant frame long enough to settle.
+    fn settle(crossfeed: &mut Crossfeed, frame: [f32; 2]) -> [f32; 2] {
+        let mut samples: Vec<f32> = frame.iter().copied().cycle().take(2 * 48000).collect();
+        crossfeed.process(&mut samples, 2);
+        [samples[samples.len() - 2], samples[samples.len() - 1]]
+    }
+
+    #[test]
+    fn presets_override_custom_levels() {
+        let params = CrossfeedParams { preset: CrossfeedPreset::JanMeier, cutoff_hz: 1500.0, feed_db: 2.0 };
+        let crossfeed = prepared(params);
+        assert_eq!(crossfeed.crossfeed_params().cutoff_hz, 650.0);
+        assert_eq!(crossfeed.crossfeed_params().feed_db, 9.5);
+
+        let params = CrossfeedParams { preset: CrossfeedPreset::Custom, cutoff_hz: 50.0, feed_db: 40.0 };
+        let crossfeed = prepared(params);
+        assert_eq!(crossfeed.crossfeed_params().cutoff_hz, 300.0);
+        assert_eq!(crossfeed.crossfeed_params().feed_db, 15.0);
+    }
+
+    #[test]
+    fn centred_low_frequencies_keep_their_level() {
+        let mut crossfeed = prepared(CrossfeedParams::default());
+        let [left, right] = settle(&mut crossfeed, [0.5, 0.5]);
+        assert!((left - 0.5).abs() < 1e-4 && (right - 0.5).abs() < 1e-4, "{} {}", left, right);
+    }
+
+    #[test]
+    fn opposite_ear_hears_bass_feed_db_lower() {
+        for preset in [CrossfeedPreset::Default, CrossfeedPreset::ChuMoy, CrossfeedPreset::JanMeier] {
+            let mut crossfeed = prepared(CrossfeedParams { preset, ..Default::default() });
+            let [left, right] = settle(&mut crossfeed, [0.5, 0.0]);
+            let difference = 20.0 * (left / right).log10();
+            let feed_db = crossfeed.crossfeed_params().feed_db;
+            assert!((difference - feed_db).abs() < 0.01, "{:?}: {} dB", preset, difference);
+        }
+    }
+
+    #[test]
+    fn high_frequencies_stay_on_their_side() {
+        let mut crossfeed = prepared(CrossfeedParams::default());
+        let mut samples: Vec<f32> = (0..4800).flat_map(|i| [if i % 2 == 0 { 0.5 } else { -0.5 }, 0.0]).collect();
+        crossfeed.process(&mut samples, 2);
+
+        let tail = &samples[samples.len() - 200..];
+        let left = tail.iter().step_by(2).fold(0f32, |peak, s| peak.max(s.abs()));
+        let right = tail.iter().skip(1).step_by(2).fold(0f32, |peak, s| peak.max(s.abs()));
+        assert!(right < left * 0.05, "left {} right {}", left, right);
+    }
+}
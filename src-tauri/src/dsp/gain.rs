@@ -0,0 +1,71 @@
+use super::AudioEffect;
+use serde_json::Value;
+
+/// Time constant of the gain smoothing, so parameter changes don't zipper.
+const SMOOTHING_SECS: f32 = 0.01;
+
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct GainParams {
+    pub gain_db: f32,
+}
+
+/// Static gain stage, mostly useful as a trim between other effects.
+pub struct Gain {
+    params: GainParams,
+    current: f32,
+    coefficient: f32,
+}
+
+impl Default for Gain {
+    fn default() -> Self {
+        Self {
+            params: GainParams::default(),
+            current: 1.0,
+            coefficient: 0.0,
+        }
+    }
+}
+
+impl Gain {
+    fn target(&self) -> f32 {
+        10f32.powf(self.params.gain_db / 20.0)
+    }
+}
+
+impl AudioEffect for Gain {
+    fn kind(&self) -> &'static str {
+        "gain"
+    }
+
+    fn prepare(&mut self, sample_rate: f32, _channels: usize) {
+        self.coefficient = (-1.0 / (SMOOTHING_SECS * sample_rate)).exp();
+    }
+
+    fn process(&mut self, samples: &mut [f32], channels: usize) {
+        let target = self.target();
+        for frame in samples.chunks_exact_mut(channels) {
+            self.current = target + (self.current - target) * self.coefficient;
+            for sample in frame {
+                *sample *= self.current;
+            }
+        }
+    }
+
+    fn reset(&mut self) {
+        self.current = self.target();
+    }
+
+    fn response_db(&self, _frequency: f64) -> f64 {
+        self.params.gain_db as f64
+    }
+
+    fn params(&self) -> Value {
+        serde_json::to_value(&self.params).unwrap_or(Value::Null)
+    }
+
+    fn set_params(&mut self, params: Value) -> Result<(), String> {
+        self.params = serde_json::from_value(params).map_err(|e| format!("Invalid gain parameters: {}", e))?;
+        Ok(())
+    }
+}
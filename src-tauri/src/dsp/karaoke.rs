@@ -0,0 +1,228 @@
+use super::biquad::{FilterType, LinkwitzRiley};
+use super::AudioEffect;
+use serde_json::Value;
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct KaraokeParams {
+    /// How much of the center channel is removed in the vocal band (0–1).
+    pub amount: f32,
+    /// Below this frequency the signal is left alone, so kick and bass survive.
+    pub low_cut_hz: f32,
+    /// Above this frequency the signal is left alone, keeping cymbals and air.
+    pub high_cut_hz: f32,
+}
+
+impl Default for KaraokeParams {
+    fn default() -> Self {
+        Self {
+            amount: 1.0,
+            low_cut_hz: 150.0,
+            high_cut_hz: 8000.0,
+        }
+    }
+}
+
+impl KaraokeParams {
+    fn normalized(mut self) -> Self {
+        self.amount = self.amount.clamp(0.0, 1.0);
+        self.low_cut_hz = self.low_cut_hz.clamp(40.0, 1000.0);
+        self.high_cut_hz = self.high_cut_hz.clamp(self.low_cut_hz * 2.0, 20000.0);
+        self
+    }
+}
+
+/// Vocal reduction by center-channel cancellation within a frequency band.
+///
+/// Left and right are split into three bands with Linkwitz-Riley crossovers.
+/// In the middle band the mid (L+R) component is attenuated, which removes
+/// center-panned vocals; the low and high bands pass unchanged. The low band
+/// goes through the upper crossover's allpass so all bands stay in phase.
+pub struct Karaoke {
+    params: KaraokeParams,
+    sample_rate: f32,
+    low_lowpass: LinkwitzRiley,
+    low_highpass: LinkwitzRiley,
+    high_lowpass: LinkwitzRiley,
+    high_highpass: LinkwitzRiley,
+    allpass_lowpass: LinkwitzRiley,
+    allpass_highpass: LinkwitzRiley,
+}
+
+impl Default for Karaoke {
+    fn default() -> Self {
+        let params = KaraokeParams::default();
+        let sample_rate = 44100.0;
+        let lowpass = LinkwitzRiley::new(FilterType::LowPass, params.low_cut_hz, sample_rate, 2);
+        let highpass = LinkwitzRiley::new(FilterType::HighPass, params.low_cut_hz, sample_rate, 2);
+        let mut karaoke = Self {
+            params,
+            sample_rate,
+            low_lowpass: lowpass.clone(),
+            low_highpass: highpass.clone(),
+            high_lowpass: lowpass.clone(),
+            high_highpass: highpass.clone(),
+            allpass_lowpass: lowpass,
+            allpass_highpass: highpass,
+        };
+        karaoke.rebuild();
+        karaoke
+    }
+}
+
+impl Karaoke {
+    pub fn karaoke_params(&self) -> &KaraokeParams {
+        &self.params
+    }
+
+    pub fn set_karaoke_params(&mut self, params: KaraokeParams) {
+        self.params = params.normalized();
+        self.rebuild();
+    }
+
+    fn rebuild(&mut self) {
+        let KaraokeParams { low_cut_hz, high_cut_hz, .. } = self.params;
+        let sample_rate = self.sample_rate;
+        let high_cut_hz = high_cut_hz.min(sample_rate * 0.45);
+
+        self.low_lowpass = LinkwitzRiley::new(FilterType::LowPass, low_cut_hz, sample_rate, 2);
+        self.low_highpass = LinkwitzRiley::new(FilterType::HighPass, low_cut_hz, sample_rate, 2);
+        self.high_lowpass = LinkwitzRiley::new(FilterType::LowPass, high_cut_hz, sample_rate, 2);
+        self.high_highpass = LinkwitzRiley::new(FilterType::HighPass, high_cut_hz, sample_rate, 2);
+        self.allpass_lowpass = LinkwitzRiley::new(FilterType::LowPass, high_cut_hz, sample_rate, 2);
+        self.allpass_highpass = LinkwitzRiley::new(FilterType::HighPass, high_cut_hz, sample_rate, 2);
+    }
+
+    /// Splits one channel into its low, vocal and high bands.
+    fn split(&mut self, input: f32, channel: usize) -> (f32, f32, f32) {
+        let low = self.low_lowpass.process_sample(input, channel);
+        let rest = self.low_highpass.process_sample(input, channel);
+        let low = self.allpass_lowpass.process_sample(low, channel) + self.allpass_highpass.process_sample(low, channel);
+        let band = self.high_lowpass.process_sample(rest, channel);
+        let high = self.high_highpass.process_sample(rest, channel);
+        (low, band, high)
+    }
+}
+
+impl AudioEffect for Karaoke {
+    fn kind(&self) -> &'static str {
+        "karaoke"
+    }
+
+    fn prepare(&mut self, sample_rate: f32, _channels: usize) {
+        self.sample_rate = sample_rate;
+        self.rebuild();
+    }
+
+    fn process(&mut self, samples: &mut [f32], channels: usize) {
+        if channels < 2 {
+            return;
+        }
+
+        let keep = 1.0 - self.params.amount;
+        for frame in samples.chunks_exact_mut(channels) {
+            let (low_l, band_l, high_l) = self.split(frame[0], 0);
+            let (low_r, band_r, high_r) = self.split(frame[1], 1);
+
+            let mid = (band_l + band_r) * 0.5 * keep;
+            let side = (band_l - band_r) * 0.5;
+
+            frame[0] = low_l + mid + side + high_l;
+            frame[1] = low_r + mid - side + high_r;
+        }
+    }
+
+    fn reset(&mut self) {
+        for filter in [
+            &mut self.low_lowpass,
+            &mut self.low_highpass,
+            &mut self.high_lowpass,
+            &mut self.high_highpass,
+            &mut self.allpass_lowpass,
+            &mut self.allpass_highpass,
+        ] {
+            filter.reset();
+        }
+    }
+
+    fn params(&self) -> Value {
+        serde_json::to_value(&self.params).unwrap_or(Value::Null)
+    }
+
+    fn set_params(&mut self, params: Value) -> Result<(), String> {
+        let params = serde_json::from_value(params).map_err(|e| format!("Invalid karaoke parameters: {}", e))?;
+        self.set_karaoke_params(params);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f32::consts::PI;
+
+    /// Output RMS over input RMS for left, right after processing one second of
+    /// a sine with the given left and right amplitudes.
+    fn level(karaoke: &mut Karaoke, frequency: f32, left: f32, right: f32) -> f32 {
+        let sample_rate = 48000.0;
+        let mut samples: Vec<f32> = (0..48000)
+            .flat_map(|i| {
+                let value = (2.0 * PI * frequency * i as f32 / sample_rate).sin();
+                [left * value, right * value]
+            })
+            .collect();
+        karaoke.process(&mut samples, 2);
+
+        // Skip the filters' settling time
+        let tail = &samples[samples.len() / 2..];
+        let rms = (tail.iter().map(|s| s * s).sum::<f32>() / tail.len() as f32).sqrt();
+        let input_rms = ((left * left + right * right) / 4.0).sqrt();
+        rms / input_rms
+    }
+
+    fn prepared(params: KaraokeParams) -> Karaoke {
+        let mut karaoke = Karaoke::default();
+        karaoke.prepare(48000.0, 2);
+        karaoke.set_karaoke_params(params);
+        karaoke
+    }
+
+    #[test]
+    fn removes_centre_vocals_in_the_band() {
+        let mut karaoke = prepared(KaraokeParams::default());
+        let ratio = level(&mut karaoke, 1000.0, 0.5, 0.5);
+        assert!(ratio < 0.05, "centre only {} dB down", 20.0 * ratio.log10());
+    }
+
+    #[test]
+    fn keeps_side_signal_and_centre_bass() {
+        let mut karaoke = prepared(KaraokeParams::default());
+        let side = level(&mut karaoke, 1000.0, 0.5, -0.5);
+        assert!((side - 1.0).abs() < 0.02, "side {}", side);
+
+        let mut karaoke = prepared(KaraokeParams::default());
+        let bass = level(&mut karaoke, 40.0, 0.5, 0.5);
+        assert!(bass > 0.9, "bass {}", bass);
+    }
+
+    #[test]
+    fn zero_amount_leaves_levels_alone() {
+        let mut karaoke = prepared(KaraokeParams { amount: 0.0, ..Default::default() });
+        for frequency in [100.0, 1000.0, 12000.0] {
+            let ratio = level(&mut karaoke, frequency, 0.5, 0.5);
+            assert!((ratio - 1.0).abs() < 0.02, "{} Hz: {}", frequency, ratio);
+        }
+    }
+
+    #[test]
+    fn normalizes_parameters_and_skips_mono() {
+        let params = KaraokeParams { amount: 2.0, low_cut_hz: 800.0, high_cut_hz: 1000.0 };
+        let mut karaoke = prepared(params);
+        assert_eq!(karaoke.karaoke_params().amount, 1.0);
+        assert_eq!(karaoke.karaoke_params().high_cut_hz, 1600.0);
+
+        let mut samples = [0.5, 0.25, -0.5];
+        karaoke.process(&mut samples, 1);
+        assert_eq!(samples, [0.5, 0.25, -0.5]);
+    }
+}
@@ -0,0 +1,696 @@
+//! Host for LADSPA plugins found on `LADSPA_PATH`.
+
+use super::AudioEffect;
+use libloading::Library;
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::ffi::{c_char, c_int, c_ulong, c_void, CStr};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// Frames handed to the plugin per `run` call.
+const MAX_BLOCK: usize = 1024;
+
+/// Searched when `LADSPA_PATH` isn't set.
+const DEFAULT_PATHS: [&str; 3] = ["/usr/lib/ladspa", "/usr/local/lib/ladspa", "/usr/lib64/ladspa"];
+
+const PORT_INPUT: c_int = 0x1;
+const PORT_OUTPUT: c_int = 0x2;
+const PORT_CONTROL: c_int = 0x4;
+const PORT_AUDIO: c_int = 0x8;
+
+const HINT_BOUNDED_BELOW: c_int = 0x1;
+const HINT_BOUNDED_ABOVE: c_int = 0x2;
+const HINT_TOGGLED: c_int = 0x4;
+const HINT_SAMPLE_RATE: c_int = 0x8;
+const HINT_LOGARITHMIC: c_int = 0x10;
+const HINT_INTEGER: c_int = 0x20;
+const HINT_DEFAULT_MASK: c_int = 0x3c0;
+const HINT_DEFAULT_MINIMUM: c_int = 0x40;
+const HINT_DEFAULT_LOW: c_int = 0x80;
+const HINT_DEFAULT_MIDDLE: c_int = 0xc0;
+const HINT_DEFAULT_HIGH: c_int = 0x100;
+const HINT_DEFAULT_MAXIMUM: c_int = 0x140;
+const HINT_DEFAULT_0: c_int = 0x200;
+const HINT_DEFAULT_1: c_int = 0x240;
+const HINT_DEFAULT_100: c_int = 0x280;
+const HINT_DEFAULT_440: c_int = 0x2c0;
+
+type Handle = *mut c_void;
+
+#[repr(C)]
+struct PortRangeHint {
+    hint_descriptor: c_int,
+    lower_bound: f32,
+    upper_bound: f32,
+}
+
+/// `LADSPA_Descriptor` from ladspa.h.
+#[repr(C)]
+#[allow(dead_code)]
+struct Descriptor {
+    unique_id: c_ulong,
+    label: *const c_char,
+    properties: c_int,
+    name: *const c_char,
+    maker: *const c_char,
+    copyright: *const c_char,
+    port_count: c_ulong,
+    port_descriptors: *const c_int,
+    port_names: *const *const c_char,
+    port_range_hints: *const PortRangeHint,
+    implementation_data: *mut c_void,
+    instantiate: Option<unsafe extern "C" fn(*const Descriptor, c_ulong) -> Handle>,
+    connect_port: Option<unsafe extern "C" fn(Handle, c_ulong, *mut f32)>,
+    activate: Option<unsafe extern "C" fn(Handle)>,
+    run: Option<unsafe extern "C" fn(Handle, c_ulong)>,
+    run_adding: Option<unsafe extern "C" fn(Handle, c_ulong)>,
+    set_run_adding_gain: Option<unsafe extern "C" fn(Handle, f32)>,
+    deactivate: Option<unsafe extern "C" fn(Handle)>,
+    cleanup: Option<unsafe extern "C" fn(Handle)>,
+}
+
+type DescriptorFunction = unsafe extern "C" fn(c_ulong) -> *const Descriptor;
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct LadspaControl {
+    pub index: usize,
+    pub name: String,
+    pub output: bool,
+    pub min: Option<f32>,
+    pub max: Option<f32>,
+    pub default: f32,
+    pub toggled: bool,
+    pub integer: bool,
+    pub logarithmic: bool,
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct LadspaPluginInfo {
+    pub library: String,
+    pub label: String,
+    pub unique_id: u64,
+    pub name: String,
+    pub maker: String,
+    pub audio_inputs: usize,
+    pub audio_outputs: usize,
+    pub controls: Vec<LadspaControl>,
+}
+
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct LadspaParams {
+    /// Path of the shared library containing the plugin.
+    pub library: String,
+    pub label: String,
+    /// Control input values by port name; unlisted ports use their defaults.
+    pub controls: BTreeMap<String, f32>,
+}
+
+fn c_string(ptr: *const c_char) -> String {
+    if ptr.is_null() {
+        return String::new();
+    }
+    // SAFETY: LADSPA strings are NUL-terminated and live as long as the library
+    unsafe { CStr::from_ptr(ptr) }.to_string_lossy().into_owned()
+}
+
+/// Directories listed in `LADSPA_PATH`, or the usual install locations.
+fn search_paths() -> Vec<PathBuf> {
+    match std::env::var_os("LADSPA_PATH") {
+        Some(paths) => std::env::split_paths(&paths).collect(),
+        None => {
+            let mut paths: Vec<PathBuf> = DEFAULT_PATHS.iter().map(PathBuf::from).collect();
+            if let Some(home) = std::env::var_os("HOME") {
+                paths.push(Path::new(&home).join(".ladspa"));
+            }
+            paths
+        }
+    }
+}
+
+/// A loaded plugin library and one of its descriptors.
+struct Plugin {
+    _library: Arc<Library>,
+    descriptor: *const Descriptor,
+}
+
+impl Plugin {
+    fn descriptor(&self) -> &Descriptor {
+        // SAFETY: descriptors are static data of the library, which `self` keeps loaded
+        unsafe { &*self.descriptor }
+    }
+
+    fn port_descriptor(&self, port: usize) -> c_int {
+        // SAFETY: `port` is below `port_count`
+        unsafe { *self.descriptor().port_descriptors.add(port) }
+    }
+
+    fn port_name(&self, port: usize) -> String {
+        // SAFETY: `port` is below `port_count`
+        c_string(unsafe { *self.descriptor().port_names.add(port) })
+    }
+
+    fn port_hint(&self, port: usize) -> &PortRangeHint {
+        // SAFETY: `port` is below `port_count`
+        unsafe { &*self.descriptor().port_range_hints.add(port) }
+    }
+
+    fn ports(&self) -> std::ops::Range<usize> {
+        0..self.descriptor().port_count as usize
+    }
+
+    fn audio_ports(&self, direction: c_int) -> Vec<usize> {
+        self.ports()
+            .filter(|port| {
+                let descriptor = self.port_descriptor(*port);
+                descriptor & PORT_AUDIO != 0 && descriptor & direction != 0
+            })
+            .collect()
+    }
+
+    /// Describes a control port, with sample-rate relative bounds resolved for `sample_rate`.
+    fn control(&self, port: usize, sample_rate: f32) -> LadspaControl {
+        let hint = self.port_hint(port);
+        let flags = hint.hint_descriptor;
+        let scale = if flags & HINT_SAMPLE_RATE != 0 { sample_rate } else { 1.0 };
+        let min = (flags & HINT_BOUNDED_BELOW != 0).then_some(hint.lower_bound * scale);
+        let max = (flags & HINT_BOUNDED_ABOVE != 0).then_some(hint.upper_bound * scale);
+        let logarithmic = flags & HINT_LOGARITHMIC != 0;
+
+        // Weighted point between the bounds, as ladspa.h defines the default hints
+        let between = |weight: f32| {
+            let (low, high) = (min.unwrap_or(0.0), max.unwrap_or(0.0));
+            if logarithmic && low > 0.0 && high > 0.0 {
+                (low.ln() * (1.0 - weight) + high.ln() * weight).exp()
+            } else {
+                low * (1.0 - weight) + high * weight
+            }
+        };
+        let default = match flags & HINT_DEFAULT_MASK {
+            HINT_DEFAULT_MINIMUM => min.unwrap_or(0.0),
+            HINT_DEFAULT_LOW => between(0.25),
+            HINT_DEFAULT_MIDDLE => between(0.5),
+            HINT_DEFAULT_HIGH => between(0.75),
+            HINT_DEFAULT_MAXIMUM => max.unwrap_or(0.0),
+            HINT_DEFAULT_1 => 1.0,
+            HINT_DEFAULT_100 => 100.0,
+            HINT_DEFAULT_440 => 440.0,
+            HINT_DEFAULT_0 => 0.0,
+            _ => min.unwrap_or(0.0).max(0.0).min(max.unwrap_or(f32::MAX)),
+        };
+
+        LadspaControl {
+            index: port,
+            name: self.port_name(port),
+            output: self.port_descriptor(port) & PORT_OUTPUT != 0,
+            min,
+            max,
+            default,
+            toggled: flags & HINT_TOGGLED != 0,
+            integer: flags & HINT_INTEGER != 0,
+            logarithmic,
+        }
+    }
+
+    fn controls(&self, sample_rate: f32) -> Vec<LadspaControl> {
+        self.ports()
+            .filter(|port| self.port_descriptor(*port) & PORT_CONTROL != 0)
+            .map(|port| self.control(port, sample_rate))
+            .collect()
+    }
+
+    // c_ulong is only 32 bits wide on Windows
+    #[allow(clippy::unnecessary_cast)]
+    fn info(&self, library: &Path) -> LadspaPluginInfo {
+        let descriptor = self.descriptor();
+        LadspaPluginInfo {
+            library: library.to_string_lossy().into_owned(),
+            label: c_string(descriptor.label),
+            unique_id: descriptor.unique_id as u64,
+            name: c_string(descriptor.name),
+            maker: c_string(descriptor.maker),
+            audio_inputs: self.audio_ports(PORT_INPUT).len(),
+            audio_outputs: self.audio_ports(PORT_OUTPUT).len(),
+            // Bounds relative to the sample rate are shown for 44.1 kHz
+            controls: self.controls(44100.0),
+        }
+    }
+}
+
+/// Loads a library and returns all the plugins it exports.
+fn load_library(path: &Path) -> Result<Vec<Plugin>, String> {
+    // SAFETY: loading runs the library's initializers; LADSPA libraries are
+    // plain C plugins meant to be loaded this way
+    let library = unsafe { Library::new(path) }.map_err(|e| format!("Failed to load {}: {}", path.display(), e))?;
+    let library = Arc::new(library);
+
+    let descriptor_fn: DescriptorFunction = unsafe {
+        *library
+            .get::<DescriptorFunction>(b"ladspa_descriptor\0")
+            .map_err(|_| format!("{} is not a LADSPA library", path.display()))?
+    };
+
+    let mut plugins = Vec::new();
+    for index in 0.. {
+        // SAFETY: the host calls ladspa_descriptor with increasing indices until it returns null
+        let descriptor = unsafe { descriptor_fn(index) };
+        if descriptor.is_null() {
+            break;
+        }
+        plugins.push(Plugin {
+            _library: Arc::clone(&library),
+            descriptor,
+        });
+    }
+    Ok(plugins)
+}
+
+/// Lists the plugins of every library in the LADSPA search path.
+pub fn scan_plugins() -> Vec<LadspaPluginInfo> {
+    scan_directories(&search_paths())
+}
+
+fn scan_directories(directories: &[PathBuf]) -> Vec<LadspaPluginInfo> {
+    let mut plugins = Vec::new();
+
+    for directory in directories {
+        let Ok(entries) = std::fs::read_dir(directory) else {
+            continue;
+        };
+        let mut libraries: Vec<PathBuf> = entries
+            .filter_map(|entry| entry.ok().map(|e| e.path()))
+            .filter(|path| path.extension().and_then(|e| e.to_str()) == Some("so"))
+            .collect();
+        libraries.sort();
+
+        for library in libraries {
+            match load_library(&library) {
+                Ok(found) => plugins.extend(found.iter().map(|plugin| plugin.info(&library))),
+                Err(e) => eprintln!("Skipping LADSPA library: {}", e),
+            }
+        }
+    }
+    plugins
+}
+
+/// One running plugin instance with the buffers its ports are connected to.
+struct Instance {
+    handle: Handle,
+    inputs: Vec<Vec<f32>>,
+    outputs: Vec<Vec<f32>>,
+    // Boxed so the addresses handed to connect_port stay put
+    controls: Box<[f32]>,
+}
+
+/// A LADSPA plugin in the effect chain.
+///
+/// Plugins whose audio inputs and outputs match the channel count run as a
+/// single instance; mono plugins get one instance per channel.
+pub struct LadspaEffect {
+    params: LadspaParams,
+    plugin: Option<Plugin>,
+    sample_rate: f32,
+    channels: usize,
+    instances: Vec<Instance>,
+    /// Port index -> slot in `Instance::controls`, for every control port.
+    control_slots: Vec<(usize, LadspaControl)>,
+}
+
+// SAFETY: the raw plugin handles are only used by whichever thread holds the
+// effect chain lock, never concurrently
+unsafe impl Send for LadspaEffect {}
+
+impl Default for LadspaEffect {
+    fn default() -> Self {
+        Self {
+            params: LadspaParams::default(),
+            plugin: None,
+            sample_rate: 44100.0,
+            channels: 2,
+            instances: Vec::new(),
+            control_slots: Vec::new(),
+        }
+    }
+}
+
+impl LadspaEffect {
+    fn set_ladspa_params(&mut self, params: LadspaParams) -> Result<(), String> {
+        let reload = params.library != self.params.library || params.label != self.params.label;
+        let plugin = if reload {
+            let plugin = load_library(Path::new(&params.library))?
+                .into_iter()
+                .find(|plugin| c_string(plugin.descriptor().label) == params.label)
+                .ok_or_else(|| format!("No LADSPA plugin labelled {} in {}", params.label, params.library))?;
+            if plugin.descriptor().instantiate.is_none() || plugin.descriptor().run.is_none() {
+                return Err(format!("LADSPA plugin {} can't be run", params.label));
+            }
+            Some(plugin)
+        } else {
+            None
+        };
+
+        let controls = match &plugin {
+            Some(plugin) => plugin.controls(self.sample_rate),
+            None => self.control_slots.iter().map(|(_, control)| control.clone()).collect(),
+        };
+        for name in params.controls.keys() {
+            if !controls.iter().any(|control| !control.output && control.name == *name) {
+                return Err(format!("Plugin {} has no control input named {}", params.label, name));
+            }
+        }
+
+        self.params = params;
+        match plugin {
+            Some(plugin) => {
+                self.drop_instances();
+                self.control_slots = controls.into_iter().enumerate().collect();
+                self.plugin = Some(plugin);
+                self.instantiate();
+            }
+            None => self.apply_controls(),
+        }
+        Ok(())
+    }
+
+    fn control_value(&self, control: &LadspaControl) -> f32 {
+        let value = self.params.controls.get(&control.name).copied().unwrap_or(control.default);
+        let value = value.max(control.min.unwrap_or(f32::MIN)).min(control.max.unwrap_or(f32::MAX));
+        if control.integer || control.toggled {
+            value.round()
+        } else {
+            value
+        }
+    }
+
+    fn apply_controls(&mut self) {
+        let values: Vec<(usize, f32)> = self
+            .control_slots
+            .iter()
+            .filter(|(_, control)| !control.output)
+            .map(|(slot, control)| (*slot, self.control_value(control)))
+            .collect();
+        for instance in &mut self.instances {
+            for (slot, value) in &values {
+                instance.controls[*slot] = *value;
+            }
+        }
+    }
+
+    fn instantiate(&mut self) {
+        self.drop_instances();
+        let Some(plugin) = &self.plugin else {
+            return;
+        };
+
+        let inputs = plugin.audio_ports(PORT_INPUT);
+        let outputs = plugin.audio_ports(PORT_OUTPUT);
+        let count = if inputs.len() == self.channels && outputs.len() == self.channels {
+            1
+        } else if inputs.len() == 1 && outputs.len() == 1 {
+            self.channels
+        } else {
+            eprintln!(
+                "LADSPA plugin {} has {} inputs and {} outputs, which doesn't fit {} channels",
+                self.params.label,
+                inputs.len(),
+                outputs.len(),
+                self.channels
+            );
+            return;
+        };
+
+        let descriptor = plugin.descriptor();
+        for _ in 0..count {
+            // SAFETY: instantiate was checked to be present when the plugin was loaded
+            let handle = unsafe { (descriptor.instantiate.unwrap())(plugin.descriptor, self.sample_rate as c_ulong) };
+            if handle.is_null() {
+                eprintln!("Failed to instantiate LADSPA plugin {}", self.params.label);
+                self.drop_instances();
+                return;
+            }
+
+            let mut instance = Instance {
+                handle,
+                inputs: vec![vec![0.0; MAX_BLOCK]; inputs.len()],
+                outputs: vec![vec![0.0; MAX_BLOCK]; outputs.len()],
+                controls: vec![0.0; self.control_slots.len()].into_boxed_slice(),
+            };
+
+            if let Some(connect) = descriptor.connect_port {
+                // SAFETY: every port gets a buffer that outlives the instance
+                unsafe {
+                    for (buffer, port) in instance.inputs.iter_mut().zip(&inputs) {
+                        connect(handle, *port as c_ulong, buffer.as_mut_ptr());
+                    }
+                    for (buffer, port) in instance.outputs.iter_mut().zip(&outputs) {
+                        connect(handle, *port as c_ulong, buffer.as_mut_ptr());
+                    }
+                    for (slot, control) in &self.control_slots {
+                        connect(handle, control.index as c_ulong, instance.controls.as_mut_ptr().add(*slot));
+                    }
+                }
+            }
+            if let Some(activate) = descriptor.activate {
+                // SAFETY: the instance is fully connected
+                unsafe { activate(handle) };
+            }
+            self.instances.push(instance);
+        }
+        self.apply_controls();
+    }
+
+    fn drop_instances(&mut self) {
+        let Some(plugin) = &self.plugin else {
+            self.instances.clear();
+            return;
+        };
+        let descriptor = plugin.descriptor();
+        for instance in self.instances.drain(..) {
+            // SAFETY: each handle came from this descriptor and is cleaned up once
+            unsafe {
+                if let Some(deactivate) = descriptor.deactivate {
+                    deactivate(instance.handle);
+                }
+                if let Some(cleanup) = descriptor.cleanup {
+                    cleanup(instance.handle);
+                }
+            }
+        }
+    }
+}
+
+impl Drop for LadspaEffect {
+    fn drop(&mut self) {
+        self.drop_instances();
+    }
+}
+
+impl AudioEffect for LadspaEffect {
+    fn kind(&self) -> &'static str {
+        "ladspa"
+    }
+
+    fn prepare(&mut self, sample_rate: f32, channels: usize) {
+        self.sample_rate = sample_rate;
+        self.channels = channels;
+        if let Some(plugin) = &self.plugin {
+            self.control_slots = plugin.controls(sample_rate).into_iter().enumerate().collect();
+        }
+        self.instantiate();
+    }
+
+    fn process(&mut self, samples: &mut [f32], channels: usize) {
+        let Some(run) = self.plugin.as_ref().and_then(|plugin| plugin.descriptor().run) else {
+            return;
+        };
+        if self.instances.is_empty() || channels != self.channels {
+            return;
+        }
+
+        let per_channel = self.instances.len() > 1;
+        for block in samples.chunks_mut(MAX_BLOCK * channels) {
+            let frames = block.len() / channels;
+
+            for (index, instance) in self.instances.iter_mut().enumerate() {
+                for (port, input) in instance.inputs.iter_mut().enumerate() {
+                    let channel = if per_channel { index } else { port };
+                    for (frame, sample) in input[..frames].iter_mut().enumerate() {
+                        *sample = block[frame * channels + channel];
+                    }
+                }
+
+                // SAFETY: all ports are connected to buffers of at least MAX_BLOCK frames
+                unsafe { run(instance.handle, frames as c_ulong) };
+            }
+
+            for (index, instance) in self.instances.iter().enumerate() {
+                for (port, output) in instance.outputs.iter().enumerate() {
+                    let channel = if per_channel { index } else { port };
+                    for (frame, sample) in output[..frames].iter().enumerate() {
+                        block[frame * channels + channel] = *sample;
+                    }
+                }
+            }
+        }
+    }
+
+    fn reset(&mut self) {
+        let Some(plugin) = &self.plugin else {
+            return;
+        };
+        let descriptor = plugin.descriptor();
+        for instance in &self.instances {
+            // SAFETY: deactivate followed by activate is how LADSPA resets an instance
+            unsafe {
+                if let Some(deactivate) = descriptor.deactivate {
+                    deactivate(instance.handle);
+                }
+                if let Some(activate) = descriptor.activate {
+                    activate(instance.handle);
+                }
+            }
+        }
+    }
+
+    fn latency(&self) -> usize {
+        // Plugins report their delay on a control output conventionally named "latency"
+        let Some(instance) = self.instances.first() else {
+            return 0;
+        };
+        self.control_slots
+            .iter()
+            .find(|(_, control)| control.output && control.name.eq_ignore_ascii_case("latency"))
+            .map(|(slot, _)| instance.controls[*slot].max(0.0) as usize)
+            .unwrap_or(0)
+    }
+
+    fn params(&self) -> Value {
+        serde_json::to_value(&self.params).unwrap_or(Value::Null)
+    }
+
+    fn set_params(&mut self, params: Value) -> Result<(), String> {
+        let params = serde_json::from_value(params).map_err(|e| format!("Invalid LADSPA parameters: {}", e))?;
+        self.set_ladspa_params(params)
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::process::Command;
+    use std::sync::OnceLock;
+
+    /// Mono gain plugin from tests/fixtures, compiled once per test run.
+    fn plugin() -> &'static str {
+        static PLUGIN: OnceLock<String> = OnceLock::new();
+        PLUGIN.get_or_init(|| {
+            let source = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/ladspa_gain.c");
+            let output = temp_dir("ladspa-fixture").join("ladspa_gain.so");
+            let compiler = std::env::var("CC").unwrap_or_else(|_| "cc".to_string());
+            let status = Command::new(&compiler)
+                .args(["-shared", "-fPIC", "-o"])
+                .arg(&output)
+                .arg(&source)
+                .status()
+                .unwrap_or_else(|e| panic!("Failed to run {}: {}", compiler, e));
+            assert!(status.success(), "Failed to compile {}", source.display());
+            output.to_string_lossy().into_owned()
+        })
+    }
+
+    fn params(library: &str, label: &str, controls: &[(&str, f32)]) -> Value {
+        serde_json::to_value(LadspaParams {
+            library: library.to_string(),
+            label: label.to_string(),
+            controls: controls.iter().map(|(name, value)| (name.to_string(), *value)).collect(),
+        })
+        .unwrap()
+    }
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("crate-test-{}-{}", std::process::id(), name));
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn discovers_plugins_and_skips_broken_libraries() {
+        let dir = temp_dir("ladspa-scan");
+        std::fs::copy(plugin(), dir.join("gain.so")).unwrap();
+        std::fs::write(dir.join("broken.so"), b"not a shared library").unwrap();
+
+        let plugins = scan_directories(std::slice::from_ref(&dir));
+        assert_eq!(plugins.len(), 1);
+        let plugin = &plugins[0];
+        assert_eq!(plugin.library, dir.join("gain.so").to_string_lossy());
+        assert_eq!(plugin.label, "test_gain");
+        assert_eq!(plugin.unique_id, 4242);
+        assert_eq!(plugin.name, "Test Gain");
+        assert_eq!((plugin.audio_inputs, plugin.audio_outputs), (1, 1));
+
+        let names: Vec<(&str, bool)> = plugin.controls.iter().map(|c| (c.name.as_str(), c.output)).collect();
+        assert_eq!(names, [("Gain", false), ("latency", true)]);
+        let gain = &plugin.controls[0];
+        assert_eq!((gain.index, gain.min, gain.max, gain.default), (0, Some(0.0), Some(4.0), 1.0));
+    }
+
+    #[test]
+    fn runs_one_instance_per_channel() {
+        let mut effect = LadspaEffect::default();
+        effect.set_params(params(plugin(), "test_gain", &[("Gain", 0.5)])).unwrap();
+        effect.prepare(48000.0, 2);
+        assert_eq!(effect.instances.len(), 2);
+
+        let mut samples: Vec<f32> = (0..3000).map(|i| if i % 2 == 0 { 0.8 } else { -0.4 }).collect();
+        effect.process(&mut samples, 2);
+        for frame in samples.chunks_exact(2) {
+            assert_eq!(frame, [0.4, -0.2]);
+        }
+        assert_eq!(effect.latency(), 7);
+
+        // Out of range values are clamped to the port bounds
+        effect.set_params(params(plugin(), "test_gain", &[("Gain", 10.0)])).unwrap();
+        let mut samples = vec![0.1; 4];
+        effect.process(&mut samples, 2);
+        assert_eq!(samples, [0.4; 4]);
+    }
+
+    #[test]
+    fn unset_controls_use_their_defaults() {
+        let mut effect = LadspaEffect::default();
+        effect.prepare(44100.0, 1);
+        effect.set_params(params(plugin(), "test_gain", &[])).unwrap();
+        assert_eq!(effect.instances.len(), 1);
+
+        let mut samples = vec![0.25; 16];
+        effect.process(&mut samples, 1);
+        assert_eq!(samples, [0.25; 16]);
+    }
+
+    #[test]
+    fn rejects_bad_libraries_and_parameters() {
+        let dir = temp_dir("ladspa-bad");
+        let broken = dir.join("broken.so");
+        std::fs::write(&broken, b"not a shared library").unwrap();
+        let broken = broken.to_string_lossy();
+
+        let mut effect = LadspaEffect::default();
+        let error = effect.set_params(params(&broken, "test_gain", &[])).unwrap_err();
+        assert!(error.starts_with("Failed to load"), "{}", error);
+
+        let error = effect.set_params(params(plugin(), "missing", &[])).unwrap_err();
+        assert_eq!(error, format!("No LADSPA plugin labelled missing in {}", plugin()));
+
+        let error = effect.set_params(params(plugin(), "test_gain", &[("Volume", 1.0)])).unwrap_err();
+        assert_eq!(error, "Plugin test_gain has no control input named Volume");
+        let error = effect.set_params(params(plugin(), "test_gain", &[("latency", 1.0)])).unwrap_err();
+        assert_eq!(error, "Plugin test_gain has no control input named latency");
+
+        // Nothing is loaded after a failed change
+        assert!(effect.plugin.is_none());
+        let mut samples = vec![0.3; 8];
+        effect.process(&mut samples, 2);
+        assert_eq!(samples, [0.3; 8]);
+    }
+}
@@ -0,0 +1,265 @@
+use super::true_peak::{TruePeakDetector, TRUE_PEAK_DELAY};
+use super::AudioEffect;
+use serde_json::Value;
+use std::collections::VecDeque;
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct LimiterParams {
+    pub enabled: bool,
+    /// Maximum true-peak output level.
+    pub ceiling_db: f32,
+    pub release_ms: f32,
+    pub lookahead_ms: f32,
+}
+
+impl Default for LimiterParams {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            ceiling_db: -1.0,
+            release_ms: 100.0,
+            lookahead_ms: 1.5,
+        }
+    }
+}
+
+impl LimiterParams {
+    fn normalized(mut self) -> Self {
+        self.ceiling_db = self.ceiling_db.clamp(-24.0, 0.0);
+        self.release_ms = self.release_ms.clamp(1.0, 2000.0);
+        self.lookahead_ms = self.lookahead_ms.clamp(0.5, 10.0);
+        self
+    }
+}
+
+/// Lookahead brickwall limiter working on 4x oversampled (true) peaks.
+///
+/// The required gain per frame goes through a sliding minimum and a box filter
+/// of the lookahead length, so the gain is already down when a peak leaves the
+/// delay line and the reduction ramps in without discontinuities.
+pub struct Limiter {
+    params: LimiterParams,
+    sample_rate: f32,
+    channels: usize,
+    ceiling: f32,
+    release: f32,
+    lookahead: usize,
+    detectors: Vec<TruePeakDetector>,
+    envelope: f32,
+    minimum: VecDeque<(u64, f32)>,
+    window: Vec<f32>,
+    window_sum: f64,
+    window_pos: usize,
+    delay: Vec<f32>,
+    delay_pos: usize,
+    frame: u64,
+    gain: f32,
+}
+
+impl Default for Limiter {
+    fn default() -> Self {
+        let mut limiter = Self {
+            params: LimiterParams::default(),
+            sample_rate: 44100.0,
+            channels: 2,
+            ceiling: 1.0,
+            release: 0.0,
+            lookahead: 1,
+            detectors: Vec::new(),
+            envelope: 1.0,
+            minimum: VecDeque::new(),
+            window: Vec::new(),
+            window_sum: 0.0,
+            window_pos: 0,
+            delay: Vec::new(),
+            delay_pos: 0,
+            frame: 0,
+            gain: 1.0,
+        };
+        limiter.rebuild();
+        limiter
+    }
+}
+
+impl Limiter {
+    pub fn limiter_params(&self) -> &LimiterParams {
+        &self.params
+    }
+
+    pub fn set_limiter_params(&mut self, params: LimiterParams) {
+        self.params = params.normalized();
+        self.rebuild();
+    }
+
+    /// Current gain reduction, in dB (zero or negative).
+    pub fn gain_reduction_db(&self) -> f32 {
+        20.0 * self.gain.max(1e-9).log10()
+    }
+
+    fn rebuild(&mut self) {
+        self.ceiling = 10f32.powf(self.params.ceiling_db / 20.0);
+        self.release = (-1.0 / (self.params.release_ms * 0.001 * self.sample_rate)).exp();
+        self.lookahead = ((self.params.lookahead_ms * 0.001 * self.sample_rate) as usize).max(1);
+
+        // The true-peak estimate trails the newest sample by half the interpolator,
+        // and the smoothed gain reaches full reduction lookahead - 1 frames after
+        // the envelope drops
+        let delay_frames = self.lookahead - 1 + TRUE_PEAK_DELAY;
+        self.detectors = vec![TruePeakDetector::default(); self.channels];
+        self.window = vec![1.0; self.lookahead];
+        self.minimum = VecDeque::with_capacity(self.lookahead + 1);
+        self.delay = vec![0.0; delay_frames * self.channels];
+        self.reset();
+    }
+
+    fn true_peak(&mut self, frame: &[f32]) -> f32 {
+        self.detectors
+            .iter_mut()
+            .zip(frame)
+            .map(|(detector, sample)| detector.process(*sample))
+            .fold(0.0, f32::max)
+    }
+}
+
+impl AudioEffect for Limiter {
+    fn kind(&self) -> &'static str {
+        "limiter"
+    }
+
+    fn prepare(&mut self, sample_rate: f32, channels: usize) {
+        self.sample_rate = sample_rate;
+        self.channels = channels;
+        self.rebuild();
+    }
+
+    fn process(&mut self, samples: &mut [f32], channels: usize) {
+        if !self.params.enabled || channels != self.channels {
+            return;
+        }
+
+        for frame in samples.chunks_exact_mut(channels) {
+            let peak = self.true_peak(frame);
+            let target = if peak > self.ceiling { self.ceiling / peak } else { 1.0 };
+
+            // Release never overshoots the target, so the envelope stays safe
+            self.envelope = target.min(1.0 - (1.0 - self.envelope) * self.release);
+
+            while self.minimum.back().is_some_and(|(_, value)| *value >= self.envelope) {
+                self.minimum.pop_back();
+            }
+            self.minimum.push_back((self.frame, self.envelope));
+            while self.minimum.front().is_some_and(|(index, _)| index + (self.lookahead as u64) <= self.frame) {
+                self.minimum.pop_front();
+            }
+            let minimum = self.minimum.front().map(|(_, value)| *value).unwrap_or(1.0);
+
+            self.window_sum += (minimum - self.window[self.window_pos]) as f64;
+            self.window[self.window_pos] = minimum;
+            self.window_pos = (self.window_pos + 1) % self.lookahead;
+            self.gain = (self.window_sum / self.lookahead as f64) as f32;
+            self.frame += 1;
+
+            let delay = &mut self.delay[self.delay_pos * channels..(self.delay_pos + 1) * channels];
+            for (sample, delayed) in frame.iter_mut().zip(delay.iter_mut()) {
+                let output = *delayed * self.gain;
+                *delayed = *sample;
+                *sample = output.clamp(-self.ceiling, self.ceiling);
+            }
+            self.delay_pos = (self.delay_pos + 1) % (self.delay.len() / channels);
+        }
+    }
+
+    fn reset(&mut self) {
+        self.detectors.iter_mut().for_each(TruePeakDetector::reset);
+        self.envelope = 1.0;
+        self.minimum.clear();
+        self.window.iter_mut().for_each(|w| *w = 1.0);
+        self.window_sum = self.lookahead as f64;
+        self.window_pos = 0;
+        self.delay.iter_mut().for_each(|d| *d = 0.0);
+        self.delay_pos = 0;
+        self.frame = 0;
+        self.gain = 1.0;
+    }
+
+    fn latency(&self) -> usize {
+        if self.params.enabled {
+            self.delay.len() / self.channels.max(1)
+        } else {
+            0
+        }
+    }
+
+    fn params(&self) -> Value {
+        serde_json::to_value(&self.params).unwrap_or(Value::Null)
+    }
+
+    fn set_params(&mut self, params: Value) -> Result<(), String> {
+        let params = serde_json::from_value(params).map_err(|e| format!("Invalid limiter parameters: {}", e))?;
+        self.set_limiter_params(params);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f32::consts::PI;
+
+    #[test]
+    fn true_peak_stays_under_ceiling() {
+        let sample_rate = 44100.0;
+        let mut limiter = Limiter::default();
+        limiter.prepare(sample_rate, 1);
+        let ceiling = 10f32.powf(limiter.limiter_params().ceiling_db / 20.0);
+
+        // A quarter-rate sine sampled 45 degrees off its crests: every sample sits
+        // at 0.71 of the amplitude, so only the oversampled peak exceeds the ceiling
+        let mut samples: Vec<f32> = (0..sample_rate as usize)
+            .map(|i| 1.2 * (PI / 2.0 * i as f32 + PI / 4.0).sin())
+            .collect();
+        assert!(samples.iter().all(|s| s.abs() < ceiling));
+        limiter.process(&mut samples, 1);
+
+        let mut detector = TruePeakDetector::default();
+        let settled = limiter.latency() + 4410;
+        let peak = samples.iter().map(|s| detector.process(*s)).skip(settled).fold(0.0, f32::max);
+        assert!(peak <= ceiling * 1.001, "true peak {} above ceiling {}", peak, ceiling);
+        assert!(peak > ceiling * 0.9, "limited too far: {}", peak);
+    }
+
+    #[test]
+    fn sudden_peak_is_reduced_before_it_arrives() {
+        let sample_rate = 48000.0;
+        let mut limiter = Limiter::default();
+        limiter.prepare(sample_rate, 1);
+        let ceiling = 10f32.powf(limiter.limiter_params().ceiling_db / 20.0);
+
+        // Silence, then a loud burst the limiter only sees lookahead frames ahead
+        let mut samples = vec![0.0; 4800];
+        samples.extend((0..4800).map(|i| 4.0 * (2.0 * PI * 1000.0 * i as f32 / sample_rate).sin()));
+        limiter.process(&mut samples, 1);
+
+        // Output the clamp had to catch would sit exactly on the ceiling
+        let clipped = samples.iter().filter(|s| s.abs() >= ceiling).count();
+        assert_eq!(clipped, 0, "{} samples hard-clipped", clipped);
+        let mut detector = TruePeakDetector::default();
+        let peak = samples.iter().map(|s| detector.process(*s)).fold(0.0, f32::max);
+        assert!(peak <= ceiling * 1.001, "true peak {} above ceiling {}", peak, ceiling);
+    }
+
+    #[test]
+    fn quiet_signal_is_only_delayed() {
+        let mut limiter = Limiter::default();
+        limiter.prepare(48000.0, 1);
+        let input: Vec<f32> = (0..4800).map(|i| 0.3 * (i as f32 * 0.01).sin()).collect();
+        let mut output = input.clone();
+        limiter.process(&mut output, 1);
+
+        let latency = limiter.latency();
+        for (out, original) in output[latency..].iter().zip(&input) {
+            assert!((out - original).abs() < 1e-6);
+        }
+    }
+}
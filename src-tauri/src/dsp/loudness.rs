@@ -0,0 +1,262 @@
+use super::biquad::{Biquad, Coefficients, FilterType};
+use super::AudioEffect;
+use serde_json::Value;
+
+/// ISO 226:2003 equal-loudness parameters: frequency, exponent `af`,
+/// magnitude of the linear transfer function `Lu` and hearing threshold `Tf`.
+const ISO_226: [(f32, f32, f32, f32); 29] = [
+    (20.0, 0.532, -31.6, 78.5),
+    (25.0, 0.506, -27.2, 68.7),
+    (31.5, 0.480, -23.0, 59.5),
+    (40.0, 0.455, -19.1, 51.1),
+    (50.0, 0.432, -15.9, 44.0),
+    (63.0, 0.409, -13.0, 37.5),
+    (80.0, 0.387, -10.3, 31.5),
+    (100.0, 0.367, -8.1, 26.5),
+    (125.0, 0.349, -6.2, 22.1),
+    (160.0, 0.330, -4.5, 17.9),
+    (200.0, 0.315, -3.1, 14.4),
+    (250.0, 0.301, -2.0, 11.4),
+    (315.0, 0.288, -1.1, 8.6),
+    (400.0, 0.276, -0.4, 6.2),
+    (500.0, 0.267, 0.0, 4.4),
+    (630.0, 0.259, 0.3, 3.0),
+    (800.0, 0.253, 0.5, 2.2),
+    (1000.0, 0.250, 0.0, 2.4),
+    (1250.0, 0.246, -2.7, 3.5),
+    (1600.0, 0.244, -4.1, 1.7),
+    (2000.0, 0.243, -1.0, -1.3),
+    (2500.0, 0.243, 1.7, -4.2),
+    (3150.0, 0.243, 2.5, -6.0),
+    (4000.0, 0.242, 1.2, -5.4),
+    (5000.0, 0.242, -2.1, -1.5),
+    (6300.0, 0.245, -7.1, 6.0),
+    (8000.0, 0.254, -11.2, 12.6),
+    (10000.0, 0.271, -10.7, 13.9),
+    (12500.0, 0.301, -3.1, 12.3),
+];
+
+/// Quietest level the contours are evaluated at; ISO 226 is only defined down to 20 phon.
+const MIN_PHON: f32 = 20.0;
+
+const BASS_SHELF_HZ: f32 = 100.0;
+/// Contour frequency that sets the bass shelf gain.
+const BASS_CONTOUR_HZ: f32 = 50.0;
+
+const TREBLE_SHELF_HZ: f32 = 10000.0;
+/// Contour frequency that sets the treble shelf gain.
+const TREBLE_CONTOUR_HZ: f32 = 12500.0;
+
+const SHELF_Q: f32 = 0.707;
+
+/// Sound pressure level in dB at which a tone of `frequency` is heard as loud
+/// as a 1 kHz tone at `phon`, per ISO 226:2003.
+fn equal_loudness_spl(phon: f32, frequency: f32) -> f32 {
+    let (_, af, lu, tf) = ISO_226
+        .iter()
+        .copied()
+        .min_by(|a, b| (a.0 - frequency).abs().total_cmp(&(b.0 - frequency).abs()))
+        .expect("table is not empty");
+
+    let a = 4.47e-3 * (10f32.powf(0.025 * phon) - 1.15) + (0.4 * 10f32.powf((tf + lu) / 10.0 - 9.0)).powf(af);
+    10.0 / af * a.log10() - lu + 94.0
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct LoudnessParams {
+    /// Listening level the music is assumed to be mixed for, reached at full volume.
+    pub reference_phon: f32,
+    /// Fraction of the contour difference that is applied.
+    pub strength: f32,
+}
+
+impl Default for LoudnessParams {
+    fn default() -> Self {
+        Self {
+            reference_phon: 83.0,
+            strength: 1.0,
+        }
+    }
+}
+
+impl LoudnessParams {
+    fn normalized(mut self) -> Self {
+        self.reference_phon = self.reference_phon.clamp(60.0, 100.0);
+        self.strength = self.strength.clamp(0.0, 1.0);
+        self
+    }
+}
+
+/// Loudness compensation that follows the player volume.
+///
+/// Turning the volume down by N dB moves the listener from the reference
+/// contour to one N phon lower, where bass and treble need more level to sound
+/// as loud. Two shelves make up the difference between those contours. The
+/// boost never exceeds the volume attenuation, so the compensated signal can't
+/// get louder than the unattenuated one.
+pub struct Loudness {
+    params: LoudnessParams,
+    sample_rate: f32,
+    volume_db: f32,
+    bass: Biquad,
+    treble: Biquad,
+}
+
+impl Default for Loudness {
+    fn default() -> Self {
+        let mut loudness = Self {
+            params: LoudnessParams::default(),
+            sample_rate: 44100.0,
+            volume_db: 0.0,
+            bass: Biquad::new(Coefficients::identity(), 2),
+            treble: Biquad::new(Coefficients::identity(), 2),
+        };
+        loudness.update_filters();
+        loudness
+    }
+}
+
+impl Loudness {
+    pub fn loudness_params(&self) -> &LoudnessParams {
+        &self.params
+    }
+
+    pub fn set_loudness_params(&mut self, params: LoudnessParams) {
+        self.params = params.normalized();
+        self.update_filters();
+    }
+
+    /// Updates the compensation for a new linear player volume.
+    pub fn set_volume(&mut self, volume: f32) {
+        self.volume_db = 20.0 * volume.max(1e-6).log10();
+        self.update_filters();
+    }
+
+    /// Boost at `frequency` that restores the reference balance at the current volume.
+    fn compensation_db(&self, frequency: f32) -> f32 {
+        let attenuation = (-self.volume_db).max(0.0);
+        let reference = self.params.reference_phon;
+        let listening = (reference - attenuation).max(MIN_PHON);
+
+        let relative = |phon: f32| equal_loudness_spl(phon, frequency) - equal_loudness_spl(phon, 1000.0);
+        let difference = relative(listening) - relative(reference);
+        (difference * self.params.strength).clamp(0.0, attenuation)
+    }
+
+    fn update_filters(&mut self) {
+        let bass_db = self.compensation_db(BASS_CONTOUR_HZ);
+        let treble_db = self.compensation_db(TREBLE_CONTOUR_HZ);
+
+        self.bass.set_coefficients(Coefficients::design(
+            FilterType::LowShelf,
+            BASS_SHELF_HZ,
+            bass_db,
+            SHELF_Q,
+            self.sample_rate,
+        ));
+        self.treble.set_coefficients(Coefficients::design(
+            FilterType::HighShelf,
+            TREBLE_SHELF_HZ,
+            treble_db,
+            SHELF_Q,
+            self.sample_rate,
+        ));
+    }
+}
+
+impl AudioEffect for Loudness {
+    fn kind(&self) -> &'static str {
+        "loudness"
+    }
+
+    fn prepare(&mut self, sample_rate: f32, channels: usize) {
+        self.sample_rate = sample_rate;
+        self.bass = Biquad::new(Coefficients::identity(), channels);
+        self.treble = Biquad::new(Coefficients::identity(), channels);
+        self.update_filters();
+    }
+
+    fn process(&mut self, samples: &mut [f32], channels: usize) {
+        self.bass.process(samples, channels);
+        self.treble.process(samples, channels);
+    }
+
+    fn reset(&mut self) {
+        self.bass.reset();
+        self.treble.reset();
+    }
+
+    fn response_db(&self, frequency: f64) -> f64 {
+        let sample_rate = self.sample_rate as f64;
+        self.bass.coefficients().magnitude_db(frequency, sample_rate)
+            + self.treble.coefficients().magnitude_db(frequency, sample_rate)
+    }
+
+    fn params(&self) -> Value {
+        serde_json::to_value(&self.params).unwrap_or(Value::Null)
+    }
+
+    fn set_params(&mut self, params: Value) -> Result<(), String> {
+        let params = serde_json::from_value(params).map_err(|e| format!("Invalid loudness parameters: {}", e))?;
+        self.set_loudness_params(params);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn loudness(params: LoudnessParams, volume_db: f32) -> Loudness {
+        let mut loudness = Loudness::default();
+        loudness.prepare(48000.0, 2);
+        loudness.set_loudness_params(params);
+        loudness.set_volume(10f32.powf(volume_db / 20.0));
+        loudness
+    }
+
+    #[test]
+    fn contours_match_iso_226() {
+        // 1 kHz is the reference tone, so its SPL is the phon value
+        for phon in [20.0, 40.0, 60.0, 80.0] {
+            assert!((equal_loudness_spl(phon, 1000.0) - phon).abs() < 0.5, "{} phon", phon);
+        }
+        // Published 40 phon value at 100 Hz, and the ear's most sensitive region around 3-4 kHz
+        assert!((equal_loudness_spl(40.0, 100.0) - 64.4).abs() < 0.1, "{}", equal_loudness_spl(40.0, 100.0));
+        assert!(equal_loudness_spl(40.0, 3150.0) < 37.0);
+    }
+
+    #[test]
+    fn flat_at_full_volume() {
+        let loudness = loudness(LoudnessParams::default(), 0.0);
+        for frequency in [30.0, 1000.0, 15000.0] {
+            assert!(loudness.response_db(frequency).abs() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn boosts_bass_and_treble_as_volume_drops() {
+        let quiet = loudness(LoudnessParams::default(), -30.0);
+        let bass = quiet.response_db(30.0);
+        assert!(bass > 6.0 && bass <= 30.0, "bass {}", bass);
+        assert!(quiet.response_db(15000.0) > 0.5, "treble {}", quiet.response_db(15000.0));
+        assert!(quiet.response_db(1000.0).abs() < 0.5);
+
+        let quieter = loudness(LoudnessParams::default(), -45.0);
+        assert!(quieter.response_db(30.0) > bass);
+    }
+
+    #[test]
+    fn boost_is_capped_by_attenuation_and_strength() {
+        let slight = loudness(LoudnessParams::default(), -3.0);
+        assert!(slight.compensation_db(BASS_CONTOUR_HZ) <= 3.0);
+
+        let off = loudness(LoudnessParams { strength: 0.0, ..Default::default() }, -30.0);
+        assert!(off.response_db(30.0).abs() < 1e-6);
+        let half = loudness(LoudnessParams { strength: 0.5, ..Default::default() }, -30.0);
+        let full = loudness(LoudnessParams::default(), -30.0);
+        let ratio = half.compensation_db(BASS_CONTOUR_HZ) / full.compensation_db(BASS_CONTOUR_HZ);
+        assert!((ratio - 0.5).abs() < 1e-4);
+    }
+}
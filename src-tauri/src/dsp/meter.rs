@@ -0,0 +1,171 @@
+use super::true_peak::TruePeakDetector;
+
+/// Level reported for silence.
+const FLOOR_DB: f32 = -120.0;
+
+/// Integration time of the RMS reading, close to a VU meter's ballistics.
+const RMS_WINDOW_SECS: f32 = 0.3;
+
+/// Sample magnitude counted as a clip: anything at or above full scale.
+const CLIP_LEVEL: f32 = 1.0;
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct ChannelLevels {
+    /// Highest sample since the previous reading, in dBFS.
+    pub peak_db: f32,
+    /// Highest 4x oversampled peak since the previous reading, in dBTP.
+    pub true_peak_db: f32,
+    pub rms_db: f32,
+    /// Runs of clipped samples since the counters were last reset.
+    pub clips: u64,
+}
+
+impl Default for ChannelLevels {
+    fn default() -> Self {
+        Self {
+            peak_db: FLOOR_DB,
+            true_peak_db: FLOOR_DB,
+            rms_db: FLOOR_DB,
+            clips: 0,
+        }
+    }
+}
+
+fn to_db(level: f32) -> f32 {
+    (20.0 * level.max(1e-9).log10()).max(FLOOR_DB)
+}
+
+/// Per-channel peak, true-peak and RMS meter with clip counters.
+///
+/// Samples are fed continuously; `update` takes a reading of everything fed
+/// since the previous one. Clip counters keep running until `reset_clips`.
+pub struct LevelMeter {
+    channels: usize,
+    rms_coefficient: f32,
+    detectors: Vec<TruePeakDetector>,
+    peak: Vec<f32>,
+    true_peak: Vec<f32>,
+    mean_square: Vec<f32>,
+    clipping: Vec<bool>,
+    clips: Vec<u64>,
+    levels: Vec<ChannelLevels>,
+}
+
+impl LevelMeter {
+    pub fn new(sample_rate: f32, channels: usize) -> Self {
+        Self {
+            channels,
+            rms_coefficient: (-1.0 / (RMS_WINDOW_SECS * sample_rate)).exp(),
+            detectors: vec![TruePeakDetector::default(); channels],
+            peak: vec![0.0; channels],
+            true_peak: vec![0.0; channels],
+            mean_square: vec![0.0; channels],
+            clipping: vec![false; channels],
+            clips: vec![0; channels],
+            levels: vec![ChannelLevels::default(); channels],
+        }
+    }
+
+    pub fn channels(&self) -> usize {
+        self.channels
+    }
+
+    pub fn push(&mut self, samples: &[f32]) {
+        for frame in samples.chunks_exact(self.channels) {
+            for (channel, sample) in frame.iter().enumerate() {
+                let level = sample.abs();
+                self.peak[channel] = self.peak[channel].max(level);
+                self.true_peak[channel] = self.true_peak[channel].max(self.detectors[channel].process(*sample));
+                self.mean_square[channel] = sample * sample + (self.mean_square[channel] - sample * sample) * self.rms_coefficient;
+
+                // Count each run of clipped samples once
+                let clipping = level >= CLIP_LEVEL;
+                if clipping && !self.clipping[channel] {
+                    self.clips[channel] += 1;
+                }
+                self.clipping[channel] = clipping;
+            }
+        }
+    }
+
+    /// Takes a reading and starts a new peak interval.
+    pub fn update(&mut self) -> &[ChannelLevels] {
+        for channel in 0..self.channels {
+            self.levels[channel] = ChannelLevels {
+                peak_db: to_db(self.peak[channel]),
+                true_peak_db: to_db(self.true_peak[channel]),
+                rms_db: to_db(self.mean_square[channel].sqrt()),
+                clips: self.clips[channel],
+            };
+        }
+        self.peak.iter_mut().for_each(|peak| *peak = 0.0);
+        self.true_peak.iter_mut().for_each(|peak| *peak = 0.0);
+        &self.levels
+    }
+
+    /// The most recent reading.
+    pub fn levels(&self) -> &[ChannelLevels] {
+        &self.levels
+    }
+
+    pub fn reset_clips(&mut self) {
+        self.clips.iter_mut().for_each(|clips| *clips = 0);
+        self.levels.iter_mut().for_each(|levels| levels.clips = 0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f32::consts::PI;
+
+    #[test]
+    fn reads_peak_and_rms_of_a_sine() {
+        let mut meter = LevelMeter::new(48000.0, 2);
+        // Long enough for the RMS integrator to settle
+        let samples: Vec<f32> = (0..144000)
+            .flat_map(|i| {
+                let sample = 0.5 * (2.0 * PI * 1000.0 * i as f32 / 48000.0).sin();
+                [sample, 0.0]
+            })
+            .collect();
+        meter.push(&samples);
+
+        let levels = meter.update().to_vec();
+        assert!((levels[0].peak_db - -6.02).abs() < 0.05, "{}", levels[0].peak_db);
+        assert!((levels[0].rms_db - -9.03).abs() < 0.1, "{}", levels[0].rms_db);
+        assert_eq!(levels[1].peak_db, FLOOR_DB);
+
+        // A new reading starts a new peak interval, RMS keeps integrating
+        let levels = meter.update().to_vec();
+        assert_eq!(levels[0].peak_db, FLOOR_DB);
+        assert!(levels[0].rms_db > -10.0);
+    }
+
+    #[test]
+    fn counts_each_run_of_clipped_samples_once() {
+        let mut meter = LevelMeter::new(48000.0, 1);
+        meter.push(&[0.5, 1.0, 1.2, -1.0, 0.2, -1.5, 0.0]);
+        assert_eq!(meter.update()[0].clips, 2);
+
+        // Clip counts survive readings until they are reset
+        meter.push(&[0.1]);
+        assert_eq!(meter.update()[0].clips, 2);
+        meter.reset_clips();
+        assert_eq!(meter.levels()[0].clips, 0);
+        assert_eq!(meter.update()[0].clips, 0);
+    }
+
+    #[test]
+    fn true_peak_catches_peaks_between_samples() {
+        let mut meter = LevelMeter::new(48000.0, 1);
+        // Quarter-rate sine 45 degrees off its crests: samples at 0.707, true peak at 1.0
+        let samples: Vec<f32> = (0..4800).map(|i| (PI / 2.0 * i as f32 + PI / 4.0).sin()).collect();
+        meter.push(&samples);
+
+        let levels = meter.update();
+        assert!((levels[0].peak_db - -3.01).abs() < 0.05);
+        assert!(levels[0].true_peak_db > -0.5, "{}", levels[0].true_peak_db);
+        assert_eq!(levels[0].clips, 0);
+    }
+}
@@ -0,0 +1,641 @@
+pub mod biquad;
+mod channel_mixer;
+mod compressor;
+mod convolver;
+mod crossfeed;
+mod gain;
+mod karaoke;
+mod ladspa;
+mod limiter;
+mod loudness;
+mod meter;
+mod parametric_eq;
+mod resample;
+mod spectrum;
+mod stereo_width;
+mod true_peak;
+
+pub use channel_mixer::{ChannelMixer, ChannelMixerParams};
+pub use compressor::{Compressor, CompressorParams};
+pub use convolver::{Convolver, ConvolverParams};
+pub use crossfeed::{Crossfeed, CrossfeedParams};
+pub use gain::Gain;
+pub use karaoke::{Karaoke, KaraokeParams};
+pub use ladspa::{scan_plugins as scan_ladspa_plugins, LadspaParams, LadspaPluginInfo};
+pub use limiter::{Limiter, LimiterParams};
+pub use loudness::{Loudness, LoudnessParams};
+pub use meter::{ChannelLevels, LevelMeter};
+pub use parametric_eq::{EqBand, ParametricEq, ParametricEqParams};
+pub use spectrum::{SpectrumAnalyzer, SpectrumFrame, SpectrumSettings};
+pub use stereo_width::{StereoWidth, StereoWidthParams};
+
+use serde_json::Value;
+use std::any::Any;
+
+/// Length of the dry/wet ramp used whenever an effect is added, removed,
+/// bypassed or moved, so chain edits never produce a click.
+const TRANSITION_SECS: f32 = 0.02;
+
+/// Signal-flow order of the built-in stages with dedicated commands. Each one
+/// goes in after the stages listed before it, whichever command created it first:
+/// vocal reduction needs the untouched center image, loudness follows the EQ
+/// curve it compensates, and the headphone and room stages come last.
+const STAGE_ORDER: &[&str] = &[
+    "karaoke",
+    "stereo_width",
+    "parametric_eq",
+    "loudness",
+    "channel_mixer",
+    "crossfeed",
+    "compressor",
+    "convolver",
+];
+
+pub trait AsAny {
+    fn as_any(&self) -> &dyn Any;
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+}
+
+impl<T: Any> AsAny for T {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+/// A processing stage in the playback effect chain.
+///
+/// Effects work in place on interleaved f32 frames at the output device's
+/// sample rate and channel count.
+pub trait AudioEffect: AsAny + Send {
+    /// Identifier used by `create_effect` and reported to the frontend.
+    fn kind(&self) -> &'static str;
+
+    /// Called before processing starts and whenever the stream format changes.
+    fn prepare(&mut self, sample_rate: f32, channels: usize);
+
+    /// Does the expensive part of `prepare` ahead of time, so it can run
+    /// without holding the effect chain lock.
+    fn preload(&mut self, _sample_rate: f32, _channels: usize) {}
+
+    fn process(&mut self, samples: &mut [f32], channels: usize);
+
+    /// Clears filter memories, delay lines and other internal state.
+    fn reset(&mut self);
+
+    /// Delay introduced by the effect, in frames.
+    fn latency(&self) -> usize {
+        0
+    }
+
+    /// Gain at `frequency` in dB, for effects that behave like linear filters.
+    /// Effects without a meaningful magnitude response count as flat.
+    fn response_db(&self, _frequency: f64) -> f64 {
+        0.0
+    }
+
+    fn params(&self) -> Value;
+
+    fn set_params(&mut self, params: Value) -> Result<(), String>;
+}
+
+/// `count` frequencies spaced evenly on a log scale between `low` and `high`.
+pub fn log_frequencies(low: f64, high: f64, count: usize) -> Vec<f64> {
+    let (low, high) = (low.ln(), high.ln());
+    let steps = count.saturating_sub(1).max(1) as f64;
+    (0..count)
+        .map(|i| (low + (high - low) * i as f64 / steps).exp())
+        .collect()
+}
+
+/// Builds an effect from its kind, optionally applying serialized parameters.
+pub fn create_effect(kind: &str, params: Option<Value>) -> Result<Box<dyn AudioEffect>, String> {
+    let mut effect: Box<dyn AudioEffect> = match kind {
+        "gain" => Box::new(Gain::default()),
+        "parametric_eq" => Box::new(ParametricEq::default()),
+        "channel_mixer" => Box::new(ChannelMixer::default()),
+        "crossfeed" => Box::new(Crossfeed::default()),
+        "compressor" => Box::new(Compressor::default()),
+        "limiter" => Box::new(Limiter::default()),
+        "loudness" => Box::new(Loudness::default()),
+        "convolver" => Box::new(Convolver::default()),
+        "karaoke" => Box::new(Karaoke::default()),
+        "stereo_width" => Box::new(StereoWidth::default()),
+        "ladspa" => Box::new(ladspa::LadspaEffect::default()),
+        _ => return Err(format!("Unknown effect: {}", kind)),
+    };
+
+    if let Some(params) = params {
+        effect.set_params(params)?;
+    }
+    Ok(effect)
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct EffectInfo {
+    pub id: u32,
+    pub kind: String,
+    pub bypassed: bool,
+    pub latency: usize,
+    pub params: Value,
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct FrequencyResponse {
+    pub frequencies: Vec<f64>,
+    pub magnitudes_db: Vec<f64>,
+}
+
+struct EffectSlot {
+    id: u32,
+    effect: Box<dyn AudioEffect>,
+    bypassed: bool,
+    removing: bool,
+    move_to: Option<usize>,
+    // 0.0 is fully dry, 1.0 fully processed
+    mix: f32,
+    // Dry signal delayed by the effect's latency, so both paths line up
+    dry_delay: Vec<f32>,
+    dry_delay_pos: usize,
+}
+
+impl EffectSlot {
+    fn target(&self) -> f32 {
+        if self.bypassed || self.removing || self.move_to.is_some() {
+            0.0
+        } else {
+            1.0
+        }
+    }
+
+    /// Delays `dry` in place by the effect's latency.
+    fn delay_dry(&mut self, dry: &mut [f32], channels: usize) {
+        let length = self.effect.latency() * channels;
+        if self.dry_delay.len() != length {
+            self.dry_delay = vec![0.0; length];
+            self.dry_delay_pos = 0;
+        }
+        if length == 0 {
+            return;
+        }
+
+        for sample in dry {
+            std::mem::swap(sample, &mut self.dry_delay[self.dry_delay_pos]);
+            self.dry_delay_pos = (self.dry_delay_pos + 1) % length;
+        }
+    }
+}
+
+/// Ordered list of effects applied to every decoded packet.
+///
+/// Structural changes are applied through a short crossfade: an effect being
+/// removed or moved first fades to dry, and only then leaves its position.
+/// The dry path around an effect with latency is delayed to match, so
+/// bypassing it stays in time and the crossfade doesn't comb-filter.
+pub struct EffectChain {
+    slots: Vec<EffectSlot>,
+    next_id: u32,
+    sample_rate: f32,
+    channels: usize,
+    dry: Vec<f32>,
+}
+
+impl Default for EffectChain {
+    fn default() -> Self {
+        Self {
+            slots: Vec::new(),
+            next_id: 1,
+            sample_rate: 44100.0,
+            channels: 2,
+            dry: Vec::new(),
+        }
+    }
+}
+
+impl EffectChain {
+    pub fn prepare(&mut self, sample_rate: f32, channels: usize) {
+        self.sample_rate = sample_rate;
+        self.channels = channels;
+        for slot in &mut self.slots {
+            slot.effect.prepare(sample_rate, channels);
+            slot.effect.reset();
+        }
+    }
+
+    /// Sample rate and channel count the chain was last prepared for.
+    pub fn format(&self) -> (f32, usize) {
+        (self.sample_rate, self.channels)
+    }
+
+    pub fn reset(&mut self) {
+        for slot in &mut self.slots {
+            slot.effect.reset();
+        }
+    }
+
+    /// Inserts an effect at `position` (or the end) and returns its id.
+    pub fn add(&mut self, mut effect: Box<dyn AudioEffect>, position: Option<usize>) -> u32 {
+        effect.prepare(self.sample_rate, self.channels);
+
+        let id = self.next_id;
+        self.next_id += 1;
+
+        let slot = EffectSlot {
+            id,
+            effect,
+            bypassed: false,
+            removing: false,
+            move_to: None,
+            mix: 0.0,
+            dry_delay: Vec::new(),
+            dry_delay_pos: 0,
+        };
+        let position = position.unwrap_or(self.slots.len()).min(self.slots.len());
+        self.slots.insert(position, slot);
+        id
+    }
+
+    pub fn remove(&mut self, id: u32) -> Result<(), String> {
+        self.slot_mut(id)?.removing = true;
+        Ok(())
+    }
+
+    pub fn set_bypass(&mut self, id: u32, bypassed: bool) -> Result<(), String> {
+        let slot = self.slot_mut(id)?;
+        if !bypassed && slot.mix == 0.0 {
+            // State left over from before the bypass would otherwise leak in
+            slot.effect.reset();
+        }
+        slot.bypassed = bypassed;
+        Ok(())
+    }
+
+    pub fn move_effect(&mut self, id: u32, index: usize) -> Result<(), String> {
+        let slot = self.slot_mut(id)?;
+        slot.move_to = Some(index);
+        Ok(())
+    }
+
+    pub fn set_params(&mut self, id: u32, params: Value) -> Result<(), String> {
+        self.slot_mut(id)?.effect.set_params(params)
+    }
+
+    pub fn effects(&self) -> Vec<EffectInfo> {
+        self.slots
+            .iter()
+            .filter(|slot| !slot.removing)
+            .map(|slot| EffectInfo {
+                id: slot.id,
+                kind: slot.effect.kind().to_string(),
+                bypassed: slot.bypassed,
+                latency: slot.effect.latency(),
+                params: slot.effect.params(),
+            })
+            .collect()
+    }
+
+    /// Id of the first effect of type `T`, if the chain has one.
+    pub fn first_id<T: AudioEffect + 'static>(&self) -> Option<u32> {
+        self.slots
+            .iter()
+            .find(|slot| !slot.removing && (*slot.effect).as_any().is::<T>())
+            .map(|slot| slot.id)
+    }
+
+    pub fn first<T: AudioEffect + 'static>(&self) -> Option<&T> {
+        self.slots
+            .iter()
+            .filter(|slot| !slot.removing)
+            .find_map(|slot| (*slot.effect).as_any().downcast_ref::<T>())
+    }
+
+    pub fn first_mut<T: AudioEffect + 'static>(&mut self) -> Option<&mut T> {
+        self.slots
+            .iter_mut()
+            .filter(|slot| !slot.removing)
+            .find_map(|slot| (*slot.effect).as_any_mut().downcast_mut::<T>())
+    }
+
+    /// Inserts a built-in stage at its place in `STAGE_ORDER`: after the other
+    /// built-in stages that come before it. Other kinds go at the end.
+    pub fn add_stage(&mut self, effect: Box<dyn AudioEffect>) -> u32 {
+        let rank = |kind: &str| STAGE_ORDER.iter().position(|stage| *stage == kind);
+        let position = match rank(effect.kind()) {
+            Some(own) => self
+                .slots
+                .iter()
+                .rposition(|slot| !slot.removing && rank(slot.effect.kind()).is_some_and(|other| other < own))
+                .map_or(0, |index| index + 1),
+            None => self.slots.len(),
+        };
+        self.add(effect, Some(position))
+    }
+
+    /// Returns the first effect of type `T`, inserting one with `add_stage` if
+    /// there is none. Used for built-in stages with dedicated commands.
+    pub fn first_or_insert_with<T: AudioEffect + 'static>(&mut self, create: impl FnOnce() -> T) -> &mut T {
+        let id = match self.first_id::<T>() {
+            Some(id) => id,
+            None => self.add_stage(Box::new(create())),
+        };
+
+        let slot = self
+            .slots
+            .iter_mut()
+            .find(|slot| slot.id == id)
+            .expect("effect was found or inserted above");
+        (*slot.effect)
+            .as_any_mut()
+            .downcast_mut::<T>()
+            .expect("effect type checked above")
+    }
+
+    /// Total delay of the chain in frames. Bypassed effects count too, since
+    /// their dry path is delayed to match.
+    pub fn latency(&self) -> usize {
+        self.slots.iter().map(|slot| slot.effect.latency()).sum()
+    }
+
+    /// Combined magnitude response of the active effects at log-spaced
+    /// frequencies between `low` and `high` (capped at Nyquist).
+    pub fn frequency_response(&self, points: usize, low: f64, high: f64) -> FrequencyResponse {
+        let high = high.min(self.sample_rate as f64 / 2.0);
+        let frequencies = log_frequencies(low.max(1.0), high, points);
+
+        let active: Vec<&EffectSlot> = self.slots.iter().filter(|slot| slot.target() > 0.0).collect();
+        let magnitudes_db = frequencies
+            .iter()
+            .map(|frequency| active.iter().map(|slot| slot.effect.response_db(*frequency)).sum())
+            .collect();
+
+        FrequencyResponse { frequencies, magnitudes_db }
+    }
+
+    pub fn process(&mut self, samples: &mut [f32]) {
+        let Self { slots, sample_rate, channels, dry, .. } = self;
+        let channels = *channels;
+        let step = 1.0 / (TRANSITION_SECS * *sample_rate).max(1.0);
+
+        for slot in slots.iter_mut() {
+            let target = slot.target();
+            let latency = slot.effect.latency();
+            if slot.mix == target && latency == 0 {
+                if target == 1.0 {
+                    slot.effect.process(samples, channels);
+                }
+                continue;
+            }
+
+            dry.clear();
+            dry.extend_from_slice(samples);
+            slot.delay_dry(dry, channels);
+            if slot.mix == target {
+                if target == 1.0 {
+                    slot.effect.process(samples, channels);
+                } else {
+                    samples.copy_from_slice(dry);
+                }
+                continue;
+            }
+            slot.effect.process(samples, channels);
+
+            for (frame, dry_frame) in samples.chunks_exact_mut(channels).zip(dry.chunks_exact(channels)) {
+                slot.mix = if target > slot.mix {
+                    (slot.mix + step).min(target)
+                } else {
+                    (slot.mix - step).max(target)
+                };
+                for (sample, dry_sample) in frame.iter_mut().zip(dry_frame) {
+                    *sample = dry_sample + (*sample - dry_sample) * slot.mix;
+                }
+            }
+        }
+
+        self.settle();
+    }
+
+    /// Applies removals and moves for slots that have finished fading out.
+    fn settle(&mut self) {
+        self.slots.retain(|slot| !(slot.removing && slot.mix == 0.0));
+
+        while let Some(from) = self
+            .slots
+            .iter()
+            .position(|slot| slot.move_to.is_some() && slot.mix == 0.0)
+        {
+            let mut slot = self.slots.remove(from);
+            let index = slot.move_to.take().unwrap_or(from).min(self.slots.len());
+            slot.effect.reset();
+            slot.dry_delay.clear();
+            self.slots.insert(index, slot);
+        }
+    }
+
+    fn slot_mut(&mut self, id: u32) -> Result<&mut EffectSlot, String> {
+        self.slots
+            .iter_mut()
+            .find(|slot| slot.id == id && !slot.removing)
+            .ok_or_else(|| format!("No effect with id {}", id))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Pure delay, so a correctly aligned crossfade leaves the signal untouched.
+    struct Delay {
+        frames: usize,
+        line: Vec<f32>,
+        pos: usize,
+    }
+
+    impl Delay {
+        fn new(frames: usize) -> Self {
+            Self { frames, line: vec![0.0; frames], pos: 0 }
+        }
+    }
+
+    impl AudioEffect for Delay {
+        fn kind(&self) -> &'static str {
+            "delay"
+        }
+
+        fn prepare(&mut self, _sample_rate: f32, channels: usize) {
+            self.line = vec![0.0; self.frames * channels];
+            self.pos = 0;
+        }
+
+        fn process(&mut self, samples: &mut [f32], _channels: usize) {
+            for sample in samples {
+                std::mem::swap(sample, &mut self.line[self.pos]);
+                self.pos = (self.pos + 1) % self.line.len();
+            }
+        }
+
+        fn reset(&mut self) {
+            self.line.iter_mut().for_each(|s| *s = 0.0);
+        }
+
+        fn latency(&self) -> usize {
+            self.frames
+        }
+
+        fn params(&self) -> Value {
+            Value::Null
+        }
+
+        fn set_params(&mut self, _params: Value) -> Result<(), String> {
+            Ok(())
+        }
+    }
+
+    fn chain() -> EffectChain {
+        let mut chain = EffectChain::default();
+        chain.prepare(48000.0, 1);
+        chain
+    }
+
+    fn gain(gain_db: f32) -> Box<dyn AudioEffect> {
+        create_effect("gain", Some(serde_json::json!({ "gain_db": gain_db }))).unwrap()
+    }
+
+    /// Runs `frames` of a ramp through the chain, one 100-frame buffer at a time.
+    fn run(chain: &mut EffectChain, start: usize, frames: usize) -> Vec<f32> {
+        let mut output = Vec::new();
+        for block in (start..start + frames).step_by(100) {
+            let mut samples: Vec<f32> = (block..block + 100).map(|i| i as f32).collect();
+            chain.process(&mut samples);
+            output.extend(samples);
+        }
+        output
+    }
+
+    fn kinds(chain: &EffectChain) -> Vec<String> {
+        chain.effects().into_iter().map(|info| info.kind).collect()
+    }
+
+    #[test]
+    fn added_effect_fades_in() {
+        let mut chain = chain();
+        chain.add(gain(-6.0), None);
+
+        let ramp = (TRANSITION_SECS * 48000.0) as usize;
+        let output = run(&mut chain, 1, 20000);
+        let wet = 10f32.powf(-6.0 / 20.0);
+        assert!((output[0] - 1.0).abs() < 0.01, "no jump at the start");
+        assert!(output[ramp / 2] / (ramp / 2 + 1) as f32 > wet);
+        // Only the gain's own smoothing is left once the ramp is over
+        for (i, sample) in output.iter().enumerate().skip(19000) {
+            assert!((sample / (i + 1) as f32 - wet).abs() < 1e-4);
+        }
+    }
+
+    #[test]
+    fn removed_effect_fades_out_before_leaving() {
+        let mut chain = chain();
+        let id = chain.add(gain(-6.0), None);
+        run(&mut chain, 0, 2000);
+
+        chain.remove(id).unwrap();
+        assert!(chain.effects().is_empty());
+        assert_eq!(chain.slots.len(), 1, "still fading out");
+
+        let output = run(&mut chain, 0, 2000);
+        assert!(chain.slots.is_empty());
+        assert_eq!(output[1999], 1999.0);
+    }
+
+    #[test]
+    fn moved_effect_changes_place_after_fading_out() {
+        let mut chain = chain();
+        let first = chain.add(gain(0.0), None);
+        chain.add(create_effect("compressor", None).unwrap(), None);
+        run(&mut chain, 0, 2000);
+
+        chain.move_effect(first, 1).unwrap();
+        assert_eq!(kinds(&chain), ["gain", "compressor"]);
+        run(&mut chain, 0, 2000);
+        assert_eq!(kinds(&chain), ["compressor", "gain"]);
+        assert!(chain.slots.iter().all(|slot| slot.mix == 1.0), "faded back in");
+    }
+
+    #[test]
+    fn stages_keep_their_order_whichever_is_created_first() {
+        let mut forward = chain();
+        forward.first_or_insert_with(Karaoke::default);
+        forward.first_or_insert_with(ParametricEq::default);
+        forward.first_or_insert_with(Crossfeed::default);
+
+        let mut backward = chain();
+        backward.add(gain(0.0), None);
+        backward.first_or_insert_with(Crossfeed::default);
+        backward.first_or_insert_with(ParametricEq::default);
+        backward.first_or_insert_with(Karaoke::default);
+
+        assert_eq!(kinds(&forward), ["karaoke", "parametric_eq", "crossfeed"]);
+        assert_eq!(kinds(&backward), ["karaoke", "parametric_eq", "crossfeed", "gain"]);
+    }
+
+    #[test]
+    fn log_frequencies_span_the_range() {
+        let frequencies = log_frequencies(20.0, 20000.0, 4);
+        let expected = [20.0, 200.0, 2000.0, 20000.0];
+        for (frequency, expected) in frequencies.iter().zip(expected) {
+            assert!((frequency / expected - 1.0).abs() < 1e-9, "{}", frequency);
+        }
+        let single = log_frequencies(20.0, 20000.0, 1);
+        assert!(single.len() == 1 && (single[0] - 20.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn frequency_response_sums_the_active_effects() {
+        let mut chain = chain();
+        let eq = ParametricEqParams {
+            auto_headroom: false,
+            bands: vec![EqBand::peak(1000.0, 6.0, 1.0)],
+            ..Default::default()
+        };
+        chain.first_or_insert_with(ParametricEq::default).set_eq_params(eq).unwrap();
+        let trim = chain.add(gain(-2.0), None);
+
+        let response = chain.frequency_response(3, 100.0, 10000.0);
+        assert_eq!(response.frequencies.len(), 3);
+        assert!((response.magnitudes_db[1] - 4.0).abs() < 0.01, "{:?}", response.magnitudes_db);
+        assert!(response.magnitudes_db[0] < -1.0 && response.magnitudes_db[2] < -1.0);
+
+        // Bypassed effects drop out right away, before their fade has finished
+        chain.set_bypass(trim, true).unwrap();
+        let response = chain.frequency_response(3, 100.0, 10000.0);
+        assert!((response.magnitudes_db[1] - 6.0).abs() < 0.01);
+    }
+
+    #[test]
+    fn frequency_response_stops_at_nyquist() {
+        let mut chain = EffectChain::default();
+        chain.prepare(32000.0, 2);
+        let response = chain.frequency_response(8, 20.0, 20000.0);
+        assert!((response.frequencies[7] - 16000.0).abs() < 1e-6);
+        assert!(response.magnitudes_db.iter().all(|db| *db == 0.0), "an empty chain is flat");
+    }
+
+    #[test]
+    fn dry_path_is_delayed_to_match_latency() {
+        let mut chain = chain();
+        let id = chain.add(Box::new(Delay::new(37)), None);
+        assert_eq!(chain.latency(), 37);
+
+        // Fading in, fading out to bypass and bypassed: always exactly the delayed input
+        let mut output = run(&mut chain, 0, 2000);
+        chain.set_bypass(id, true).unwrap();
+        output.extend(run(&mut chain, 2000, 2000));
+        assert_eq!(chain.latency(), 37);
+
+        for (i, sample) in output.iter().enumerate().skip(37) {
+            assert!((sample - (i - 37) as f32).abs() < 1e-3, "frame {}: {}", i, sample);
+        }
+    }
+}
@@ -0,0 +1,299 @@
+use super::biquad::{Biquad, Coefficients, FilterType};
+use super::{log_frequencies, AudioEffect};
+use serde_json::Value;
+
+/// Centre frequencies of the 10-band graphic equalizer shown in the UI.
+pub const GRAPHIC_BANDS: [u32; 10] = [32, 64, 125, 250, 500, 1000, 2000, 4000, 8000, 16000];
+
+/// Bandwidth of the graphic bands, roughly one octave.
+const GRAPHIC_Q: f32 = 1.41;
+
+/// Number of log-spaced points used to find the highest boost of the curve.
+const HEADROOM_POINTS: usize = 256;
+
+fn default_enabled() -> bool {
+    true
+}
+
+fn default_q() -> f32 {
+    0.707
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct EqBand {
+    pub filter_type: FilterType,
+    pub frequency: f32,
+    #[serde(default)]
+    pub gain_db: f32,
+    #[serde(default = "default_q")]
+    pub q: f32,
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+}
+
+impl EqBand {
+    pub fn peak(frequency: f32, gain_db: f32, q: f32) -> Self {
+        Self {
+            filter_type: FilterType::Peak,
+            frequency,
+            gain_db,
+            q,
+            enabled: true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct ParametricEqParams {
+    pub preamp_db: f32,
+    /// Lowers the output by the highest boost of the curve so it can't clip.
+    pub auto_headroom: bool,
+    pub bands: Vec<EqBand>,
+}
+
+impl Default for ParametricEqParams {
+    fn default() -> Self {
+        Self {
+            preamp_db: 0.0,
+            auto_headroom: true,
+            bands: Vec::new(),
+        }
+    }
+}
+
+/// Equalizer with any number of biquad bands, a preamp and automatic headroom.
+///
+/// The 10-band graphic equalizer sits on top of the parametric bands, so moving
+/// a slider never disturbs an applied headphone profile and vice versa.
+pub struct ParametricEq {
+    params: ParametricEqParams,
+    /// Gains of `GRAPHIC_BANDS`, in dB.
+    graphic: [f32; GRAPHIC_BANDS.len()],
+    filters: Vec<Biquad>,
+    sample_rate: f32,
+    channels: usize,
+    gain: f32,
+}
+
+impl Default for ParametricEq {
+    fn default() -> Self {
+        let mut eq = Self {
+            params: ParametricEqParams::default(),
+            graphic: [0.0; GRAPHIC_BANDS.len()],
+            filters: Vec::new(),
+            sample_rate: 44100.0,
+            channels: 2,
+            gain: 1.0,
+        };
+        eq.rebuild();
+        eq
+    }
+}
+
+impl ParametricEq {
+    pub fn params(&self) -> &ParametricEqParams {
+        &self.params
+    }
+
+    /// Replaces the parametric bands and preamp. The graphic bands are kept.
+    pub fn set_eq_params(&mut self, params: ParametricEqParams) -> Result<(), String> {
+        if !params.preamp_db.is_finite() {
+            return Err(format!("Invalid preamp: {}", params.preamp_db));
+        }
+        for band in &params.bands {
+            if !(band.frequency.is_finite() && band.frequency > 0.0) {
+                return Err(format!("Invalid band frequency: {}", band.frequency));
+            }
+            if !(band.q.is_finite() && band.q > 0.0) {
+                return Err(format!("Invalid band Q: {}", band.q));
+            }
+            if !band.gain_db.is_finite() {
+                return Err(format!("Invalid band gain: {}", band.gain_db));
+            }
+        }
+
+        self.params = params;
+        self.rebuild();
+        Ok(())
+    }
+
+    pub fn graphic_gains(&self) -> &[f32] {
+        &self.graphic
+    }
+
+    /// Sets the gain of the graphic band at `frequency`.
+    pub fn set_graphic_band(&mut self, frequency: u32, gain_db: f32) -> Result<(), String> {
+        if !gain_db.is_finite() {
+            return Err(format!("Invalid band gain: {}", gain_db));
+        }
+        let index = GRAPHIC_BANDS
+            .iter()
+            .position(|band| *band == frequency)
+            .ok_or_else(|| format!("No graphic band at {} Hz", frequency))?;
+
+        self.graphic[index] = gain_db;
+        self.rebuild();
+        Ok(())
+    }
+
+    /// Sets the graphic bands from `gains` in order; missing gains are flat.
+    pub fn set_graphic_preset(&mut self, gains: &[f32]) -> Result<(), String> {
+        if let Some(gain_db) = gains.iter().find(|gain_db| !gain_db.is_finite()) {
+            return Err(format!("Invalid band gain: {}", gain_db));
+        }
+
+        self.graphic = [0.0; GRAPHIC_BANDS.len()];
+        for (slot, gain_db) in self.graphic.iter_mut().zip(gains) {
+            *slot = *gain_db;
+        }
+        self.rebuild();
+        Ok(())
+    }
+
+    /// Peak filters for the graphic bands, none while they are all flat.
+    fn graphic_bands(&self) -> Vec<EqBand> {
+        if self.graphic.iter().all(|gain_db| *gain_db == 0.0) {
+            return Vec::new();
+        }
+        GRAPHIC_BANDS
+            .iter()
+            .zip(&self.graphic)
+            .map(|(frequency, gain_db)| EqBand::peak(*frequency as f32, *gain_db, GRAPHIC_Q))
+            .collect()
+    }
+
+    /// Combined gain of the enabled bands at `frequency`, without preamp or headroom.
+    fn bands_response_db(&self, frequency: f64) -> f64 {
+        self.filters
+            .iter()
+            .map(|filter| filter.coefficients().magnitude_db(frequency, self.sample_rate as f64))
+            .sum()
+    }
+
+    fn headroom_db(&self) -> f64 {
+        if !self.params.auto_headroom || self.filters.is_empty() {
+            return 0.0;
+        }
+
+        let nyquist = self.sample_rate as f64 / 2.0;
+        log_frequencies(20.0, nyquist.min(20000.0), HEADROOM_POINTS)
+            .into_iter()
+            .map(|frequency| self.bands_response_db(frequency))
+            .fold(0.0, f64::max)
+    }
+
+    fn rebuild(&mut self) {
+        let graphic = self.graphic_bands();
+        let coefficients: Vec<Coefficients> = self
+            .params
+            .bands
+            .iter()
+            .chain(&graphic)
+            .filter(|band| band.enabled)
+            .map(|band| Coefficients::design(band.filter_type, band.frequency, band.gain_db, band.q, self.sample_rate))
+            .collect();
+
+        // Keep the running filters when only their settings changed
+        if coefficients.len() == self.filters.len() {
+            for (filter, c) in self.filters.iter_mut().zip(coefficients) {
+                filter.set_coefficients(c);
+            }
+        } else {
+            self.filters = coefficients.into_iter().map(|c| Biquad::new(c, self.channels)).collect();
+        }
+
+        let gain_db = self.params.preamp_db as f64 - self.headroom_db();
+        self.gain = 10f64.powf(gain_db / 20.0) as f32;
+    }
+}
+
+impl AudioEffect for ParametricEq {
+    fn kind(&self) -> &'static str {
+        "parametric_eq"
+    }
+
+    fn prepare(&mut self, sample_rate: f32, channels: usize) {
+        self.sample_rate = sample_rate;
+        self.channels = channels;
+        self.filters.clear();
+        self.rebuild();
+    }
+
+    fn process(&mut self, samples: &mut [f32], channels: usize) {
+        if self.gain != 1.0 {
+            samples.iter_mut().for_each(|sample| *sample *= self.gain);
+        }
+        for filter in &mut self.filters {
+            filter.process(samples, channels);
+        }
+    }
+
+    fn reset(&mut self) {
+        self.filters.iter_mut().for_each(Biquad::reset);
+    }
+
+    fn response_db(&self, frequency: f64) -> f64 {
+        20.0 * (self.gain as f64).log10() + self.bands_response_db(frequency)
+    }
+
+    fn params(&self) -> Value {
+        serde_json::to_value(&self.params).unwrap_or(Value::Null)
+    }
+
+    fn set_params(&mut self, params: Value) -> Result<(), String> {
+        let params = serde_json::from_value(params).map_err(|e| format!("Invalid equalizer parameters: {}", e))?;
+        self.set_eq_params(params)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn eq() -> ParametricEq {
+        let mut eq = ParametricEq::default();
+        eq.prepare(48000.0, 2);
+        eq
+    }
+
+    fn profile() -> ParametricEqParams {
+        ParametricEqParams {
+            preamp_db: -3.0,
+            auto_headroom: false,
+            bands: vec![EqBand::peak(3000.0, 4.0, 2.0)],
+        }
+    }
+
+    #[test]
+    fn rejects_non_finite_gains() {
+        let mut eq = eq();
+        let mut params = profile();
+        params.bands[0].gain_db = f32::NAN;
+        assert!(eq.set_eq_params(params).is_err());
+
+        let params = ParametricEqParams { preamp_db: f32::INFINITY, ..profile() };
+        assert!(eq.set_eq_params(params).is_err());
+        assert!(eq.set_graphic_band(1000, f32::NAN).is_err());
+        assert!(eq.set_graphic_preset(&[0.0, f32::NEG_INFINITY]).is_err());
+        assert!(eq.params().bands.is_empty(), "nothing was applied");
+    }
+
+    #[test]
+    fn graphic_bands_leave_the_profile_alone() {
+        let mut eq = eq();
+        eq.set_eq_params(profile()).unwrap();
+        let profile_db = eq.response_db(3000.0);
+
+        eq.set_graphic_preset(&[0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 6.0]).unwrap();
+        eq.set_graphic_band(125, -2.0).unwrap();
+        assert_eq!(eq.params().bands.len(), 1);
+        assert_eq!(eq.graphic_gains()[2], -2.0);
+        assert!(eq.response_db(3000.0) > profile_db + 1.0, "both curves apply");
+
+        // A new profile keeps the sliders
+        eq.set_eq_params(ParametricEqParams::default()).unwrap();
+        assert_eq!(eq.graphic_gains()[7], 6.0);
+        assert!(eq.set_graphic_band(1001, 1.0).is_err());
+    }
+}
@@ -0,0 +1,223 @@
+use super::log_frequencies;
+use realfft::num_complex::Complex;
+use realfft::{RealFftPlanner, RealToComplex};
+use std::f32::consts::PI;
+use std::sync::Arc;
+
+/// Level reported for silent bands.
+const FLOOR_DB: f32 = -120.0;
+
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct SpectrumSettings {
+    pub enabled: bool,
+    /// FFT length in samples; a power of two.
+    pub fft_size: usize,
+    /// Number of log-spaced bands between `min_frequency` and `max_frequency`.
+    pub bands: usize,
+    pub min_frequency: f32,
+    pub max_frequency: f32,
+    /// How slowly bands fall back after a peak (0 = no smoothing).
+    pub smoothing: f32,
+    /// Frames sent to the frontend per second.
+    pub fps: u32,
+}
+
+impl Default for SpectrumSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            fft_size: 2048,
+            bands: 64,
+            min_frequency: 20.0,
+            max_frequency: 20000.0,
+            smoothing: 0.7,
+            fps: 30,
+        }
+    }
+}
+
+impl SpectrumSettings {
+    pub fn validate(&self) -> Result<(), String> {
+        if !self.fft_size.is_power_of_two() || !(256..=16384).contains(&self.fft_size) {
+            return Err("FFT size must be a power of two between 256 and 16384".to_string());
+        }
+        if !(4..=512).contains(&self.bands) {
+            return Err("Band count must be between 4 and 512".to_string());
+        }
+        if self.min_frequency <= 0.0 || self.max_frequency <= self.min_frequency {
+            return Err("Invalid spectrum frequency range".to_string());
+        }
+        if !(0.0..1.0).contains(&self.smoothing) {
+            return Err("Smoothing must be between 0 and 1".to_string());
+        }
+        if !(1..=60).contains(&self.fps) {
+            return Err("Frame rate must be between 1 and 60".to_string());
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct SpectrumFrame {
+    /// Center frequency of each band.
+    pub frequencies: Vec<f32>,
+    /// Band levels in dBFS.
+    pub magnitudes_db: Vec<f32>,
+    /// Per-channel sample peak since the previous frame (linear).
+    pub peak: Vec<f32>,
+    /// Per-channel RMS since the previous frame (linear).
+    pub rms: Vec<f32>,
+}
+
+/// Center frequencies of `count` log-spaced bands and the `count + 1` edges
+/// between them. Edges sit halfway (on a log scale) between neighbouring
+/// centers, and the outer bands are as wide as the inner ones.
+fn bands(min_frequency: f32, max_frequency: f32, count: usize) -> (Vec<f32>, Vec<f32>) {
+    let frequencies: Vec<f32> = log_frequencies(min_frequency as f64, max_frequency as f64, count)
+        .into_iter()
+        .map(|f| f as f32)
+        .collect();
+    let ratio = (max_frequency / min_frequency).powf(0.5 / count.saturating_sub(1).max(1) as f32);
+    let mut edges: Vec<f32> = frequencies.iter().map(|f| f / ratio).collect();
+    edges.push(max_frequency * ratio);
+    (frequencies, edges)
+}
+
+/// Turns the output signal into smoothed log-frequency bands for the visualizer.
+pub struct SpectrumAnalyzer {
+    settings: SpectrumSettings,
+    sample_rate: f32,
+    channels: usize,
+    fft: Arc<dyn RealToComplex<f32>>,
+    window: Vec<f32>,
+    window_gain: f32,
+    // Most recent fft_size samples of the channel average
+    history: Vec<f32>,
+    history_pos: usize,
+    input: Vec<f32>,
+    spectrum: Vec<Complex<f32>>,
+    scratch: Vec<Complex<f32>>,
+    frequencies: Vec<f32>,
+    edges: Vec<f32>,
+    smoothed: Vec<f32>,
+    peak: Vec<f32>,
+    sum_squares: Vec<f64>,
+    frames: usize,
+}
+
+impl SpectrumAnalyzer {
+    pub fn new(settings: SpectrumSettings, sample_rate: f32, channels: usize) -> Self {
+        let size = settings.fft_size;
+        let fft = RealFftPlanner::<f32>::new().plan_fft_forward(size);
+        let window: Vec<f32> = (0..size).map(|i| 0.5 - 0.5 * (2.0 * PI * i as f32 / size as f32).cos()).collect();
+        let window_gain = window.iter().sum::<f32>() / 2.0;
+
+        let max_frequency = settings.max_frequency.min(sample_rate / 2.0);
+        let (frequencies, edges) = bands(settings.min_frequency, max_frequency, settings.bands);
+
+        Self {
+            sample_rate,
+            channels,
+            scratch: fft.make_scratch_vec(),
+            spectrum: fft.make_output_vec(),
+            input: vec![0.0; size],
+            history: vec![0.0; size],
+            history_pos: 0,
+            fft,
+            window,
+            window_gain,
+            smoothed: vec![FLOOR_DB; frequencies.len()],
+            frequencies,
+            edges,
+            peak: vec![0.0; channels],
+            sum_squares: vec![0.0; channels],
+            frames: 0,
+            settings,
+        }
+    }
+
+    pub fn settings(&self) -> &SpectrumSettings {
+        &self.settings
+    }
+
+    pub fn push(&mut self, samples: &[f32]) {
+        let size = self.history.len();
+        for frame in samples.chunks_exact(self.channels) {
+            let mut sum = 0.0;
+            for (channel, sample) in frame.iter().enumerate() {
+                self.peak[channel] = self.peak[channel].max(sample.abs());
+                self.sum_squares[channel] += (*sample as f64).powi(2);
+                sum += sample;
+            }
+            self.history[self.history_pos] = sum / self.channels as f32;
+            self.history_pos = (self.history_pos + 1) % size;
+        }
+        self.frames += samples.len() / self.channels;
+    }
+
+    /// Analyzes the latest FFT window and resets the peak and RMS accumulators.
+    pub fn frame(&mut self) -> SpectrumFrame {
+        let size = self.history.len();
+        for (i, value) in self.input.iter_mut().enumerate() {
+            *value = self.history[(self.history_pos + i) % size] * self.window[i];
+        }
+        let _ = self.fft.process_with_scratch(&mut self.input, &mut self.spectrum, &mut self.scratch);
+
+        let bin_width = self.sample_rate / size as f32;
+        let smoothing = self.settings.smoothing;
+        for (band, level) in self.smoothed.iter_mut().enumerate() {
+            let low = (self.edges[band] / bin_width).ceil() as usize;
+            let high = ((self.edges[band + 1] / bin_width).floor() as usize).min(self.spectrum.len() - 1);
+
+            // Narrow low bands may fall between bins; use the nearest one then
+            let magnitude = if low <= high {
+                self.spectrum[low..=high].iter().map(|bin| bin.norm()).fold(0.0, f32::max)
+            } else {
+                let nearest = ((self.frequencies[band] / bin_width).round() as usize).min(self.spectrum.len() - 1);
+                self.spectrum[nearest].norm()
+            };
+            let db = (20.0 * (magnitude / self.window_gain).max(1e-9).log10()).max(FLOOR_DB);
+
+            // Rise immediately, fall back smoothly
+            *level = if db > *level { db } else { *level * smoothing + db * (1.0 - smoothing) };
+        }
+
+        let frames = self.frames.max(1) as f64;
+        let rms = self.sum_squares.iter().map(|sum| (sum / frames).sqrt() as f32).collect();
+        let peak = std::mem::replace(&mut self.peak, vec![0.0; self.channels]);
+        self.sum_squares.iter_mut().for_each(|sum| *sum = 0.0);
+        self.frames = 0;
+
+        SpectrumFrame {
+            frequencies: self.frequencies.clone(),
+            magnitudes_db: self.smoothed.clone(),
+            peak,
+            rms,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn band_edges_are_geometric_midpoints() {
+        let (frequencies, edges) = bands(20.0, 20000.0, 64);
+        assert_eq!(edges.len(), frequencies.len() + 1);
+        for (i, pair) in frequencies.windows(2).enumerate() {
+            let midpoint = (pair[0] * pair[1]).sqrt();
+            assert!((edges[i + 1] / midpoint - 1.0).abs() < 1e-4, "edge {} at {} instead of {}", i + 1, edges[i + 1], midpoint);
+        }
+    }
+
+    #[test]
+    fn outer_bands_match_inner_width() {
+        let (_, edges) = bands(20.0, 20000.0, 16);
+        let widths: Vec<f32> = edges.windows(2).map(|pair| pair[1] / pair[0]).collect();
+        for width in &widths {
+            assert!((width / widths[1] - 1.0).abs() < 1e-4, "band widths differ: {:?}", widths);
+        }
+    }
+}
@@ -0,0 +1,175 @@
+use super::AudioEffect;
+use serde_json::Value;
+
+/// Time constant of the gain smoothing, so parameter changes don't zipper.
+const SMOOTHING_SECS: f32 = 0.01;
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct StereoWidthParams {
+    /// 0 is mono, 1 leaves the image unchanged, 2 doubles the side signal.
+    pub width: f32,
+    pub mid_gain_db: f32,
+    pub side_gain_db: f32,
+}
+
+impl Default for StereoWidthParams {
+    fn default() -> Self {
+        Self {
+            width: 1.0,
+            mid_gain_db: 0.0,
+            side_gain_db: 0.0,
+        }
+    }
+}
+
+impl StereoWidthParams {
+    fn normalized(mut self) -> Self {
+        self.width = self.width.clamp(0.0, 2.0);
+        self.mid_gain_db = self.mid_gain_db.clamp(-24.0, 12.0);
+        self.side_gain_db = self.side_gain_db.clamp(-24.0, 12.0);
+        self
+    }
+
+    /// Linear gains applied to the mid and side signals.
+    fn gains(&self) -> (f32, f32) {
+        let mid = 10f32.powf(self.mid_gain_db / 20.0);
+        let side = 10f32.powf(self.side_gain_db / 20.0) * self.width;
+        (mid, side)
+    }
+}
+
+/// Mid/side stage for the first two channels: the stereo image is split into
+/// its mid (L+R) and side (L−R) parts, which are scaled independently.
+pub struct StereoWidth {
+    params: StereoWidthParams,
+    coefficient: f32,
+    mid: f32,
+    side: f32,
+}
+
+impl Default for StereoWidth {
+    fn default() -> Self {
+        Self {
+            params: StereoWidthParams::default(),
+            coefficient: 0.0,
+            mid: 1.0,
+            side: 1.0,
+        }
+    }
+}
+
+impl StereoWidth {
+    pub fn width_params(&self) -> &StereoWidthParams {
+        &self.params
+    }
+
+    pub fn set_width_params(&mut self, params: StereoWidthParams) {
+        self.params = params.normalized();
+    }
+}
+
+impl AudioEffect for StereoWidth {
+    fn kind(&self) -> &'static str {
+        "stereo_width"
+    }
+
+    fn prepare(&mut self, sample_rate: f32, _channels: usize) {
+        self.coefficient = (-1.0 / (SMOOTHING_SECS * sample_rate)).exp();
+    }
+
+    fn process(&mut self, samples: &mut [f32], channels: usize) {
+        if channels < 2 {
+            return;
+        }
+
+        let (mid_target, side_target) = self.params.gains();
+        for frame in samples.chunks_exact_mut(channels) {
+            self.mid = mid_target + (self.mid - mid_target) * self.coefficient;
+            self.side = side_target + (self.side - side_target) * self.coefficient;
+
+            let mid = (frame[0] + frame[1]) * 0.5 * self.mid;
+            let side = (frame[0] - frame[1]) * 0.5 * self.side;
+            frame[0] = mid + side;
+            frame[1] = mid - side;
+        }
+    }
+
+    fn reset(&mut self) {
+        (self.mid, self.side) = self.params.gains();
+    }
+
+    fn params(&self) -> Value {
+        serde_json::to_value(&self.params).unwrap_or(Value::Null)
+    }
+
+    fn set_params(&mut self, params: Value) -> Result<(), String> {
+        let params = serde_json::from_value(params).map_err(|e| format!("Invalid stereo width parameters: {}", e))?;
+        self.set_width_params(params);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn prepared(params: StereoWidthParams) -> StereoWidth {
+        let mut width = StereoWidth::default();
+        width.prepare(48000.0, 2);
+        width.set_width_params(params);
+        width.reset();
+        width
+    }
+
+    fn close(actual: &[f32], expected: &[f32]) -> bool {
+        actual.iter().zip(expected).all(|(a, e)| (a - e).abs() < 1e-6)
+    }
+
+    #[test]
+    fn unity_width_is_transparent() {
+        let mut width = prepared(StereoWidthParams::default());
+        let mut samples = [0.5, -0.1, 0.3, 0.3, 0.7, 0.0];
+        width.process(&mut samples, 2);
+        assert!(close(&samples, &[0.5, -0.1, 0.3, 0.3, 0.7, 0.0]), "{:?}", samples);
+    }
+
+    #[test]
+    fn width_scales_the_side_signal() {
+        let mut mono = prepared(StereoWidthParams { width: 0.0, ..Default::default() });
+        let mut samples = [0.6, 0.2];
+        mono.process(&mut samples, 2);
+        assert!(close(&samples, &[0.4, 0.4]), "{:?}", samples);
+
+        let mut wide = prepared(StereoWidthParams { width: 2.0, ..Default::default() });
+        let mut samples = [0.6, 0.2];
+        wide.process(&mut samples, 2);
+        assert!(close(&samples, &[0.8, 0.0]), "{:?}", samples);
+    }
+
+    #[test]
+    fn mid_and_side_gains_apply_separately() {
+        let params = StereoWidthParams { mid_gain_db: -6.0206, side_gain_db: 6.0206, width: 1.0 };
+        let mut width = prepared(params);
+        // Mid 0.4 and side 0.2 become 0.2 and 0.4
+        let mut samples = [0.6, 0.2, 0.0, 0.0, 0.0, 0.0, 1.0, 0.5];
+        width.process(&mut samples, 4);
+        assert!(close(&samples[..2], &[0.6, -0.2]), "{:?}", samples);
+        assert!(close(&samples[6..], &[1.0, 0.5]), "other channels untouched");
+    }
+
+    #[test]
+    fn changes_are_smoothed_and_clamped() {
+        let mut width = prepared(StereoWidthParams::default());
+        width.set_width_params(StereoWidthParams { width: 5.0, mid_gain_db: -40.0, side_gain_db: 20.0 });
+        assert_eq!(width.width_params().width, 2.0);
+        assert_eq!(width.width_params().mid_gain_db, -24.0);
+        assert_eq!(width.width_params().side_gain_db, 12.0);
+
+        width.set_width_params(StereoWidthParams { width: 0.0, ..Default::default() });
+        let mut samples: Vec<f32> = [1.0, -1.0].repeat(4800);
+        width.process(&mut samples, 2);
+        assert!(samples[0] > 0.9, "no jump: {}", samples[0]);
+        assert!(samples[samples.len() - 2].abs() < 1e-3);
+    }
+}
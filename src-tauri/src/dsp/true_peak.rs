@@ -0,0 +1,107 @@
+use std::f32::consts::PI;
+
+/// Samples kept for the inter-sample peak estimate.
+const TAPS: usize = 8;
+
+/// Fractional positions between samples checked for inter-sample peaks (4x oversampling).
+const PHASES: [f32; 3] = [0.25, 0.5, 0.75];
+
+/// Frames by which the true-peak estimate trails the newest sample.
+pub const TRUE_PEAK_DELAY: usize = TAPS / 2;
+
+/// True-peak detector for one channel: the signal is interpolated at 4x the
+/// sample rate, catching peaks between samples that would clip after the DAC's
+/// reconstruction filter.
+#[derive(Clone)]
+pub struct TruePeakDetector {
+    interpolation: [[f32; TAPS]; 3],
+    history: [f32; TAPS],
+}
+
+impl Default for TruePeakDetector {
+    fn default() -> Self {
+        Self {
+            interpolation: interpolation_taps(),
+            history: [0.0; TAPS],
+        }
+    }
+}
+
+/// Hann-windowed sinc taps for each fractional position, normalized to unity DC gain.
+fn interpolation_taps() -> [[f32; TAPS]; 3] {
+    let half = (TAPS / 2) as f32;
+    let mut taps = [[0.0; TAPS]; 3];
+
+    for (phase, fraction) in PHASES.iter().enumerate() {
+        for (k, tap) in taps[phase].iter_mut().enumerate() {
+            // Tap k weighs the sample at offset k - (half - 1) from the interpolated position
+            let t = k as f32 - (half - 1.0) - fraction;
+            let sinc = if t == 0.0 { 1.0 } else { (PI * t).sin() / (PI * t) };
+            let window = 0.5 * (1.0 + (PI * t / half).cos());
+            *tap = sinc * window;
+        }
+        let sum: f32 = taps[phase].iter().sum();
+        taps[phase].iter_mut().for_each(|tap| *tap /= sum);
+    }
+    taps
+}
+
+impl TruePeakDetector {
+    /// Feeds one sample and returns the absolute true peak of the interval
+    /// `TRUE_PEAK_DELAY` samples back.
+    pub fn process(&mut self, sample: f32) -> f32 {
+        self.history.rotate_left(1);
+        self.history[TAPS - 1] = sample;
+
+        let mut peak = self.history[TAPS - 1 - TRUE_PEAK_DELAY].abs();
+        for taps in &self.interpolation {
+            let value: f32 = taps.iter().zip(self.history.iter()).map(|(t, x)| t * x).sum();
+            peak = peak.max(value.abs());
+        }
+        peak
+    }
+
+    pub fn reset(&mut self) {
+        self.history = [0.0; TAPS];
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reports_the_interval_true_peak_delay_back() {
+        let mut detector = TruePeakDetector::default();
+        let mut peaks = Vec::new();
+        for i in 0..16 {
+            peaks.push(detector.process(if i == 0 { 0.8 } else { 0.0 }));
+        }
+
+        // The impulse itself shows up TRUE_PEAK_DELAY samples later
+        assert!(peaks[..TRUE_PEAK_DELAY].iter().all(|peak| *peak < 0.8));
+        assert_eq!(peaks[TRUE_PEAK_DELAY], 0.8);
+    }
+
+    #[test]
+    fn interpolation_passes_dc_unchanged() {
+        let mut detector = TruePeakDetector::default();
+        let mut peak = 0.0;
+        for _ in 0..TAPS * 2 {
+            peak = detector.process(-0.5);
+        }
+        assert!((peak - 0.5).abs() < 1e-6);
+    }
+
+    #[test]
+    fn finds_inter_sample_peaks() {
+        let mut detector = TruePeakDetector::default();
+        // A sine at a quarter of the sample rate, sampled 45 degrees off its crests
+        let samples = (0..64).map(|i| (std::f32::consts::PI / 2.0 * i as f32 + std::f32::consts::PI / 4.0).sin());
+        let peak = samples.map(|sample| detector.process(sample)).skip(TAPS).fold(0.0, f32::max);
+        assert!(peak > 0.95 && peak < 1.05, "{}", peak);
+
+        detector.reset();
+        assert_eq!(detector.process(0.0), 0.0);
+    }
+}
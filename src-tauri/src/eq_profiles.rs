@@ -0,0 +1,466 @@
+use crate::dsp::biquad::{Coefficients, FilterType};
+use crate::dsp::{EqBand, ParametricEqParams};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Sample rate used when fitting GraphicEQ curves. The fitted peak filters are
+/// bilinear-transformed at the device rate, so at other rates the bands near
+/// Nyquist (the top one sits at 20480 Hz) land slightly off the curve.
+const FIT_SAMPLE_RATE: f32 = 48000.0;
+
+/// Bandwidth of the peak filters used to approximate a GraphicEQ curve (1/3 octave).
+const FIT_Q: f32 = 4.32;
+
+/// The GraphicEQ fit stops once every band is within this distance of the curve.
+const FIT_TOLERANCE_DB: f32 = 0.01;
+
+const FIT_MAX_ITERATIONS: usize = 100;
+
+/// Default Q for filters that don't specify one, matching EqualizerAPO's shelf slope of 1.
+const DEFAULT_Q: f32 = 0.707;
+
+pub struct ApoProfile {
+    pub params: ParametricEqParams,
+    /// Largest deviation of the fitted filters from a GraphicEQ curve, in dB.
+    pub fit_error_db: Option<f32>,
+}
+
+/// Parses an EqualizerAPO configuration (as published by AutoEQ) into
+/// parametric EQ settings.
+///
+/// Supports `Preamp:`, `Filter:` and `GraphicEQ:` lines. GraphicEQ curves are
+/// approximated with a bank of 1/3-octave peak filters.
+pub fn parse_equalizer_apo(text: &str) -> Result<ApoProfile, String> {
+    let mut preamp_db = 0.0;
+    let mut has_preamp = false;
+    let mut bands = Vec::new();
+    let mut fit_error_db: Option<f32> = None;
+
+    for (index, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let Some((key, value)) = line.split_once(':') else {
+            continue;
+        };
+        let key = key.trim();
+
+        if key.eq_ignore_ascii_case("preamp") {
+            preamp_db += parse_preamp(value).map_err(|e| format!("Line {}: {}", index + 1, e))?;
+            has_preamp = true;
+        } else if key.to_ascii_lowercase().starts_with("filter") {
+            let band = parse_filter(value).map_err(|e| format!("Line {}: {}", index + 1, e))?;
+            bands.push(band);
+        } else if key.eq_ignore_ascii_case("graphiceq") {
+            let points = parse_graphic_eq(value).map_err(|e| format!("Line {}: {}", index + 1, e))?;
+            let (fitted, error_db) = fit_graphic_eq(&points);
+            bands.extend(fitted);
+            fit_error_db = Some(fit_error_db.unwrap_or(0.0).max(error_db));
+        }
+    }
+
+    if bands.is_empty() && !has_preamp {
+        return Err("No EqualizerAPO filters found".to_string());
+    }
+
+    Ok(ApoProfile {
+        params: ParametricEqParams {
+            preamp_db,
+            // An explicit preamp already provides the headroom the profile needs
+            auto_headroom: !has_preamp,
+            bands,
+        },
+        fit_error_db,
+    })
+}
+
+fn parse_number(token: Option<&str>, what: &str) -> Result<f32, String> {
+    token
+        .and_then(|t| t.trim_end_matches(|c: char| c.is_ascii_alphabetic()).parse().ok())
+        .ok_or_else(|| format!("Invalid {}", what))
+}
+
+fn parse_preamp(value: &str) -> Result<f32, String> {
+    parse_number(value.split_whitespace().next(), "preamp")
+}
+
+fn parse_filter(value: &str) -> Result<EqBand, String> {
+    let mut tokens = value.split_whitespace();
+
+    let enabled = match tokens.next() {
+        Some(state) if state.eq_ignore_ascii_case("on") => true,
+        Some(state) if state.eq_ignore_ascii_case("off") => false,
+        _ => return Err("Expected ON or OFF".to_string()),
+    };
+
+    let filter_type = match tokens.next().map(|t| t.to_ascii_uppercase()).as_deref() {
+        Some("PK") | Some("PEQ") | Some("MODAL") => FilterType::Peak,
+        Some("LS") | Some("LSC") | Some("LSQ") => FilterType::LowShelf,
+        Some("HS") | Some("HSC") | Some("HSQ") => FilterType::HighShelf,
+        Some("LP") | Some("LPQ") => FilterType::LowPass,
+        Some("HP") | Some("HPQ") => FilterType::HighPass,
+        Some("NO") => FilterType::Notch,
+        Some(other) => return Err(format!("Unsupported filter type: {}", other)),
+        None => return Err("Missing filter type".to_string()),
+    };
+
+    let mut frequency = None;
+    let mut gain_db = 0.0;
+    let mut q = DEFAULT_Q;
+
+    while let Some(token) = tokens.next() {
+        match token.to_ascii_lowercase().as_str() {
+            "fc" => frequency = Some(parse_number(tokens.next(), "frequency")?),
+            "gain" => gain_db = parse_number(tokens.next(), "gain")?,
+            "q" => q = parse_number(tokens.next(), "Q")?,
+            "bw" => {
+                // "BW Oct 1.0": convert the bandwidth in octaves to Q
+                if tokens.next().map(|t| t.eq_ignore_ascii_case("oct")) != Some(true) {
+                    return Err("Only octave bandwidths are supported".to_string());
+                }
+                let octaves = parse_number(tokens.next(), "bandwidth")?;
+                let ratio = 2f32.powf(octaves);
+                q = ratio.sqrt() / (ratio - 1.0);
+            }
+            // Units and slope markers such as "Hz", "dB" or "12dB"
+            _ => {}
+        }
+    }
+
+    let frequency = frequency.ok_or("Missing filter frequency")?;
+    Ok(EqBand {
+        filter_type,
+        frequency,
+        gain_db,
+        q,
+        enabled,
+    })
+}
+
+fn parse_graphic_eq(value: &str) -> Result<Vec<(f32, f32)>, String> {
+    let mut points: Vec<(f32, f32)> = value
+        .split(';')
+        .filter(|pair| !pair.trim().is_empty())
+        .map(|pair| {
+            let mut parts = pair.split_whitespace();
+            let frequency = parse_number(parts.next(), "GraphicEQ frequency")?;
+            let gain = parse_number(parts.next(), "GraphicEQ gain")?;
+            Ok((frequency, gain))
+        })
+        .collect::<Result<_, String>>()?;
+
+    if points.is_empty() {
+        return Err("Empty GraphicEQ curve".to_string());
+    }
+    points.sort_by(|a, b| a.0.total_cmp(&b.0));
+    Ok(points)
+}
+
+/// Gain of a GraphicEQ curve at `frequency`, interpolated on a log-frequency axis.
+fn curve_gain(points: &[(f32, f32)], frequency: f32) -> f32 {
+    let first = points[0];
+    let last = points[points.len() - 1];
+    if frequency <= first.0 {
+        return first.1;
+    }
+    if frequency >= last.0 {
+        return last.1;
+    }
+
+    let upper = points.iter().position(|p| p.0 >= frequency).unwrap_or(points.len() - 1);
+    let (f0, g0) = points[upper - 1];
+    let (f1, g1) = points[upper];
+    let t = (frequency.ln() - f0.ln()) / (f1.ln() - f0.ln());
+    g0 + (g1 - g0) * t
+}
+
+/// Approximates a GraphicEQ curve with 1/3-octave peak filters, iteratively
+/// correcting each band for the overlap of its neighbours until the response
+/// at every band center is within `FIT_TOLERANCE_DB` of the curve.
+///
+/// Returns the bands and the largest remaining error at the band centers.
+fn fit_graphic_eq(points: &[(f32, f32)]) -> (Vec<EqBand>, f32) {
+    let centers: Vec<f32> = (0..31).map(|i| 20.0 * 2f32.powf(i as f32 / 3.0)).collect();
+    let targets: Vec<f32> = centers.iter().map(|f| curve_gain(points, *f)).collect();
+    let mut gains = targets.clone();
+    let mut error_db = f32::INFINITY;
+
+    for _ in 0..FIT_MAX_ITERATIONS {
+        let residuals = fit_residuals(&centers, &gains, &targets);
+        error_db = residuals.iter().fold(0.0, |max, r| max.max(r.abs()));
+        if error_db < FIT_TOLERANCE_DB {
+            break;
+        }
+        for (gain, residual) in gains.iter_mut().zip(residuals) {
+            *gain += residual;
+        }
+    }
+
+    let bands = centers
+        .into_iter()
+        .zip(gains)
+        .map(|(frequency, gain_db)| EqBand::peak(frequency, gain_db, FIT_Q))
+        .collect();
+    (bands, error_db)
+}
+
+/// Difference between the target curve and the combined response of the
+/// peak filters, at each band center.
+fn fit_residuals(centers: &[f32], gains: &[f32], targets: &[f32]) -> Vec<f32> {
+    let coefficients: Vec<Coefficients> = centers
+        .iter()
+        .zip(gains)
+        .map(|(f, g)| Coefficients::design(FilterType::Peak, *f, *g, FIT_Q, FIT_SAMPLE_RATE))
+        .collect();
+
+    centers
+        .iter()
+        .zip(targets)
+        .map(|(frequency, target)| {
+            let response: f64 = coefficients
+                .iter()
+                .map(|c| c.magnitude_db(*frequency as f64, FIT_SAMPLE_RATE as f64))
+                .sum();
+            target - response as f32
+        })
+        .collect()
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct EqProfileInfo {
+    pub name: String,
+    pub preamp_db: f32,
+    pub band_count: usize,
+    /// Largest deviation from the GraphicEQ curve, for fitted profiles.
+    pub fit_error_db: Option<f32>,
+    pub devices: Vec<String>,
+}
+
+#[derive(Debug, Default, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+struct StoredProfiles {
+    profiles: BTreeMap<String, ParametricEqParams>,
+    /// Output device name -> profile name
+    devices: BTreeMap<String, String>,
+    /// Profile name -> GraphicEQ fit error in dB
+    fit_errors: BTreeMap<String, f32>,
+}
+
+/// Named EQ profiles and their output device assignments, persisted as JSON
+/// in the app data directory.
+#[derive(Default)]
+pub struct EqProfileStore {
+    path: Option<PathBuf>,
+    data: StoredProfiles,
+}
+
+impl EqProfileStore {
+    pub fn load(&mut self, path: PathBuf) {
+        if let Ok(content) = fs::read_to_string(&path) {
+            match serde_json::from_str(&content) {
+                Ok(data) => self.data = data,
+                Err(e) => eprintln!("Failed to read EQ profiles: {}", e),
+            }
+        }
+        self.path = Some(path);
+    }
+
+    fn save(&self) -> Result<(), String> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).map_err(|e| format!("Failed to create profile directory: {}", e))?;
+        }
+        let content = serde_json::to_string_pretty(&self.data).map_err(|e| format!("Failed to serialize profiles: {}", e))?;
+        fs::write(path, content).map_err(|e| format!("Failed to save profiles: {}", e))
+    }
+
+    /// Parses an EqualizerAPO file and stores it, named after the file unless `name` is given.
+    pub fn import_file(&mut self, path: &str, name: Option<String>) -> Result<String, String> {
+        let text = fs::read_to_string(path).map_err(|e| format!("Failed to read profile: {}", e))?;
+        let profile = parse_equalizer_apo(&text)?;
+
+        let name = name
+            .or_else(|| Path::new(path).file_stem().and_then(|s| s.to_str()).map(|s| s.to_string()))
+            .ok_or("Profile name required")?;
+
+        self.data.profiles.insert(name.clone(), profile.params);
+        match profile.fit_error_db {
+            Some(error_db) => self.data.fit_errors.insert(name.clone(), error_db),
+            None => self.data.fit_errors.remove(&name),
+        };
+        self.save()?;
+        Ok(name)
+    }
+
+    pub fn remove(&mut self, name: &str) -> Result<(), String> {
+        if self.data.profiles.remove(name).is_none() {
+            return Err(format!("No EQ profile named {}", name));
+        }
+        self.data.devices.retain(|_, profile| profile != name);
+        self.data.fit_errors.remove(name);
+        self.save()
+    }
+
+    pub fn get(&self, name: &str) -> Result<ParametricEqParams, String> {
+        self.data
+            .profiles
+            .get(name)
+            .cloned()
+            .ok_or_else(|| format!("No EQ profile named {}", name))
+    }
+
+    pub fn list(&self) -> Vec<EqProfileInfo> {
+        self.data
+            .profiles
+            .iter()
+            .map(|(name, params)| EqProfileInfo {
+                name: name.clone(),
+                preamp_db: params.preamp_db,
+                band_count: params.bands.len(),
+                fit_error_db: self.data.fit_errors.get(name).copied(),
+                devices: self
+                    .data
+                    .devices
+                    .iter()
+                    .filter(|(_, profile)| *profile == name)
+                    .map(|(device, _)| device.clone())
+                    .collect(),
+            })
+            .collect()
+    }
+
+    /// Assigns a profile to an output device, or clears the assignment with `None`.
+    pub fn assign_device(&mut self, device: String, profile: Option<String>) -> Result<(), String> {
+        match profile {
+            Some(profile) => {
+                if !self.data.profiles.contains_key(&profile) {
+                    return Err(format!("No EQ profile named {}", profile));
+                }
+                self.data.devices.insert(device, profile);
+            }
+            None => {
+                self.data.devices.remove(&device);
+            }
+        }
+        self.save()
+    }
+
+    pub fn profile_for_device(&self, device: &str) -> Option<ParametricEqParams> {
+        self.data
+            .devices
+            .get(device)
+            .and_then(|name| self.data.profiles.get(name))
+            .cloned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(text: &str) -> Result<ApoProfile, String> {
+        parse_equalizer_apo(text)
+    }
+
+    #[test]
+    fn preamp() {
+        let cases = [
+            ("Preamp: -6.5 dB", -6.5),
+            ("preamp: -3dB", -3.0),
+            ("Preamp: -2 dB\nPreamp: -1.5 dB", -3.5),
+            ("Preamp: 0 dB", 0.0),
+        ];
+        for (text, expected) in cases {
+            let profile = parse(text).unwrap_or_else(|e| panic!("{:?}: {}", text, e));
+            assert_eq!(profile.params.preamp_db, expected, "{:?}", text);
+            assert!(!profile.params.auto_headroom, "{:?}", text);
+        }
+
+        let profile = parse("Filter 1: ON PK Fc 100 Hz Gain 3 dB Q 1").unwrap();
+        assert_eq!(profile.params.preamp_db, 0.0);
+        assert!(profile.params.auto_headroom);
+    }
+
+    #[test]
+    fn filter_types() {
+        let cases = [
+            ("ON PK Fc 105 Hz Gain -4.2 dB Q 0.70", FilterType::Peak, 105.0, -4.2, 0.70, true),
+            ("ON PEQ Fc 1000 Hz Gain 2 dB Q 2", FilterType::Peak, 1000.0, 2.0, 2.0, true),
+            ("ON LSC Fc 105 Hz Gain 6.0 dB", FilterType::LowShelf, 105.0, 6.0, DEFAULT_Q, true),
+            ("ON LSC 12dB Fc 105 Hz Gain 6.0 dB Q 0.5", FilterType::LowShelf, 105.0, 6.0, 0.5, true),
+            ("ON HS Fc 10000 Hz Gain -2 dB", FilterType::HighShelf, 10000.0, -2.0, DEFAULT_Q, true),
+            ("ON LP Fc 18000 Hz", FilterType::LowPass, 18000.0, 0.0, DEFAULT_Q, true),
+            ("OFF HPQ Fc 20 Hz Q 0.9", FilterType::HighPass, 20.0, 0.0, 0.9, false),
+            ("ON NO Fc 60 Hz", FilterType::Notch, 60.0, 0.0, DEFAULT_Q, true),
+            ("ON PK Fc 500 Hz Gain 1 dB BW Oct 1", FilterType::Peak, 500.0, 1.0, 2f32.sqrt(), true),
+        ];
+        for (filter, filter_type, frequency, gain_db, q, enabled) in cases {
+            let text = format!("Filter 1: {}", filter);
+            let profile = parse(&text).unwrap_or_else(|e| panic!("{:?}: {}", text, e));
+            let band = &profile.params.bands[0];
+            assert_eq!(band.filter_type, filter_type, "{:?}", text);
+            assert_eq!(band.frequency, frequency, "{:?}", text);
+            assert_eq!(band.gain_db, gain_db, "{:?}", text);
+            assert!((band.q - q).abs() < 1e-5, "{:?}: Q {}", text, band.q);
+            assert_eq!(band.enabled, enabled, "{:?}", text);
+        }
+    }
+
+    #[test]
+    fn ignored_lines() {
+        let text = "# AutoEQ profile\n\nDevice: Headphones\nInclude: other.txt\nPreamp: -1 dB\nFilter 1: ON PK Fc 100 Hz Gain 1 dB Q 1\n";
+        let profile = parse(text).unwrap();
+        assert_eq!(profile.params.bands.len(), 1);
+        assert!(profile.fit_error_db.is_none());
+    }
+
+    #[test]
+    fn malformed_lines() {
+        let cases = [
+            ("", "No EqualizerAPO filters found"),
+            ("# only a comment", "No EqualizerAPO filters found"),
+            ("Preamp: loud", "Line 1: Invalid preamp"),
+            ("Filter 1: MAYBE PK Fc 100 Hz", "Line 1: Expected ON or OFF"),
+            ("Filter 1: ON", "Line 1: Missing filter type"),
+            ("Filter 1: ON XX Fc 100 Hz", "Line 1: Unsupported filter type: XX"),
+            ("Filter 1: ON PK Gain 3 dB Q 1", "Line 1: Missing filter frequency"),
+            ("Filter 1: ON PK Fc abc Hz", "Line 1: Invalid frequency"),
+            ("Filter 1: ON PK Fc 100 Hz Gain", "Line 1: Invalid gain"),
+            ("Filter 1: ON PK Fc 100 Hz BW 2", "Line 1: Only octave bandwidths are supported"),
+            ("Preamp: -1 dB\nGraphicEQ: 20 x; 1000 0", "Line 2: Invalid GraphicEQ gain"),
+            ("GraphicEQ: ;", "Line 1: Empty GraphicEQ curve"),
+        ];
+        for (text, expected) in cases {
+            match parse(text) {
+                Ok(_) => panic!("{:?} parsed", text),
+                Err(e) => assert_eq!(e, expected, "{:?}", text),
+            }
+        }
+    }
+
+    #[test]
+    fn graphic_eq_fit_converges() {
+        let text = "GraphicEQ: 20 6; 100 4; 500 -3; 2000 0; 6000 5; 12000 -4; 20000 -8";
+        let profile = parse(text).unwrap();
+        let error_db = profile.fit_error_db.unwrap();
+        assert!(error_db < FIT_TOLERANCE_DB, "fit error {} dB", error_db);
+        assert_eq!(profile.params.bands.len(), 31);
+
+        // The fitted filters follow the curve at the band centers
+        let points = parse_graphic_eq("20 6; 100 4; 500 -3; 2000 0; 6000 5; 12000 -4; 20000 -8").unwrap();
+        for band in &profile.params.bands {
+            let response: f64 = profile
+                .params
+                .bands
+                .iter()
+                .map(|b| Coefficients::design(b.filter_type, b.frequency, b.gain_db, b.q, FIT_SAMPLE_RATE).magnitude_db(band.frequency as f64, FIT_SAMPLE_RATE as f64))
+                .sum();
+            assert!((response as f32 - curve_gain(&points, band.frequency)).abs() < 0.02);
+        }
+    }
+}
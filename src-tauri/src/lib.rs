@@ -1,19 +1,54 @@
-mod audio;
 mod symphonia_player;
 mod audio_new;
 mod crossfade_engine;
+mod decode;
+mod dsp;
+mod eq_profiles;
+mod library_db;
+mod lossless;
+mod musical_key;
+mod settings;
+mod spectrogram;
+mod tags;
+mod tempo;
+#[cfg(test)]
+mod test_util;
+mod time_stretch;
+mod track_analysis;
+mod verify;
+mod waveform;
 
-use audio::{AudioPlayer, TrackMetadata, AlbumArtwork};
-use crossfade_engine::{CrossfadeAudioPlayer, CrossfadeConfig, CrossfadeTrackInfo, CrossfadeCurve};
+use audio_new::{AudioPlayer, TrackMetadata, AlbumArtwork, VolumeCurve};
+use crossfade_engine::{BeatSync, CrossfadeAudioPlayer, CrossfadeConfig, CrossfadeTrackInfo, CrossfadeCurve};
+use dsp::{AudioEffect, ChannelLevels, ChannelMixerParams, CompressorParams, ConvolverParams, CrossfeedParams, EffectInfo, FrequencyResponse, KaraokeParams, LadspaParams, LadspaPluginInfo, LimiterParams, LoudnessParams, ParametricEq, ParametricEqParams, SpectrumSettings, StereoWidthParams};
+use eq_profiles::{EqProfileInfo, EqProfileStore};
+use library_db::{classify_scan, FileStat, LibraryDb, LibraryTrack, MigrationReport, NewTrack, PlayStats, Playlist};
+use lossless::LosslessReport;
+use musical_key::KeyEstimate;
+use settings::SettingsStore;
+use spectrogram::{Spectrogram, SpectrogramOptions};
+use tags::TagField;
+use tempo::TempoEstimate;
+use track_analysis::{TrackAnalysis, TrackAnalysisStore};
+use verify::VerifyReport;
+use waveform::{Waveform, WaveformService};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Mutex};
 use std::path::Path;
 use std::fs;
-use tauri::{State, Emitter};
+use tauri::{State, Emitter, Manager};
 use walkdir::WalkDir;
 
 struct AppState {
     player: Arc<Mutex<AudioPlayer>>,
     crossfade_player: Arc<Mutex<Option<CrossfadeAudioPlayer>>>,
+    eq_profiles: Arc<Mutex<EqProfileStore>>,
+    settings: Arc<Mutex<SettingsStore>>,
+    track_analysis: Arc<Mutex<TrackAnalysisStore>>,
+    library: Arc<Mutex<LibraryDb>>,
+    waveforms: Arc<WaveformService>,
+    /// Cancellation token of the running library verification, if any.
+    verification: Arc<Mutex<Option<Arc<AtomicBool>>>>,
 }
 
 #[tauri::command]
@@ -50,6 +85,50 @@ fn set_volume(volume: f32, state: State<AppState>) -> Result<(), String> {
     Ok(())
 }
 
+#[tauri::command]
+fn set_volume_range(min_db: f32, max_db: f32, state: State<AppState>) -> Result<(), String> {
+    let curve = VolumeCurve { min_db, max_db };
+    state.player.lock().unwrap().set_volume_curve(curve)?;
+    state.settings.lock().unwrap().update(|settings| settings.volume_curve = curve)
+}
+
+#[tauri::command]
+fn get_volume_range(state: State<AppState>) -> Result<VolumeCurve, String> {
+    let player = state.player.lock().unwrap();
+    Ok(player.get_volume_curve())
+}
+
+fn update_channel_mixer(state: &AppState, change: impl FnOnce(&mut ChannelMixerParams)) -> Result<(), String> {
+    let params = state.settings.lock().unwrap().update(|settings| {
+        change(&mut settings.channels);
+        settings.channels.clone()
+    })?;
+    let player = state.player.lock().unwrap();
+    player.set_channel_mixer(params);
+    Ok(())
+}
+
+#[tauri::command]
+fn set_balance(balance: f32, state: State<AppState>) -> Result<(), String> {
+    update_channel_mixer(&state, |channels| channels.balance = balance.clamp(-1.0, 1.0))
+}
+
+#[tauri::command]
+fn set_mono(enabled: bool, state: State<AppState>) -> Result<(), String> {
+    update_channel_mixer(&state, |channels| channels.mono = enabled)
+}
+
+#[tauri::command]
+fn set_channel_swap(enabled: bool, state: State<AppState>) -> Result<(), String> {
+    update_channel_mixer(&state, |channels| channels.swap_channels = enabled)
+}
+
+#[tauri::command]
+fn get_channel_settings(state: State<AppState>) -> Result<ChannelMixerParams, String> {
+    let player = state.player.lock().unwrap();
+    Ok(player.get_channel_mixer())
+}
+
 #[tauri::command]
 async fn seek(position: f32, state: State<'_, AppState>) -> Result<(), String> {
     // Use async to avoid blocking the main thread
@@ -62,6 +141,19 @@ async fn seek(position: f32, state: State<'_, AppState>) -> Result<(), String> {
     .map_err(|e| format!("Seek task failed: {}", e))?
 }
 
+#[tauri::command]
+fn set_ab_loop(start: f32, end: f32, state: State<AppState>) -> Result<(), String> {
+    let player = state.player.lock().unwrap();
+    player.set_ab_loop(start, end)
+}
+
+#[tauri::command]
+fn clear_ab_loop(state: State<AppState>) -> Result<(), String> {
+    let player = state.player.lock().unwrap();
+    player.clear_ab_loop();
+    Ok(())
+}
+
 #[tauri::command]
 fn get_current_time(state: State<AppState>) -> Result<f32, String> {
     let player = state.player.lock().unwrap();
@@ -76,9 +168,21 @@ fn get_song_info(state: State<AppState>) -> Result<(f32, f32), String> {
     Ok((current_time, duration))
 }
 
+/// Reads the tags of `path`, filling in tempo and key from earlier analysis
+/// where the file has no tag for them.
+fn track_metadata(path: &str, analysis: &Mutex<TrackAnalysisStore>) -> Result<TrackMetadata, String> {
+    let mut metadata = AudioPlayer::get_track_metadata(path)?;
+    if metadata.bpm.is_none() || metadata.key.is_none() {
+        let stored = analysis.lock().unwrap().get(path).unwrap_or_default();
+        metadata.bpm = metadata.bpm.or(stored.tempo.map(|t| t.bpm));
+        metadata.key = metadata.key.or(stored.key.map(|k| k.key));
+    }
+    Ok(metadata)
+}
+
 #[tauri::command]
-fn get_track_metadata(path: String) -> Result<TrackMetadata, String> {
-    AudioPlayer::get_track_metadata(&path)
+fn get_track_metadata(path: String, state: State<AppState>) -> Result<TrackMetadata, String> {
+    track_metadata(&path, &state.track_analysis)
 }
 
 #[tauri::command]
@@ -99,6 +203,307 @@ fn enable_equalizer(enabled: bool, state: State<AppState>) -> Result<(), String>
     player.enable_equalizer(enabled)
 }
 
+#[tauri::command]
+fn set_parametric_eq(params: ParametricEqParams, state: State<AppState>) -> Result<(), String> {
+    let player = state.player.lock().unwrap();
+    player.set_parametric_eq(params)
+}
+
+#[tauri::command]
+fn get_parametric_eq(state: State<AppState>) -> Result<ParametricEqParams, String> {
+    let player = state.player.lock().unwrap();
+    Ok(player.get_parametric_eq())
+}
+
+// EQ profile commands
+#[tauri::command]
+fn import_eq_profile(path: String, name: Option<String>, state: State<AppState>) -> Result<String, String> {
+    let mut profiles = state.eq_profiles.lock().unwrap();
+    profiles.import_file(&path, name)
+}
+
+#[tauri::command]
+fn list_eq_profiles(state: State<AppState>) -> Result<Vec<EqProfileInfo>, String> {
+    let profiles = state.eq_profiles.lock().unwrap();
+    Ok(profiles.list())
+}
+
+#[tauri::command]
+fn delete_eq_profile(name: String, state: State<AppState>) -> Result<(), String> {
+    let mut profiles = state.eq_profiles.lock().unwrap();
+    profiles.remove(&name)
+}
+
+#[tauri::command]
+fn apply_eq_profile(name: String, state: State<AppState>) -> Result<(), String> {
+    let params = state.eq_profiles.lock().unwrap().get(&name)?;
+    let player = state.player.lock().unwrap();
+    player.set_parametric_eq(params)
+}
+
+#[tauri::command]
+fn assign_eq_profile_to_device(device: String, profile: Option<String>, state: State<AppState>) -> Result<(), String> {
+    let params = {
+        let mut profiles = state.eq_profiles.lock().unwrap();
+        profiles.assign_device(device.clone(), profile)?;
+        profiles.profile_for_device(&device)
+    };
+
+    // Switch right away when the assignment is for the device in use; without a
+    // profile the device gets a flat EQ
+    let player = state.player.lock().unwrap();
+    if player.get_output_device().as_deref() == Some(device.as_str()) {
+        player.set_parametric_eq(params.unwrap_or_default())
+    } else {
+        Ok(())
+    }
+}
+
+#[tauri::command]
+fn set_crossfeed(device: String, params: Option<CrossfeedParams>, state: State<AppState>) -> Result<(), String> {
+    state.settings.lock().unwrap().update(|settings| match &params {
+        Some(params) => settings.crossfeed.insert(device.clone(), params.clone()),
+        None => settings.crossfeed.remove(&device),
+    })?;
+
+    let player = state.player.lock().unwrap();
+    if player.get_output_device().as_deref() == Some(device.as_str()) {
+        player.set_crossfeed(params)?;
+    }
+    Ok(())
+}
+
+#[tauri::command]
+fn get_crossfeed(device: String, state: State<AppState>) -> Result<Option<CrossfeedParams>, String> {
+    let settings = state.settings.lock().unwrap();
+    Ok(settings.get().crossfeed.get(&device).cloned())
+}
+
+// Dynamics commands
+#[tauri::command]
+fn set_compressor(params: Option<CompressorParams>, state: State<AppState>) -> Result<(), String> {
+    state.settings.lock().unwrap().update(|settings| settings.compressor = params.clone())?;
+    let player = state.player.lock().unwrap();
+    player.set_compressor(params);
+    Ok(())
+}
+
+#[tauri::command]
+fn get_compressor(state: State<AppState>) -> Result<Option<CompressorParams>, String> {
+    let player = state.player.lock().unwrap();
+    Ok(player.get_compressor())
+}
+
+#[tauri::command]
+fn set_limiter(params: LimiterParams, state: State<AppState>) -> Result<(), String> {
+    state.settings.lock().unwrap().update(|settings| settings.limiter = params.clone())?;
+    let player = state.player.lock().unwrap();
+    player.set_limiter(params);
+    Ok(())
+}
+
+#[tauri::command]
+fn get_limiter(state: State<AppState>) -> Result<LimiterParams, String> {
+    let player = state.player.lock().unwrap();
+    Ok(player.get_limiter())
+}
+
+#[tauri::command]
+fn set_loudness_compensation(params: Option<LoudnessParams>, state: State<AppState>) -> Result<(), String> {
+    state.settings.lock().unwrap().update(|settings| settings.loudness = params.clone())?;
+    let player = state.player.lock().unwrap();
+    player.set_loudness(params);
+    Ok(())
+}
+
+#[tauri::command]
+fn get_loudness_compensation(state: State<AppState>) -> Result<Option<LoudnessParams>, String> {
+    let player = state.player.lock().unwrap();
+    Ok(player.get_loudness())
+}
+
+#[tauri::command]
+fn set_karaoke(enabled: bool, params: Option<KaraokeParams>, state: State<AppState>) -> Result<(), String> {
+    let player = state.player.lock().unwrap();
+    player.set_karaoke(enabled.then(|| params.unwrap_or_default()));
+    Ok(())
+}
+
+#[tauri::command]
+fn get_karaoke(state: State<AppState>) -> Result<Option<KaraokeParams>, String> {
+    let player = state.player.lock().unwrap();
+    Ok(player.get_karaoke())
+}
+
+#[tauri::command]
+fn set_stereo_width(params: Option<StereoWidthParams>, state: State<AppState>) -> Result<(), String> {
+    let player = state.player.lock().unwrap();
+    player.set_stereo_width(params);
+    Ok(())
+}
+
+#[tauri::command]
+fn get_stereo_width(state: State<AppState>) -> Result<Option<StereoWidthParams>, String> {
+    let player = state.player.lock().unwrap();
+    Ok(player.get_stereo_width())
+}
+
+#[tauri::command]
+fn set_convolver(params: Option<ConvolverParams>, state: State<AppState>) -> Result<(), String> {
+    // Load first so a bad impulse response isn't saved
+    state.player.lock().unwrap().set_convolver(params.clone())?;
+    state.settings.lock().unwrap().update(|settings| settings.convolver = params)
+}
+
+#[tauri::command]
+fn get_convolver(state: State<AppState>) -> Result<Option<ConvolverParams>, String> {
+    let player = state.player.lock().unwrap();
+    Ok(player.get_convolver())
+}
+
+#[tauri::command]
+fn set_spectrum_settings(settings: SpectrumSettings, state: State<AppState>) -> Result<(), String> {
+    state.player.lock().unwrap().set_spectrum_settings(settings.clone())?;
+    state.settings.lock().unwrap().update(|saved| saved.spectrum = settings)
+}
+
+#[tauri::command]
+fn get_spectrum_settings(state: State<AppState>) -> Result<SpectrumSettings, String> {
+    let player = state.player.lock().unwrap();
+    Ok(player.get_spectrum_settings())
+}
+
+/// Min/max/RMS overview of a track for the seek bar. Buckets are streamed as
+/// "waveform-progress" events while the file is decoded, for files whose
+/// container reports their length; others arrive in one piece at the end.
+#[tauri::command]
+async fn get_waveform(path: String, resolution: usize, window: tauri::Window, state: State<'_, AppState>) -> Result<Waveform, String> {
+    let waveforms = Arc::clone(&state.waveforms);
+    waveforms
+        .get(path, resolution, move |chunk| {
+            let _ = window.emit("waveform-progress", chunk);
+        })
+        .await
+}
+
+/// Renders a full-track spectrogram to `output_path` as PNG, or returns it base64-encoded.
+#[tauri::command]
+async fn export_spectrogram(path: String, options: Option<SpectrogramOptions>, output_path: Option<String>) -> Result<Spectrogram, String> {
+    let options = options.unwrap_or_default();
+    tokio::task::spawn_blocking(move || spectrogram::export_spectrogram(&path, &options, output_path.as_deref()))
+        .await
+        .map_err(|e| format!("Spectrogram task failed: {}", e))?
+}
+
+#[tauri::command]
+fn get_levels(state: State<AppState>) -> Result<Vec<ChannelLevels>, String> {
+    let player = state.player.lock().unwrap();
+    Ok(player.get_levels())
+}
+
+#[tauri::command]
+fn reset_clip_counters(state: State<AppState>) -> Result<(), String> {
+    let player = state.player.lock().unwrap();
+    player.reset_clip_counters();
+    Ok(())
+}
+
+/// Night mode: heavy compression with makeup gain, with the limiter catching what gets through.
+#[tauri::command]
+fn set_night_mode(enabled: bool, state: State<AppState>) -> Result<(), String> {
+    let (compressor, limiter) = state.settings.lock().unwrap().update(|settings| {
+        settings.compressor = enabled.then(CompressorParams::night_mode);
+        if enabled {
+            settings.limiter.enabled = true;
+        }
+        (settings.compressor.clone(), settings.limiter.clone())
+    })?;
+
+    let player = state.player.lock().unwrap();
+    player.set_compressor(compressor);
+    player.set_limiter(limiter);
+    Ok(())
+}
+
+#[tauri::command]
+fn get_output_devices() -> Result<Vec<String>, String> {
+    AudioPlayer::output_devices()
+}
+
+// Effect chain commands
+#[tauri::command]
+fn add_effect(kind: String, params: Option<serde_json::Value>, position: Option<usize>, state: State<AppState>) -> Result<u32, String> {
+    let mut effect = dsp::create_effect(&kind, params)?;
+    let player = state.player.lock().unwrap();
+    // Expensive setup happens before the chain is locked, so playback keeps going
+    let (sample_rate, channels) = player.with_effects(|chain| chain.format());
+    effect.preload(sample_rate, channels);
+    Ok(player.with_effects(|chain| chain.add(effect, position)))
+}
+
+#[tauri::command]
+fn remove_effect(id: u32, state: State<AppState>) -> Result<(), String> {
+    let player = state.player.lock().unwrap();
+    player.with_effects(|chain| chain.remove(id))
+}
+
+#[tauri::command]
+fn set_effect_bypass(id: u32, bypassed: bool, state: State<AppState>) -> Result<(), String> {
+    let player = state.player.lock().unwrap();
+    player.with_effects(|chain| chain.set_bypass(id, bypassed))
+}
+
+#[tauri::command]
+fn move_effect(id: u32, index: usize, state: State<AppState>) -> Result<(), String> {
+    let player = state.player.lock().unwrap();
+    player.with_effects(|chain| chain.move_effect(id, index))
+}
+
+#[tauri::command]
+fn set_effect_params(id: u32, params: serde_json::Value, state: State<AppState>) -> Result<(), String> {
+    let player = state.player.lock().unwrap();
+    player.with_effects(|chain| chain.set_params(id, params))
+}
+
+#[tauri::command]
+fn list_ladspa_plugins() -> Result<Vec<LadspaPluginInfo>, String> {
+    Ok(dsp::scan_ladspa_plugins())
+}
+
+/// Sets one control input of a LADSPA effect, keeping its other settings.
+#[tauri::command]
+fn set_ladspa_control(id: u32, control: String, value: f32, state: State<AppState>) -> Result<(), String> {
+    let player = state.player.lock().unwrap();
+    player.with_effects(|chain| {
+        let effect = chain
+            .effects()
+            .into_iter()
+            .find(|effect| effect.id == id && effect.kind == "ladspa")
+            .ok_or_else(|| format!("No LADSPA effect with id {}", id))?;
+
+        let mut params: LadspaParams =
+            serde_json::from_value(effect.params).map_err(|e| format!("Invalid LADSPA parameters: {}", e))?;
+        params.controls.insert(control, value);
+        let params = serde_json::to_value(params).map_err(|e| format!("Invalid LADSPA parameters: {}", e))?;
+        chain.set_params(id, params)
+    })
+}
+
+#[tauri::command]
+fn get_effect_chain(state: State<AppState>) -> Result<Vec<EffectInfo>, String> {
+    let player = state.player.lock().unwrap();
+    Ok(player.with_effects(|chain| chain.effects()))
+}
+
+#[tauri::command]
+fn get_frequency_response(points: Option<usize>, min_frequency: Option<f64>, max_frequency: Option<f64>, state: State<AppState>) -> Result<FrequencyResponse, String> {
+    let points = points.unwrap_or(256).clamp(2, 4096);
+    let player = state.player.lock().unwrap();
+    Ok(player.with_effects(|chain| {
+        chain.frequency_response(points, min_frequency.unwrap_or(20.0), max_frequency.unwrap_or(20000.0))
+    }))
+}
+
 #[tauri::command]
 fn get_album_artwork(path: String) -> Result<Option<AlbumArtwork>, String> {
     AudioPlayer::get_album_artwork(&path)
@@ -111,89 +516,515 @@ struct ScanProgress {
     current_file: String,
 }
 
+/// Extensions of the files a library scan picks up.
+const MUSIC_EXTENSIONS: [&str; 8] = ["mp3", "wav", "ogg", "flac", "m4a", "aac", "opus", "wma"];
+
+#[derive(serde::Serialize)]
+struct MusicFileInfo {
+    path: String,
+    name: String,
+    metadata: Option<TrackMetadata>,
+}
+
+fn music_file_info(path: String, analysis: &Mutex<TrackAnalysisStore>) -> MusicFileInfo {
+    let name = Path::new(&path)
+        .file_name()
+        .and_then(|n| n.to_str())
+        .unwrap_or(&path)
+        .to_string();
+    let metadata = track_metadata(&path, analysis).ok();
+    MusicFileInfo { path, name, metadata }
+}
+
+/// Difference between a folder and the player's library.
+#[derive(serde::Serialize)]
+struct ScanDiff {
+    /// Files not in the player's library, with their metadata. Files that were
+    /// never imported have no stored state, so each scan reads them again.
+    added: Vec<MusicFileInfo>,
+    /// Library files whose metadata changed, or was never stored; the database
+    /// already has their new metadata.
+    changed: Vec<MusicFileInfo>,
+    /// Library and database files under the folder that are gone. The database
+    /// keeps their ratings and history until removed with `remove_library_tracks`.
+    removed: Vec<String>,
+    unchanged: usize,
+}
+
+/// Compares a folder with the player's library, given as the paths of its
+/// songs, and with the file states stored in the database. Metadata is read
+/// only from files that are new or changed since it was stored.
 #[tauri::command]
-async fn scan_music_folder(folder_path: String, window: tauri::Window) -> Result<Vec<String>, String> {
-    let path = Path::new(&folder_path);
-    
-    if !path.exists() || !path.is_dir() {
-        return Err("Invalid folder path".to_string());
-    }
-    
-    let mut music_files = Vec::new();
-    let extensions = ["mp3", "wav", "ogg", "flac", "m4a", "aac", "opus", "wma"];
-    
-    // First, count total files for progress
-    let total_files: usize = WalkDir::new(&folder_path)
-        .into_iter()
-        .filter_map(|e| e.ok())
-        .filter(|e| e.file_type().is_file())
-        .filter(|e| {
-            if let Some(ext) = e.path().extension() {
-                extensions.contains(&ext.to_str().unwrap_or("").to_lowercase().as_str())
-            } else {
-                false
+async fn scan_music_folder(
+    folder_path: String,
+    library_paths: Vec<String>,
+    window: tauri::Window,
+    state: State<'_, AppState>,
+) -> Result<ScanDiff, String> {
+    let library = Arc::clone(&state.library);
+    let analysis = Arc::clone(&state.track_analysis);
+    tokio::task::spawn_blocking(move || {
+        let folder = Path::new(&folder_path);
+        if !folder.is_dir() {
+            return Err("Invalid folder path".to_string());
+        }
+
+        let mut found = Vec::new();
+        for entry in WalkDir::new(folder).into_iter().filter_map(|e| e.ok()).filter(|e| e.file_type().is_file()) {
+            let is_music = entry
+                .path()
+                .extension()
+                .and_then(|ext| ext.to_str())
+                .is_some_and(|ext| MUSIC_EXTENSIONS.contains(&ext.to_lowercase().as_str()));
+            if !is_music {
+                continue;
             }
-        })
-        .count();
-    
-    let mut current = 0;
-    
-    // Now scan and collect files
-    for entry in WalkDir::new(&folder_path)
-        .into_iter()
-        .filter_map(|e| e.ok())
-        .filter(|e| e.file_type().is_file())
-    {
-        if let Some(ext) = entry.path().extension() {
-            if extensions.contains(&ext.to_str().unwrap_or("").to_lowercase().as_str()) {
-                if let Some(path_str) = entry.path().to_str() {
-                    music_files.push(path_str.to_string());
+            if let (Some(path), Ok(metadata)) = (entry.path().to_str(), entry.metadata()) {
+                found.push((path.to_string(), FileStat::from_metadata(&metadata)));
+            }
+        }
+
+        let stored = library.lock().unwrap().file_stats(folder)?;
+        let plan = classify_scan(folder, found, &library_paths.into_iter().collect(), stored);
+
+        // Whatever the walk didn't find is gone, unless it was just unreadable
+        let removed = plan.missing.into_iter().filter(|path| !Path::new(path).exists()).collect();
+
+        let total = plan.added.len() + plan.changed.len();
+        let mut current = 0;
+        let mut read = |paths: Vec<String>| -> Vec<MusicFileInfo> {
+            paths
+                .into_iter()
+                .map(|path| {
                     current += 1;
-                    
-                    // Emit progress event
                     let _ = window.emit("scan-progress", ScanProgress {
                         current,
-                        total: total_files,
-                        current_file: path_str.to_string(),
+                        total,
+                        current_file: path.clone(),
                     });
-                }
-            }
+                    music_file_info(path, &analysis)
+                })
+                .collect()
+        };
+        let mut added = read(plan.added);
+        let changed = read(plan.changed);
+
+        for path in plan.restored {
+            let stored = library.lock().unwrap().track(&path)?;
+            added.push(match stored {
+                Some(track) => MusicFileInfo {
+                    path: track.path,
+                    name: track.name,
+                    metadata: track.metadata,
+                },
+                None => music_file_info(path, &analysis),
+            });
         }
-    }
-    
-    Ok(music_files)
-}
 
-#[derive(serde::Serialize)]
-struct MusicFileInfo {
-    path: String,
-    name: String,
-    metadata: Option<TrackMetadata>,
+        let updates: Vec<NewTrack> = changed
+            .iter()
+            .map(|file| NewTrack {
+                path: file.path.clone(),
+                name: file.name.clone(),
+                metadata: file.metadata.clone(),
+            })
+            .collect();
+        library.lock().unwrap().add_tracks(&updates)?;
+
+        Ok(ScanDiff {
+            added,
+            changed,
+            removed,
+            unchanged: plan.unchanged,
+        })
+    })
+    .await
+    .map_err(|e| format!("Scan task failed: {}", e))?
 }
 
+/// Metadata of `paths`, taken from the library for files unchanged since it
+/// was stored and read from the files otherwise.
 #[tauri::command]
-async fn get_music_files_metadata(paths: Vec<String>) -> Result<Vec<MusicFileInfo>, String> {
+async fn get_music_files_metadata(paths: Vec<String>, state: State<'_, AppState>) -> Result<Vec<MusicFileInfo>, String> {
     let mut files_info = Vec::new();
-    
+
     for path in paths {
-        let name = Path::new(&path)
-            .file_name()
-            .and_then(|n| n.to_str())
-            .unwrap_or(&path)
-            .to_string();
-        
-        let metadata = AudioPlayer::get_track_metadata(&path).ok();
-        
-        files_info.push(MusicFileInfo {
-            path,
-            name,
-            metadata,
+        let stored = state.library.lock().unwrap().unchanged_track(&path).ok().flatten();
+        files_info.push(match stored {
+            Some(track) => MusicFileInfo {
+                path: track.path,
+                name: track.name,
+                metadata: track.metadata,
+            },
+            None => music_file_info(path, &state.track_analysis),
         });
     }
-    
+
     Ok(files_info)
 }
 
+/// Number of files analyzed between saves of the analysis store during a batch.
+const ANALYSIS_SAVE_INTERVAL: usize = 50;
+
+/// Outcome for one file of a batch: `result` if it was analyzed, otherwise
+/// the `error` that stopped it.
+#[derive(serde::Serialize)]
+struct TrackResult<T> {
+    path: String,
+    result: Option<T>,
+    error: Option<String>,
+}
+
+impl<T> TrackResult<T> {
+    fn new(path: String, result: Result<T, String>) -> Self {
+        match result {
+            Ok(result) => Self { path, result: Some(result), error: None },
+            Err(error) => Self { path, result: None, error: Some(error) },
+        }
+    }
+}
+
+/// Runs `analyze` over a batch of files, emitting `event` after each one and
+/// saving the analysis store as it goes. Returns a result for every file.
+fn analyze_library<T>(
+    paths: Vec<String>,
+    event: &str,
+    window: &tauri::Window,
+    analysis: &Mutex<TrackAnalysisStore>,
+    analyze: impl Fn(&str) -> Result<T, String>,
+) -> Vec<TrackResult<T>> {
+    let total = paths.len();
+    let mut results = Vec::new();
+
+    for (index, path) in paths.into_iter().enumerate() {
+        results.push(TrackResult::new(path.clone(), analyze(&path)));
+
+        if (index + 1) % ANALYSIS_SAVE_INTERVAL == 0 || index + 1 == total {
+            if let Err(e) = analysis.lock().unwrap().save() {
+                eprintln!("{}", e);
+            }
+        }
+
+        let _ = window.emit(event, ScanProgress {
+            current: index + 1,
+            total,
+            current_file: path,
+        });
+    }
+    results
+}
+
+/// Tempo of `path`, from the store while the file is unchanged. With
+/// `write_tag`, the rounded tempo is also written to the file's BPM tag.
+/// The library copy of the track's metadata is updated to match.
+fn analyze_tempo(
+    analysis: &Mutex<TrackAnalysisStore>,
+    library: &Mutex<LibraryDb>,
+    path: &str,
+    write_tag: bool,
+) -> Result<TempoEstimate, String> {
+    // Estimates stored before beat positions were analyzed are redone
+    let stored = analysis.lock().unwrap().get(path).and_then(|a| a.tempo).filter(|t| t.offset.is_some());
+    let tempo = match stored {
+        Some(tempo) => tempo,
+        None => {
+            let tempo = tempo::detect_tempo(path)?;
+            analysis.lock().unwrap().set_tempo(path, tempo)?;
+            tempo
+        }
+    };
+
+    let bpm = if write_tag {
+        let bpm = tempo.bpm.round();
+        tags::write_tag(path, TagField::Bpm, &bpm.to_string())?;
+        analysis.lock().unwrap().refresh(path)?;
+        bpm
+    } else {
+        tempo.bpm
+    };
+    if let Err(e) = library.lock().unwrap().set_bpm(path, bpm, write_tag) {
+        eprintln!("{}", e);
+    }
+    Ok(tempo)
+}
+
+#[tauri::command]
+async fn detect_bpm(path: String, write_tag: bool, state: State<'_, AppState>) -> Result<TempoEstimate, String> {
+    let analysis = Arc::clone(&state.track_analysis);
+    let library = Arc::clone(&state.library);
+    tokio::task::spawn_blocking(move || {
+        let tempo = analyze_tempo(&analysis, &library, &path, write_tag);
+        if let Err(e) = analysis.lock().unwrap().save() {
+            eprintln!("{}", e);
+        }
+        tempo
+    })
+    .await
+    .map_err(|e| format!("Tempo task failed: {}", e))?
+}
+
+/// Estimates the tempo of a batch of files, emitting "bpm-progress" after each
+/// one. Files that fail to decode or tag carry the error in their result.
+#[tauri::command]
+async fn detect_bpm_library(
+    paths: Vec<String>,
+    write_tags: bool,
+    window: tauri::Window,
+    state: State<'_, AppState>,
+) -> Result<Vec<TrackResult<TempoEstimate>>, String> {
+    let analysis = Arc::clone(&state.track_analysis);
+    let library = Arc::clone(&state.library);
+    tokio::task::spawn_blocking(move || {
+        analyze_library(paths, "bpm-progress", &window, &analysis, |path| analyze_tempo(&analysis, &library, path, write_tags))
+    })
+    .await
+    .map_err(|e| format!("Tempo task failed: {}", e))
+}
+
+/// Key of `path`, from the store while the file is unchanged. With
+/// `write_tag`, the key is also written to the file's initial key tag.
+/// The library copy of the track's metadata is updated to match.
+fn analyze_key(
+    analysis: &Mutex<TrackAnalysisStore>,
+    library: &Mutex<LibraryDb>,
+    path: &str,
+    write_tag: bool,
+) -> Result<KeyEstimate, String> {
+    let stored = analysis.lock().unwrap().get(path).and_then(|a| a.key);
+    let key = match stored {
+        Some(key) => key,
+        None => {
+            let key = musical_key::detect_key(path)?;
+            analysis.lock().unwrap().set_key(path, key.clone())?;
+            key
+        }
+    };
+
+    if write_tag {
+        tags::write_tag(path, TagField::InitialKey, &key.key)?;
+        analysis.lock().unwrap().refresh(path)?;
+    }
+    if let Err(e) = library.lock().unwrap().set_initial_key(path, &key.key, write_tag) {
+        eprintln!("{}", e);
+    }
+    Ok(key)
+}
+
+#[tauri::command]
+async fn detect_key(path: String, write_tag: bool, state: State<'_, AppState>) -> Result<KeyEstimate, String> {
+    let analysis = Arc::clone(&state.track_analysis);
+    let library = Arc::clone(&state.library);
+    tokio::task::spawn_blocking(move || {
+        let key = analyze_key(&analysis, &library, &path, write_tag);
+        if let Err(e) = analysis.lock().unwrap().save() {
+            eprintln!("{}", e);
+        }
+        key
+    })
+    .await
+    .map_err(|e| format!("Key task failed: {}", e))?
+}
+
+/// Estimates the key of a batch of files, emitting "key-progress" after each
+/// one. Files that fail to decode or tag carry the error in their result.
+#[tauri::command]
+async fn detect_key_library(
+    paths: Vec<String>,
+    write_tags: bool,
+    window: tauri::Window,
+    state: State<'_, AppState>,
+) -> Result<Vec<TrackResult<KeyEstimate>>, String> {
+    let analysis = Arc::clone(&state.track_analysis);
+    let library = Arc::clone(&state.library);
+    tokio::task::spawn_blocking(move || {
+        analyze_library(paths, "key-progress", &window, &analysis, |path| analyze_key(&analysis, &library, path, write_tags))
+    })
+    .await
+    .map_err(|e| format!("Key task failed: {}", e))
+}
+
+/// Stored analysis results for `path`, if it was analyzed since it last changed.
+#[tauri::command]
+fn get_track_analysis(path: String, state: State<AppState>) -> Option<TrackAnalysis> {
+    state.track_analysis.lock().unwrap().get(&path)
+}
+
+/// Checks one file for signs of a lossy source, upsampling or padded bit depth.
+#[tauri::command]
+async fn check_lossless(path: String) -> Result<LosslessReport, String> {
+    tokio::task::spawn_blocking(move || lossless::analyze(&path))
+        .await
+        .map_err(|e| format!("Analysis task failed: {}", e))?
+}
+
+/// Checks a batch of files, emitting "lossless-check-progress" after each one.
+/// Files that fail to decode carry the error in their result.
+#[tauri::command]
+async fn check_lossless_library(paths: Vec<String>, window: tauri::Window) -> Result<Vec<TrackResult<LosslessReport>>, String> {
+    tokio::task::spawn_blocking(move || {
+        let total = paths.len();
+        let mut reports = Vec::new();
+
+        for (index, path) in paths.into_iter().enumerate() {
+            reports.push(TrackResult::new(path.clone(), lossless::analyze(&path)));
+
+            let _ = window.emit("lossless-check-progress", ScanProgress {
+                current: index + 1,
+                total,
+                current_file: path,
+            });
+        }
+        reports
+    })
+    .await
+    .map_err(|e| format!("Analysis task failed: {}", e))
+}
+
+/// Decodes one file end to end, checking the FLAC MD5 signature where present.
+#[tauri::command]
+async fn verify_file(path: String) -> Result<VerifyReport, String> {
+    tokio::task::spawn_blocking(move || verify::verify_file(&path))
+        .await
+        .map_err(|e| format!("Verification task failed: {}", e))
+}
+
+/// Fully decodes every file, emitting "verify-progress" after each one, and
+/// returns the reports of the broken ones. Only one verification runs at a time.
+#[tauri::command]
+async fn verify_library(paths: Vec<String>, window: tauri::Window, state: State<'_, AppState>) -> Result<Vec<VerifyReport>, String> {
+    let cancel = Arc::new(AtomicBool::new(false));
+    {
+        let mut running = state.verification.lock().unwrap();
+        if running.is_some() {
+            return Err("A library verification is already running".to_string());
+        }
+        *running = Some(Arc::clone(&cancel));
+    }
+
+    let result = tokio::task::spawn_blocking(move || {
+        let total = paths.len();
+        let mut broken = Vec::new();
+
+        for (index, path) in paths.into_iter().enumerate() {
+            if cancel.load(Ordering::Relaxed) {
+                break;
+            }
+
+            let report = verify::verify_file(&path);
+            if !report.ok {
+                broken.push(report);
+            }
+
+            let _ = window.emit("verify-progress", ScanProgress {
+                current: index + 1,
+                total,
+                current_file: path,
+            });
+        }
+        broken
+    })
+    .await
+    .map_err(|e| format!("Verification task failed: {}", e));
+
+    *state.verification.lock().unwrap() = None;
+    result
+}
+
+#[tauri::command]
+fn cancel_library_verification(state: State<AppState>) {
+    if let Some(cancel) = state.verification.lock().unwrap().as_ref() {
+        cancel.store(true, Ordering::Relaxed);
+    }
+}
+
+// Library database commands
+#[tauri::command]
+fn get_library_tracks(state: State<AppState>) -> Result<Vec<LibraryTrack>, String> {
+    state.library.lock().unwrap().tracks()
+}
+
+#[tauri::command]
+fn get_library_track(path: String, state: State<AppState>) -> Result<Option<LibraryTrack>, String> {
+    state.library.lock().unwrap().track(&path)
+}
+
+/// Adds tracks to the library or refreshes their metadata; returns how many were new.
+#[tauri::command]
+fn add_library_tracks(tracks: Vec<NewTrack>, state: State<AppState>) -> Result<usize, String> {
+    state.library.lock().unwrap().add_tracks(&tracks)
+}
+
+#[tauri::command]
+fn remove_library_tracks(paths: Vec<String>, state: State<AppState>) -> Result<usize, String> {
+    state.library.lock().unwrap().remove_tracks(&paths)
+}
+
+#[tauri::command]
+fn set_track_rating(path: String, rating: u8, state: State<AppState>) -> Result<(), String> {
+    state.library.lock().unwrap().set_rating(&path, rating)
+}
+
+#[tauri::command]
+fn set_track_favorite(path: String, favorite: bool, state: State<AppState>) -> Result<(), String> {
+    state.library.lock().unwrap().set_favorite(&path, favorite)
+}
+
+#[tauri::command]
+fn record_play(path: String, played_at: Option<String>, state: State<AppState>) -> Result<(), String> {
+    state.library.lock().unwrap().record_play(&path, played_at.as_deref())
+}
+
+#[tauri::command]
+fn get_play_history(limit: Option<usize>, state: State<AppState>) -> Result<Vec<PlayStats>, String> {
+    state.library.lock().unwrap().play_history(limit)
+}
+
+#[tauri::command]
+fn clear_play_history(state: State<AppState>) -> Result<(), String> {
+    state.library.lock().unwrap().clear_play_history()
+}
+
+#[tauri::command]
+fn get_playlists(state: State<AppState>) -> Result<Vec<Playlist>, String> {
+    state.library.lock().unwrap().playlists()
+}
+
+#[tauri::command]
+fn create_playlist(name: String, tracks: Option<Vec<String>>, state: State<AppState>) -> Result<Playlist, String> {
+    state.library.lock().unwrap().create_playlist(&name, &tracks.unwrap_or_default())
+}
+
+#[tauri::command]
+fn update_playlist(id: i64, name: Option<String>, tracks: Option<Vec<String>>, state: State<AppState>) -> Result<Playlist, String> {
+    state.library.lock().unwrap().update_playlist(id, name.as_deref(), tracks.as_deref())
+}
+
+#[tauri::command]
+fn delete_playlist(id: i64, state: State<AppState>) -> Result<(), String> {
+    state.library.lock().unwrap().delete_playlist(id)
+}
+
+#[tauri::command]
+fn get_smart_playlists(state: State<AppState>) -> Result<Vec<serde_json::Value>, String> {
+    state.library.lock().unwrap().smart_playlists()
+}
+
+#[tauri::command]
+fn save_smart_playlist(playlist: serde_json::Value, state: State<AppState>) -> Result<(), String> {
+    state.library.lock().unwrap().save_smart_playlist(&playlist)
+}
+
+#[tauri::command]
+fn delete_smart_playlist(id: String, state: State<AppState>) -> Result<(), String> {
+    state.library.lock().unwrap().delete_smart_playlist(&id)
+}
+
+/// One-time import of the library the frontend kept in localStorage.
+#[tauri::command]
+fn migrate_local_storage(data: serde_json::Value, state: State<AppState>) -> Result<MigrationReport, String> {
+    state.library.lock().unwrap().migrate_local_storage(&data)
+}
+
 #[tauri::command]
 fn save_playlist_file(path: String, content: String) -> Result<(), String> {
     fs::write(&path, content)
@@ -260,11 +1091,35 @@ fn get_crossfade_track_info(state: State<AppState>) -> Result<CrossfadeTrackInfo
     }
 }
 
+#[tauri::command]
+fn set_crossfade_beat_match(enabled: bool, state: State<AppState>) -> Result<(), String> {
+    let crossfade_player = state.crossfade_player.lock().unwrap();
+    if let Some(ref player) = *crossfade_player {
+        player.set_beat_match(enabled);
+        Ok(())
+    } else {
+        Err("Crossfade player not initialized".to_string())
+    }
+}
+
 #[tauri::command]
 fn play_song_with_crossfade(path: String, crossfade_duration: Option<f32>, state: State<AppState>) -> Result<(), String> {
     let crossfade_player = state.crossfade_player.lock().unwrap();
     if let Some(ref player) = *crossfade_player {
-        player.play_with_crossfade(&path, crossfade_duration)
+        // Beat matching needs the analyzed beat grid of both tracks, not just tagged BPM
+        let beat_sync = if player.get_crossfade_config().beat_match {
+            let analysis = state.track_analysis.lock().unwrap();
+            let tempo = |track: &str| analysis.get(track).and_then(|a| a.tempo);
+            player
+                .get_track_info()
+                .current_track
+                .and_then(|current| tempo(&current))
+                .zip(tempo(&path))
+                .and_then(|(outgoing, incoming)| BeatSync::new(outgoing, incoming))
+        } else {
+            None
+        };
+        player.play_with_crossfade(&path, crossfade_duration, beat_sync)
     } else {
         Err("Crossfade player not initialized".to_string())
     }
@@ -276,6 +1131,12 @@ pub fn run() {
     let app_state = AppState {
         player: Arc::new(Mutex::new(player)),
         crossfade_player: Arc::new(Mutex::new(None)),
+        eq_profiles: Arc::new(Mutex::new(EqProfileStore::default())),
+        settings: Arc::new(Mutex::new(SettingsStore::default())),
+        track_analysis: Arc::new(Mutex::new(TrackAnalysisStore::default())),
+        library: Arc::new(Mutex::new(LibraryDb::default())),
+        waveforms: Arc::new(WaveformService::default()),
+        verification: Arc::new(Mutex::new(None)),
     };
 
     tauri::Builder::default()
@@ -283,27 +1144,165 @@ pub fn run() {
         .plugin(tauri_plugin_dialog::init())
         .plugin(tauri_plugin_global_shortcut::Builder::new().build())
         .manage(app_state)
+        .setup(|app| {
+            let state = app.state::<AppState>();
+            let data_dir = app.path().app_data_dir()?;
+            state.eq_profiles.lock().unwrap().load(data_dir.join("eq_profiles.json"));
+            state.track_analysis.lock().unwrap().load(data_dir.join("track_analysis.json"));
+            if let Err(e) = state.library.lock().unwrap().open(data_dir.join("library.db")) {
+                eprintln!("{}", e);
+            }
+
+            state.waveforms.set_cache_dir(app.path().app_cache_dir()?.join("waveforms"));
+
+            let config_dir = app.path().app_config_dir()?;
+            let restored = {
+                let mut settings = state.settings.lock().unwrap();
+                settings.load(config_dir.join("settings.json"));
+                settings.get().clone()
+            };
+            {
+                let player = state.player.lock().unwrap();
+                if let Err(e) = player.set_volume_curve(restored.volume_curve) {
+                    eprintln!("Ignoring saved volume range: {}", e);
+                }
+                player.set_channel_mixer(restored.channels);
+                player.set_compressor(restored.compressor);
+                player.set_limiter(restored.limiter);
+                player.set_loudness(restored.loudness);
+                if let Err(e) = player.set_convolver(restored.convolver) {
+                    eprintln!("Failed to restore impulse response: {}", e);
+                }
+                if let Err(e) = player.set_spectrum_settings(restored.spectrum) {
+                    eprintln!("Ignoring saved spectrum settings: {}", e);
+                }
+            }
+
+            // Stream spectrum frames and meter readings to the frontend while playing
+            {
+                let player = state.player.lock().unwrap();
+                let handle = app.handle().clone();
+                player.set_spectrum_listener(move |frame| {
+                    let _ = handle.emit("spectrum", frame);
+                });
+                let handle = app.handle().clone();
+                player.set_level_listener(move |levels| {
+                    let _ = handle.emit("levels", levels);
+                });
+            }
+
+            // Switch to the EQ profile and crossfeed assigned to the output device whenever it
+            // changes; a device without a profile gets a flat EQ rather than the last device's
+            let eq_profiles = Arc::clone(&state.eq_profiles);
+            let settings = Arc::clone(&state.settings);
+            state.player.lock().unwrap().set_device_listener(move |device, effects| {
+                let params = eq_profiles.lock().unwrap().profile_for_device(device).unwrap_or_default();
+                if let Err(e) = effects.first_or_insert_with(ParametricEq::default).set_eq_params(params) {
+                    eprintln!("Failed to apply EQ profile for {}: {}", device, e);
+                }
+
+                let crossfeed = settings.lock().unwrap().get().crossfeed.get(device).cloned();
+                if let Err(e) = AudioPlayer::apply_crossfeed(effects, crossfeed) {
+                    eprintln!("Failed to apply crossfeed for {}: {}", device, e);
+                }
+            });
+            Ok(())
+        })
         .invoke_handler(tauri::generate_handler![
             play_song,
             pause,
             resume,
             stop,
             set_volume,
+            set_volume_range,
+            get_volume_range,
+            set_balance,
+            set_mono,
+            set_channel_swap,
+            get_channel_settings,
             seek,
+            set_ab_loop,
+            clear_ab_loop,
             get_current_time,
             get_song_info,
             get_track_metadata,
             set_equalizer_band,
             set_equalizer_preset,
             enable_equalizer,
+            set_parametric_eq,
+            get_parametric_eq,
+            import_eq_profile,
+            list_eq_profiles,
+            delete_eq_profile,
+            apply_eq_profile,
+            assign_eq_profile_to_device,
+            set_crossfeed,
+            get_crossfeed,
+            set_compressor,
+            get_compressor,
+            set_limiter,
+            get_limiter,
+            set_night_mode,
+            set_loudness_compensation,
+            get_loudness_compensation,
+            set_convolver,
+            get_convolver,
+            set_karaoke,
+            get_karaoke,
+            set_stereo_width,
+            get_stereo_width,
+            set_spectrum_settings,
+            get_spectrum_settings,
+            get_levels,
+            get_waveform,
+            export_spectrogram,
+            reset_clip_counters,
+            get_output_devices,
+            add_effect,
+            remove_effect,
+            set_effect_bypass,
+            move_effect,
+            set_effect_params,
+            get_effect_chain,
+            list_ladspa_plugins,
+            set_ladspa_control,
+            get_frequency_response,
             get_album_artwork,
             scan_music_folder,
             get_music_files_metadata,
+            detect_bpm,
+            detect_bpm_library,
+            detect_key,
+            detect_key_library,
+            get_track_analysis,
+            check_lossless,
+            check_lossless_library,
+            verify_file,
+            verify_library,
+            cancel_library_verification,
+            get_library_tracks,
+            get_library_track,
+            add_library_tracks,
+            remove_library_tracks,
+            set_track_rating,
+            set_track_favorite,
+            record_play,
+            get_play_history,
+            clear_play_history,
+            get_playlists,
+            create_playlist,
+            update_playlist,
+            delete_playlist,
+            get_smart_playlists,
+            save_smart_playlist,
+            delete_smart_playlist,
+            migrate_local_storage,
             save_playlist_file,
             enable_crossfade,
             set_crossfade_duration,
             get_crossfade_config,
             get_crossfade_track_info,
+            set_crossfade_beat_match,
             play_song_with_crossfade
         ])
         .run(tauri::generate_context!())
@@ -0,0 +1,970 @@
+use crate::audio_new::TrackMetadata;
+use rusqlite::{params, Connection, OptionalExtension, Row, Transaction};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+/// Schema changes, applied in order; `PRAGMA user_version` records how many
+/// have run. Append new ones, never edit old ones.
+const MIGRATIONS: &[&str] = &[
+    "CREATE TABLE tracks (
+        path TEXT PRIMARY KEY,
+        name TEXT NOT NULL,
+        title TEXT,
+        artist TEXT,
+        album TEXT,
+        track_number INTEGER,
+        year INTEGER,
+        genre TEXT,
+        duration REAL,
+        codec TEXT,
+        sample_rate INTEGER,
+        channels TEXT,
+        bits_per_sample INTEGER,
+        bpm REAL,
+        initial_key TEXT,
+        has_artwork INTEGER NOT NULL DEFAULT 0,
+        added_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ', 'now'))
+    );
+    CREATE TABLE ratings (
+        path TEXT PRIMARY KEY REFERENCES tracks(path) ON DELETE CASCADE,
+        rating INTEGER NOT NULL DEFAULT 0 CHECK (rating BETWEEN 0 AND 5),
+        favorite INTEGER NOT NULL DEFAULT 0
+    );
+    CREATE TABLE play_events (
+        id INTEGER PRIMARY KEY,
+        path TEXT NOT NULL REFERENCES tracks(path) ON DELETE CASCADE,
+        played_at TEXT NOT NULL,
+        plays INTEGER NOT NULL DEFAULT 1
+    );
+    CREATE INDEX play_events_path ON play_events(path);
+    CREATE TABLE playlists (
+        id INTEGER PRIMARY KEY,
+        name TEXT NOT NULL,
+        created_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ', 'now')),
+        updated_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ', 'now'))
+    );
+    CREATE TABLE playlist_tracks (
+        playlist_id INTEGER NOT NULL REFERENCES playlists(id) ON DELETE CASCADE,
+        position INTEGER NOT NULL,
+        path TEXT NOT NULL REFERENCES tracks(path) ON DELETE CASCADE,
+        PRIMARY KEY (playlist_id, position)
+    );
+    CREATE TABLE smart_playlists (
+        id TEXT PRIMARY KEY,
+        definition TEXT NOT NULL
+    );
+    CREATE TABLE library_meta (
+        name TEXT PRIMARY KEY,
+        value TEXT NOT NULL
+    );",
+    "ALTER TABLE tracks ADD COLUMN size INTEGER;
+    ALTER TABLE tracks ADD COLUMN modified INTEGER;",
+];
+
+/// `library_meta` entry set once the localStorage data has been imported.
+const LOCAL_STORAGE_MIGRATED: &str = "local_storage_migrated";
+
+const TRACK_COLUMNS: &str = "t.path, t.name, t.title, t.artist, t.album, t.track_number, t.year, t.genre, t.duration, t.codec,
+    t.sample_rate, t.channels, t.bits_per_sample, t.bpm, t.initial_key, t.has_artwork, t.added_at,
+    COALESCE(r.rating, 0), COALESCE(r.favorite, 0)";
+
+/// Size and modification time of a file, which tell a rescan whether it changed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FileStat {
+    pub size: u64,
+    /// Milliseconds since the epoch.
+    pub modified: u64,
+}
+
+impl FileStat {
+    pub fn from_metadata(metadata: &fs::Metadata) -> Self {
+        Self {
+            size: metadata.len(),
+            modified: metadata
+                .modified()
+                .ok()
+                .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+                .map(|d| d.as_millis() as u64)
+                .unwrap_or(0),
+        }
+    }
+
+    pub fn read(path: &str) -> Result<Self, String> {
+        fs::metadata(path)
+            .map(|metadata| Self::from_metadata(&metadata))
+            .map_err(|e| format!("Failed to read file: {}", e))
+    }
+}
+
+/// How a rescan treats the music files found under a folder.
+#[derive(Debug, Default, PartialEq)]
+pub struct ScanPlan {
+    /// Files the player's library doesn't have, whose metadata has to be read.
+    pub added: Vec<String>,
+    /// Files the player's library doesn't have but the database has current
+    /// metadata for, such as songs removed from the playlist.
+    pub restored: Vec<String>,
+    /// Library files changed since their metadata was stored, or never stored.
+    pub changed: Vec<String>,
+    pub unchanged: usize,
+    /// Library and database files under the folder that weren't found.
+    pub missing: Vec<String>,
+}
+
+/// Sorts the music files found under `folder`, with their current state,
+/// against the paths of the player's library and the file states the
+/// database has stored for the folder (see `LibraryDb::file_stats`).
+pub fn classify_scan(
+    folder: &Path,
+    found: Vec<(String, FileStat)>,
+    library: &HashSet<String>,
+    mut stored: HashMap<String, Option<FileStat>>,
+) -> ScanPlan {
+    let mut plan = ScanPlan::default();
+    let mut unseen: HashSet<&String> = library.iter().filter(|path| Path::new(path).starts_with(folder)).collect();
+
+    for (path, stat) in found {
+        unseen.remove(&path);
+        let current = stored.remove(&path).flatten() == Some(stat);
+        match (library.contains(&path), current) {
+            (false, false) => plan.added.push(path),
+            (false, true) => plan.restored.push(path),
+            (true, false) => plan.changed.push(path),
+            (true, true) => plan.unchanged += 1,
+        }
+    }
+
+    let mut missing: Vec<String> = unseen.into_iter().cloned().chain(stored.into_keys()).collect();
+    missing.sort();
+    missing.dedup();
+    plan.missing = missing;
+    plan
+}
+
+/// A track to add to the library, as `get_music_files_metadata` returns it.
+#[derive(Debug, Clone, serde::Deserialize)]
+pub struct NewTrack {
+    pub path: String,
+    pub name: String,
+    pub metadata: Option<TrackMetadata>,
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct LibraryTrack {
+    pub path: String,
+    pub name: String,
+    pub metadata: Option<TrackMetadata>,
+    /// Stars from 0 to 5.
+    pub rating: u8,
+    pub favorite: bool,
+    pub added_at: String,
+}
+
+/// Plays of one track, most recent first in the history.
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct PlayStats {
+    pub path: String,
+    pub play_count: u32,
+    pub last_played: String,
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct Playlist {
+    pub id: i64,
+    pub name: String,
+    /// Track paths in playing order.
+    pub tracks: Vec<String>,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+/// What `migrate_local_storage` imported.
+#[derive(Debug, Clone, Default, serde::Serialize)]
+pub struct MigrationReport {
+    pub tracks: usize,
+    pub ratings: usize,
+    pub favorites: usize,
+    pub play_events: usize,
+    pub smart_playlists: usize,
+    /// Ratings, favorites and history entries for files no longer in the library.
+    pub skipped: usize,
+}
+
+/// A song as the frontend kept it in localStorage.
+#[derive(serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct StoredSong {
+    path: String,
+    name: String,
+    metadata: Option<TrackMetadata>,
+    rating: Option<u8>,
+    is_favorite: Option<bool>,
+}
+
+#[derive(serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct StoredHistoryEntry {
+    song: StoredSong,
+    played_at: String,
+    play_count: u32,
+}
+
+fn track_from_row(row: &Row) -> rusqlite::Result<LibraryTrack> {
+    let duration: Option<f64> = row.get(8)?;
+    let metadata = match duration {
+        Some(duration) => Some(TrackMetadata {
+            title: row.get(2)?,
+            artist: row.get(3)?,
+            album: row.get(4)?,
+            track_number: row.get(5)?,
+            year: row.get(6)?,
+            genre: row.get(7)?,
+            duration,
+            codec: row.get(9)?,
+            sample_rate: row.get(10)?,
+            channels: row.get(11)?,
+            bits_per_sample: row.get(12)?,
+            bpm: row.get(13)?,
+            key: row.get(14)?,
+            has_artwork: row.get(15)?,
+        }),
+        // Files the scan couldn't read are stored without metadata
+        None => None,
+    };
+    Ok(LibraryTrack {
+        path: row.get(0)?,
+        name: row.get(1)?,
+        metadata,
+        rating: row.get(17)?,
+        favorite: row.get(18)?,
+        added_at: row.get(16)?,
+    })
+}
+
+/// Adds or updates a track, keeping its rating and history. `stat` is the
+/// state of the file the metadata was read from; without it the next rescan
+/// reads the file again.
+fn upsert_track(tx: &Transaction, track: &NewTrack, stat: Option<FileStat>) -> rusqlite::Result<()> {
+    let m = track.metadata.as_ref();
+    tx.execute(
+        "INSERT INTO tracks (path, name, title, artist, album, track_number, year, genre, duration, codec,
+            sample_rate, channels, bits_per_sample, bpm, initial_key, has_artwork, size, modified)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18)
+         ON CONFLICT(path) DO UPDATE SET
+            name = excluded.name, title = excluded.title, artist = excluded.artist, album = excluded.album,
+            track_number = excluded.track_number, year = excluded.year, genre = excluded.genre,
+            duration = excluded.duration, codec = excluded.codec, sample_rate = excluded.sample_rate,
+            channels = excluded.channels, bits_per_sample = excluded.bits_per_sample, bpm = excluded.bpm,
+            initial_key = excluded.initial_key, has_artwork = excluded.has_artwork,
+            size = excluded.size, modified = excluded.modified",
+        params![
+            track.path,
+            track.name,
+            m.and_then(|m| m.title.as_ref()),
+            m.and_then(|m| m.artist.as_ref()),
+            m.and_then(|m| m.album.as_ref()),
+            m.and_then(|m| m.track_number),
+            m.and_then(|m| m.year),
+            m.and_then(|m| m.genre.as_ref()),
+            m.map(|m| m.duration),
+            m.and_then(|m| m.codec.as_ref()),
+            m.and_then(|m| m.sample_rate),
+            m.and_then(|m| m.channels.as_ref()),
+            m.and_then(|m| m.bits_per_sample),
+            m.and_then(|m| m.bpm),
+            m.and_then(|m| m.key.as_ref()),
+            m.is_some_and(|m| m.has_artwork),
+            stat.map(|stat| stat.size as i64),
+            stat.map(|stat| stat.modified as i64),
+        ],
+    )?;
+    Ok(())
+}
+
+fn track_exists(tx: &Transaction, path: &str) -> rusqlite::Result<bool> {
+    tx.query_row("SELECT EXISTS (SELECT 1 FROM tracks WHERE path = ?1)", [path], |row| row.get(0))
+}
+
+fn set_rating_in(tx: &Transaction, path: &str, rating: u8) -> rusqlite::Result<()> {
+    tx.execute(
+        "INSERT INTO ratings (path, rating) VALUES (?1, ?2) ON CONFLICT(path) DO UPDATE SET rating = excluded.rating",
+        params![path, rating],
+    )?;
+    Ok(())
+}
+
+fn set_favorite_in(tx: &Transaction, path: &str, favorite: bool) -> rusqlite::Result<()> {
+    tx.execute(
+        "INSERT INTO ratings (path, favorite) VALUES (?1, ?2) ON CONFLICT(path) DO UPDATE SET favorite = excluded.favorite",
+        params![path, favorite],
+    )?;
+    Ok(())
+}
+
+/// Replaces the tracks of a playlist and marks it updated.
+fn set_playlist_tracks(tx: &Transaction, id: i64, tracks: &[String]) -> rusqlite::Result<()> {
+    tx.execute("DELETE FROM playlist_tracks WHERE playlist_id = ?1", [id])?;
+    let mut insert = tx.prepare("INSERT INTO playlist_tracks (playlist_id, position, path) VALUES (?1, ?2, ?3)")?;
+    for (position, path) in tracks.iter().enumerate() {
+        insert.execute(params![id, position as i64, path])?;
+    }
+    Ok(())
+}
+
+/// Reads one localStorage item, which the frontend may pass either as the
+/// stored JSON string or already parsed.
+fn stored_item<T: serde::de::DeserializeOwned>(data: &serde_json::Value, key: &str) -> Result<Option<T>, String> {
+    let parsed = match data.get(key) {
+        None | Some(serde_json::Value::Null) => return Ok(None),
+        Some(serde_json::Value::String(text)) => serde_json::from_str(text),
+        Some(value) => serde_json::from_value(value.clone()),
+    };
+    parsed.map(Some).map_err(|e| format!("Invalid {}: {}", key, e))
+}
+
+/// The music library in SQLite: tracks with their metadata, ratings, play
+/// history and playlists.
+#[derive(Default)]
+pub struct LibraryDb {
+    connection: Option<Connection>,
+}
+
+impl LibraryDb {
+    /// Opens or creates the database at `path` and brings its schema up to date.
+    pub fn open(&mut self, path: PathBuf) -> Result<(), String> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).map_err(|e| format!("Failed to create library directory: {}", e))?;
+        }
+        let connection = Connection::open(&path).map_err(|e| format!("Failed to open library database: {}", e))?;
+        self.attach(connection)
+    }
+
+    /// Brings the schema of `connection` up to date and uses it from now on.
+    fn attach(&mut self, mut connection: Connection) -> Result<(), String> {
+        connection
+            .pragma_update(None, "foreign_keys", true)
+            .map_err(|e| format!("Failed to open library database: {}", e))?;
+
+        let version: usize = connection
+            .pragma_query_value(None, "user_version", |row| row.get(0))
+            .map_err(|e| format!("Failed to read library schema version: {}", e))?;
+        if version > MIGRATIONS.len() {
+            return Err("Library database was created by a newer version".to_string());
+        }
+        for (index, migration) in MIGRATIONS.iter().enumerate().skip(version) {
+            let tx = connection.transaction().map_err(|e| format!("Failed to upgrade library database: {}", e))?;
+            tx.execute_batch(migration)
+                .and_then(|_| tx.pragma_update(None, "user_version", index + 1))
+                .and_then(|_| tx.commit())
+                .map_err(|e| format!("Failed to upgrade library database: {}", e))?;
+        }
+
+        self.connection = Some(connection);
+        Ok(())
+    }
+
+    fn connection(&mut self) -> Result<&mut Connection, String> {
+        self.connection.as_mut().ok_or_else(|| "Library database is not open".to_string())
+    }
+
+    /// Runs `f` in a transaction, committing only if it succeeds.
+    fn write<T>(&mut self, f: impl FnOnce(&Transaction) -> rusqlite::Result<T>) -> Result<T, String> {
+        let tx = self.connection()?.transaction().map_err(|e| format!("Library database error: {}", e))?;
+        let result = f(&tx).map_err(|e| format!("Library database error: {}", e))?;
+        tx.commit().map_err(|e| format!("Library database error: {}", e))?;
+        Ok(result)
+    }
+
+    /// Like `write`, for changes to a track that has to be in the library.
+    fn write_track(&mut self, path: &str, f: impl FnOnce(&Transaction) -> rusqlite::Result<()>) -> Result<(), String> {
+        let found = self.write(|tx| {
+            let found = track_exists(tx, path)?;
+            if found {
+                f(tx)?;
+            }
+            Ok(found)
+        })?;
+        if found {
+            Ok(())
+        } else {
+            Err(format!("{} is not in the library", path))
+        }
+    }
+
+    /// All tracks, in the order they were added.
+    pub fn tracks(&mut self) -> Result<Vec<LibraryTrack>, String> {
+        let connection = self.connection()?;
+        let mut statement = connection
+            .prepare(&format!(
+                "SELECT {} FROM tracks t LEFT JOIN ratings r ON r.path = t.path ORDER BY t.rowid",
+                TRACK_COLUMNS
+            ))
+            .map_err(|e| format!("Library database error: {}", e))?;
+        let rows = statement.query_map([], track_from_row).map_err(|e| format!("Library database error: {}", e))?;
+        rows.collect::<rusqlite::Result<_>>().map_err(|e| format!("Library database error: {}", e))
+    }
+
+    pub fn track(&mut self, path: &str) -> Result<Option<LibraryTrack>, String> {
+        self.connection()?
+            .query_row(
+                &format!("SELECT {} FROM tracks t LEFT JOIN ratings r ON r.path = t.path WHERE t.path = ?1", TRACK_COLUMNS),
+                [path],
+                track_from_row,
+            )
+            .optional()
+            .map_err(|e| format!("Library database error: {}", e))
+    }
+
+    /// Adds tracks, updating the metadata of those already present. The
+    /// metadata is taken to describe the files as they are now. Returns how
+    /// many were new.
+    pub fn add_tracks(&mut self, tracks: &[NewTrack]) -> Result<usize, String> {
+        self.write(|tx| {
+            let mut added = 0;
+            for track in tracks {
+                if !track_exists(tx, &track.path)? {
+                    added += 1;
+                }
+                upsert_track(tx, track, FileStat::read(&track.path).ok())?;
+            }
+            Ok(added)
+        })
+    }
+
+    /// Stored file state of every track under `folder`; `None` for tracks
+    /// whose file state was never recorded.
+    pub fn file_stats(&mut self, folder: &Path) -> Result<HashMap<String, Option<FileStat>>, String> {
+        let connection = self.connection()?;
+        let mut statement = connection
+            .prepare("SELECT path, size, modified FROM tracks")
+            .map_err(|e| format!("Library database error: {}", e))?;
+        let rows = statement
+            .query_map([], |row| {
+                let size: Option<i64> = row.get(1)?;
+                let modified: Option<i64> = row.get(2)?;
+                let stat = size.zip(modified).map(|(size, modified)| FileStat {
+                    size: size as u64,
+                    modified: modified as u64,
+                });
+                Ok((row.get::<_, String>(0)?, stat))
+            })
+            .map_err(|e| format!("Library database error: {}", e))?;
+
+        let mut stats = HashMap::new();
+        for row in rows {
+            let (path, stat) = row.map_err(|e| format!("Library database error: {}", e))?;
+            if Path::new(&path).starts_with(folder) {
+                stats.insert(path, stat);
+            }
+        }
+        Ok(stats)
+    }
+
+    /// The stored track for `path`, if the file hasn't changed since its
+    /// metadata was read.
+    pub fn unchanged_track(&mut self, path: &str) -> Result<Option<LibraryTrack>, String> {
+        let stored: Option<(Option<i64>, Option<i64>)> = self
+            .connection()?
+            .query_row("SELECT size, modified FROM tracks WHERE path = ?1", [path], |row| Ok((row.get(0)?, row.get(1)?)))
+            .optional()
+            .map_err(|e| format!("Library database error: {}", e))?;
+        let stored = stored.and_then(|(size, modified)| size.zip(modified)).map(|(size, modified)| FileStat {
+            size: size as u64,
+            modified: modified as u64,
+        });
+        match (stored, FileStat::read(path).ok()) {
+            (Some(stored), Some(current)) if stored == current => self.track(path),
+            _ => Ok(None),
+        }
+    }
+
+    /// Removes tracks along with their ratings, history and playlist entries.
+    /// Returns how many were in the library.
+    pub fn remove_tracks(&mut self, paths: &[String]) -> Result<usize, String> {
+        self.write(|tx| {
+            let mut removed = 0;
+            for path in paths {
+                removed += tx.execute("DELETE FROM tracks WHERE path = ?1", [path])?;
+            }
+            Ok(removed)
+        })
+    }
+
+    /// Stores an analyzed tempo for `path` if it is in the library. A tagged
+    /// tempo is kept unless `replace`, as when the estimate was just written
+    /// to the tag.
+    pub fn set_bpm(&mut self, path: &str, bpm: f32, replace: bool) -> Result<(), String> {
+        self.write(|tx| {
+            tx.execute(
+                "UPDATE tracks SET bpm = CASE WHEN ?3 THEN ?2 ELSE COALESCE(bpm, ?2) END WHERE path = ?1",
+                params![path, bpm, replace],
+            )
+        })
+        .map(|_| ())
+    }
+
+    /// Stores an analyzed key for `path` if it is in the library, like `set_bpm`.
+    pub fn set_initial_key(&mut self, path: &str, key: &str, replace: bool) -> Result<(), String> {
+        self.write(|tx| {
+            tx.execute(
+                "UPDATE tracks SET initial_key = CASE WHEN ?3 THEN ?2 ELSE COALESCE(initial_key, ?2) END WHERE path = ?1",
+                params![path, key, replace],
+            )
+        })
+        .map(|_| ())
+    }
+
+    pub fn set_rating(&mut self, path: &str, rating: u8) -> Result<(), String> {
+        if rating > 5 {
+            return Err("Rating must be between 0 and 5".to_string());
+        }
+        self.write_track(path, |tx| set_rating_in(tx, path, rating))
+    }
+
+    pub fn set_favorite(&mut self, path: &str, favorite: bool) -> Result<(), String> {
+        self.write_track(path, |tx| set_favorite_in(tx, path, favorite))
+    }
+
+    /// Records a play of `path`, at `played_at` (an ISO 8601 timestamp) or now.
+    pub fn record_play(&mut self, path: &str, played_at: Option<&str>) -> Result<(), String> {
+        self.write_track(path, |tx| {
+            tx.execute(
+                "INSERT INTO play_events (path, played_at) VALUES (?1, COALESCE(?2, strftime('%Y-%m-%dT%H:%M:%fZ', 'now')))",
+                params![path, played_at],
+            )?;
+            Ok(())
+        })
+    }
+
+    /// Play counts per track, most recently played first.
+    pub fn play_history(&mut self, limit: Option<usize>) -> Result<Vec<PlayStats>, String> {
+        let connection = self.connection()?;
+        let mut statement = connection
+            .prepare(
+                "SELECT path, SUM(plays), MAX(played_at) FROM play_events
+                 GROUP BY path ORDER BY MAX(played_at) DESC LIMIT ?1",
+            )
+            .map_err(|e| format!("Library database error: {}", e))?;
+        let rows = statement
+            .query_map([limit.map_or(-1, |limit| limit as i64)], |row| {
+                Ok(PlayStats {
+                    path: row.get(0)?,
+                    play_count: row.get(1)?,
+                    last_played: row.get(2)?,
+                })
+            })
+            .map_err(|e| format!("Library database error: {}", e))?;
+        rows.collect::<rusqlite::Result<_>>().map_err(|e| format!("Library database error: {}", e))
+    }
+
+    pub fn clear_play_history(&mut self) -> Result<(), String> {
+        self.write(|tx| tx.execute("DELETE FROM play_events", [])).map(|_| ())
+    }
+
+    pub fn playlists(&mut self) -> Result<Vec<Playlist>, String> {
+        let connection = self.connection()?;
+        let mut statement = connection
+            .prepare("SELECT id, name, created_at, updated_at FROM playlists ORDER BY name COLLATE NOCASE, id")
+            .map_err(|e| format!("Library database error: {}", e))?;
+        let mut tracks = connection
+            .prepare("SELECT path FROM playlist_tracks WHERE playlist_id = ?1 ORDER BY position")
+            .map_err(|e| format!("Library database error: {}", e))?;
+        let rows = statement
+            .query_map([], |row| {
+                let id = row.get(0)?;
+                Ok(Playlist {
+                    id,
+                    name: row.get(1)?,
+                    tracks: tracks.query_map([id], |row| row.get(0))?.collect::<rusqlite::Result<_>>()?,
+                    created_at: row.get(2)?,
+                    updated_at: row.get(3)?,
+                })
+            })
+            .map_err(|e| format!("Library database error: {}", e))?;
+        rows.collect::<rusqlite::Result<_>>().map_err(|e| format!("Library database error: {}", e))
+    }
+
+    fn playlist(&mut self, id: i64) -> Result<Playlist, String> {
+        let connection = self.connection()?;
+        let playlist = connection
+            .query_row("SELECT id, name, created_at, updated_at FROM playlists WHERE id = ?1", [id], |row| {
+                Ok(Playlist {
+                    id: row.get(0)?,
+                    name: row.get(1)?,
+                    tracks: Vec::new(),
+                    created_at: row.get(2)?,
+                    updated_at: row.get(3)?,
+                })
+            })
+            .optional()
+            .map_err(|e| format!("Library database error: {}", e))?;
+        let mut playlist = playlist.ok_or_else(|| format!("No playlist with id {}", id))?;
+        playlist.tracks = connection
+            .prepare("SELECT path FROM playlist_tracks WHERE playlist_id = ?1 ORDER BY position")
+            .and_then(|mut statement| statement.query_map([id], |row| row.get(0))?.collect())
+            .map_err(|e| format!("Library database error: {}", e))?;
+        Ok(playlist)
+    }
+
+    pub fn create_playlist(&mut self, name: &str, tracks: &[String]) -> Result<Playlist, String> {
+        let id = self.write(|tx| {
+            tx.execute("INSERT INTO playlists (name) VALUES (?1)", [name])?;
+            let id = tx.last_insert_rowid();
+            set_playlist_tracks(tx, id, tracks)?;
+            Ok(id)
+        })?;
+        self.playlist(id)
+    }
+
+    /// Renames a playlist and/or replaces its tracks.
+    pub fn update_playlist(&mut self, id: i64, name: Option<&str>, tracks: Option<&[String]>) -> Result<Playlist, String> {
+        let found = self.write(|tx| {
+            let found = tx.execute(
+                "UPDATE playlists SET name = COALESCE(?2, name), updated_at = strftime('%Y-%m-%dT%H:%M:%fZ', 'now') WHERE id = ?1",
+                params![id, name],
+            )?;
+            if let Some(tracks) = tracks.filter(|_| found > 0) {
+                set_playlist_tracks(tx, id, tracks)?;
+            }
+            Ok(found > 0)
+        })?;
+        if !found {
+            return Err(format!("No playlist with id {}", id));
+        }
+        self.playlist(id)
+    }
+
+    pub fn delete_playlist(&mut self, id: i64) -> Result<(), String> {
+        match self.write(|tx| tx.execute("DELETE FROM playlists WHERE id = ?1", [id]))? {
+            0 => Err(format!("No playlist with id {}", id)),
+            _ => Ok(()),
+        }
+    }
+
+    /// Smart playlists as the frontend defines them; their rules are
+    /// evaluated there, so they are stored as given.
+    pub fn smart_playlists(&mut self) -> Result<Vec<serde_json::Value>, String> {
+        let connection = self.connection()?;
+        let mut statement = connection
+            .prepare("SELECT definition FROM smart_playlists ORDER BY rowid")
+            .map_err(|e| format!("Library database error: {}", e))?;
+        let definitions: Vec<String> = statement
+            .query_map([], |row| row.get(0))
+            .and_then(|rows| rows.collect())
+            .map_err(|e| format!("Library database error: {}", e))?;
+        definitions
+            .iter()
+            .map(|definition| serde_json::from_str(definition).map_err(|e| format!("Invalid smart playlist: {}", e)))
+            .collect()
+    }
+
+    /// Adds a smart playlist or replaces the one with the same `id`.
+    pub fn save_smart_playlist(&mut self, playlist: &serde_json::Value) -> Result<(), String> {
+        let id = playlist.get("id").and_then(|id| id.as_str()).ok_or("Smart playlist needs an id")?;
+        let definition = playlist.to_string();
+        self.write(|tx| {
+            tx.execute(
+                "INSERT INTO smart_playlists (id, definition) VALUES (?1, ?2)
+                 ON CONFLICT(id) DO UPDATE SET definition = excluded.definition",
+                params![id, definition],
+            )
+        })
+        .map(|_| ())
+    }
+
+    pub fn delete_smart_playlist(&mut self, id: &str) -> Result<(), String> {
+        match self.write(|tx| tx.execute("DELETE FROM smart_playlists WHERE id = ?1", [id]))? {
+            0 => Err(format!("No smart playlist with id {}", id)),
+            _ => Ok(()),
+        }
+    }
+
+    /// Imports the library the frontend kept in localStorage, given as an
+    /// object of its items (`musicPlayerPlaylist`, `musicPlayerRatings`,
+    /// ...). Runs once; later calls fail so the data isn't imported twice.
+    pub fn migrate_local_storage(&mut self, data: &serde_json::Value) -> Result<MigrationReport, String> {
+        let songs: Vec<StoredSong> = stored_item(data, "musicPlayerPlaylist")?.unwrap_or_default();
+        let history: Vec<StoredHistoryEntry> = stored_item(data, "musicPlayerHistory")?.unwrap_or_default();
+        let ratings: HashMap<String, u8> = stored_item(data, "musicPlayerRatings")?.unwrap_or_default();
+        let favorites: HashMap<String, bool> = stored_item(data, "musicPlayerFavorites")?.unwrap_or_default();
+        let smart_playlists: Vec<serde_json::Value> = stored_item(data, "musicPlayerSmartPlaylists")?.unwrap_or_default();
+
+        let done: Option<String> = self
+            .connection()?
+            .query_row("SELECT value FROM library_meta WHERE name = ?1", [LOCAL_STORAGE_MIGRATED], |row| row.get(0))
+            .optional()
+            .map_err(|e| format!("Library database error: {}", e))?;
+        if let Some(when) = done {
+            return Err(format!("Library was already imported from local storage on {}", when));
+        }
+
+        self.write(|tx| {
+            let mut report = MigrationReport::default();
+            for song in &songs {
+                // The stored metadata may predate changes to the file, so the next rescan reads it again
+                upsert_track(
+                    tx,
+                    &NewTrack {
+                        path: song.path.clone(),
+                        name: song.name.clone(),
+                        metadata: song.metadata.clone(),
+                    },
+                    None,
+                )?;
+                report.tracks += 1;
+            }
+
+            // The separate maps are what the player kept up to date; the
+            // copies on the songs are only a fallback
+            let song_ratings = songs.iter().filter_map(|song| song.rating.map(|rating| (&song.path, rating)));
+            let ratings: HashMap<&String, u8> = song_ratings.chain(ratings.iter().map(|(path, rating)| (path, *rating))).collect();
+            for (path, rating) in ratings {
+                if track_exists(tx, path)? {
+                    set_rating_in(tx, path, rating.min(5))?;
+                    report.ratings += 1;
+                } else {
+                    report.skipped += 1;
+                }
+            }
+
+            let song_favorites = songs.iter().filter_map(|song| song.is_favorite.map(|favorite| (&song.path, favorite)));
+            let favorites: HashMap<&String, bool> = song_favorites.chain(favorites.iter().map(|(path, favorite)| (path, *favorite))).collect();
+            for (path, favorite) in favorites {
+                if track_exists(tx, path)? {
+                    set_favorite_in(tx, path, favorite)?;
+                    report.favorites += 1;
+                } else {
+                    report.skipped += 1;
+                }
+            }
+
+            // The old history only kept each track's last play and total count
+            for entry in &history {
+                if track_exists(tx, &entry.song.path)? {
+                    tx.execute(
+                        "INSERT INTO play_events (path, played_at, plays) VALUES (?1, ?2, ?3)",
+                        params![entry.song.path, entry.played_at, entry.play_count.max(1)],
+                    )?;
+                    report.play_events += 1;
+                } else {
+                    report.skipped += 1;
+                }
+            }
+
+            for playlist in &smart_playlists {
+                if let Some(id) = playlist.get("id").and_then(|id| id.as_str()) {
+                    tx.execute(
+                        "INSERT OR REPLACE INTO smart_playlists (id, definition) VALUES (?1, ?2)",
+                        params![id, playlist.to_string()],
+                    )?;
+                    report.smart_playlists += 1;
+                }
+            }
+
+            tx.execute(
+                "INSERT INTO library_meta (name, value) VALUES (?1, strftime('%Y-%m-%dT%H:%M:%fZ', 'now'))",
+                [LOCAL_STORAGE_MIGRATED],
+            )?;
+            Ok(report)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn memory_db() -> LibraryDb {
+        let mut db = LibraryDb::default();
+        db.attach(Connection::open_in_memory().unwrap()).unwrap();
+        db
+    }
+
+    fn metadata(title: &str, bpm: Option<f32>) -> TrackMetadata {
+        TrackMetadata {
+            title: Some(title.to_string()),
+            artist: None,
+            album: None,
+            track_number: None,
+            year: None,
+            genre: None,
+            duration: 180.0,
+            codec: None,
+            sample_rate: None,
+            channels: None,
+            bits_per_sample: None,
+            bpm,
+            key: None,
+            has_artwork: false,
+        }
+    }
+
+    fn new_track(path: &str, metadata: Option<TrackMetadata>) -> NewTrack {
+        NewTrack {
+            path: path.to_string(),
+            name: path.trim_start_matches('/').to_string(),
+            metadata,
+        }
+    }
+
+    fn count(db: &mut LibraryDb, table: &str) -> i64 {
+        db.connection()
+            .unwrap()
+            .query_row(&format!("SELECT COUNT(*) FROM {}", table), [], |row| row.get(0))
+            .unwrap()
+    }
+
+    #[test]
+    fn migrations_bring_the_schema_up_to_date() {
+        let mut db = memory_db();
+        let version: usize = db.connection().unwrap().pragma_query_value(None, "user_version", |row| row.get(0)).unwrap();
+        assert_eq!(version, MIGRATIONS.len());
+        assert_eq!(count(&mut db, "tracks"), 0);
+
+        let newer = Connection::open_in_memory().unwrap();
+        newer.pragma_update(None, "user_version", MIGRATIONS.len() + 1).unwrap();
+        assert!(LibraryDb::default().attach(newer).is_err());
+    }
+
+    #[test]
+    fn readding_a_track_keeps_its_rating() {
+        let mut db = memory_db();
+        assert_eq!(db.add_tracks(&[new_track("/a.mp3", Some(metadata("Old", None)))]).unwrap(), 1);
+        db.set_rating("/a.mp3", 4).unwrap();
+        db.set_favorite("/a.mp3", true).unwrap();
+
+        assert_eq!(db.add_tracks(&[new_track("/a.mp3", Some(metadata("New", None)))]).unwrap(), 0);
+        let track = db.track("/a.mp3").unwrap().unwrap();
+        assert_eq!(track.metadata.unwrap().title.as_deref(), Some("New"));
+        assert_eq!(track.rating, 4);
+        assert!(track.favorite);
+    }
+
+    #[test]
+    fn removing_a_track_removes_what_refers_to_it() {
+        let mut db = memory_db();
+        db.add_tracks(&[new_track("/a.mp3", None), new_track("/b.mp3", None)]).unwrap();
+        db.set_rating("/a.mp3", 5).unwrap();
+        db.record_play("/a.mp3", None).unwrap();
+        let playlist = db.create_playlist("Mix", &["/a.mp3".to_string(), "/b.mp3".to_string()]).unwrap();
+
+        assert_eq!(db.remove_tracks(&["/a.mp3".to_string(), "/gone.mp3".to_string()]).unwrap(), 1);
+        assert_eq!(count(&mut db, "ratings"), 0);
+        assert_eq!(count(&mut db, "play_events"), 0);
+        assert_eq!(db.playlist(playlist.id).unwrap().tracks, ["/b.mp3"]);
+
+        db.delete_playlist(playlist.id).unwrap();
+        assert_eq!(count(&mut db, "playlist_tracks"), 0);
+        assert!(db.playlist(playlist.id).is_err());
+    }
+
+    #[test]
+    fn analysis_keeps_tagged_values_unless_replaced() {
+        let mut db = memory_db();
+        db.add_tracks(&[new_track("/a.mp3", Some(metadata("A", Some(120.0)))), new_track("/b.mp3", Some(metadata("B", None)))])
+            .unwrap();
+
+        db.set_bpm("/a.mp3", 121.3, false).unwrap();
+        db.set_bpm("/b.mp3", 98.0, false).unwrap();
+        db.set_initial_key("/b.mp3", "8A", false).unwrap();
+        // Files outside the library are left alone
+        db.set_bpm("/gone.mp3", 100.0, true).unwrap();
+
+        let bpm = |db: &mut LibraryDb, path: &str| db.track(path).unwrap().unwrap().metadata.unwrap().bpm;
+        assert_eq!(bpm(&mut db, "/a.mp3"), Some(120.0));
+        assert_eq!(bpm(&mut db, "/b.mp3"), Some(98.0));
+        assert_eq!(db.track("/b.mp3").unwrap().unwrap().metadata.unwrap().key.as_deref(), Some("8A"));
+
+        db.set_bpm("/a.mp3", 121.0, true).unwrap();
+        assert_eq!(bpm(&mut db, "/a.mp3"), Some(121.0));
+    }
+
+    #[test]
+    fn scan_compares_with_the_player_library_and_stored_state() {
+        let stat = |size| FileStat { size, modified: 1000 };
+        let found = vec![
+            ("/music/new.mp3".to_string(), stat(1)),
+            ("/music/dropped.mp3".to_string(), stat(2)),
+            ("/music/dropped-edited.mp3".to_string(), stat(3)),
+            ("/music/same.mp3".to_string(), stat(4)),
+            ("/music/edited.mp3".to_string(), stat(5)),
+            ("/music/unrecorded.mp3".to_string(), stat(6)),
+            ("/music/not-in-db.mp3".to_string(), stat(7)),
+        ];
+        let library: HashSet<String> = ["same", "edited", "unrecorded", "not-in-db", "gone", "gone-from-db"]
+            .iter()
+            .map(|name| format!("/music/{}.mp3", name))
+            .chain(["/elsewhere/other.mp3".to_string()])
+            .collect();
+        let stored: HashMap<String, Option<FileStat>> = [
+            ("dropped", Some(stat(2))),
+            ("dropped-edited", Some(stat(30))),
+            ("same", Some(stat(4))),
+            ("edited", Some(stat(50))),
+            ("unrecorded", None),
+            ("gone-from-db", Some(stat(8))),
+            ("only-in-db", Some(stat(9))),
+        ]
+        .into_iter()
+        .map(|(name, stat)| (format!("/music/{}.mp3", name), stat))
+        .collect();
+
+        let plan = classify_scan(Path::new("/music"), found, &library, stored);
+        assert_eq!(
+            plan,
+            ScanPlan {
+                added: vec!["/music/new.mp3".to_string(), "/music/dropped-edited.mp3".to_string()],
+                restored: vec!["/music/dropped.mp3".to_string()],
+                changed: vec![
+                    "/music/edited.mp3".to_string(),
+                    "/music/unrecorded.mp3".to_string(),
+                    "/music/not-in-db.mp3".to_string()
+                ],
+                unchanged: 1,
+                missing: vec![
+                    "/music/gone-from-db.mp3".to_string(),
+                    "/music/gone.mp3".to_string(),
+                    "/music/only-in-db.mp3".to_string()
+                ],
+            }
+        );
+    }
+
+    #[test]
+    fn local_storage_is_imported_once() {
+        let mut db = memory_db();
+        let songs = json!([
+            {"path": "/a.mp3", "name": "a.mp3", "rating": 3},
+            {"path": "/b.flac", "name": "b.flac", "isFavorite": false}
+        ]);
+        let data = json!({
+            // Items come either as the stored string or already parsed
+            "musicPlayerPlaylist": songs.to_string(),
+            "musicPlayerRatings": {"/a.mp3": 5, "/gone.mp3": 2},
+            "musicPlayerFavorites": "{\"/b.flac\": true}",
+            "musicPlayerHistory": [
+                {"song": {"path": "/a.mp3", "name": "a.mp3"}, "playedAt": "2025-01-01T00:00:00.000Z", "playCount": 4}
+            ],
+            "musicPlayerSmartPlaylists": [{"id": "top", "rules": []}]
+        });
+
+        let report = db.migrate_local_storage(&data).unwrap();
+        assert_eq!(
+            (report.tracks, report.ratings, report.favorites, report.play_events, report.smart_playlists, report.skipped),
+            (2, 1, 1, 1, 1, 1)
+        );
+        let tracks = db.tracks().unwrap();
+        assert_eq!(tracks.iter().map(|t| t.path.as_str()).collect::<Vec<_>>(), ["/a.mp3", "/b.flac"]);
+        // The separate maps win over the copies on the songs
+        assert_eq!(tracks[0].rating, 5);
+        assert!(tracks[1].favorite);
+        assert_eq!(db.play_history(None).unwrap()[0].play_count, 4);
+        assert_eq!(db.smart_playlists().unwrap(), [json!({"id": "top", "rules": []})]);
+
+        assert!(db.migrate_local_storage(&data).is_err());
+        assert_eq!(db.tracks().unwrap().len(), 2);
+    }
+}
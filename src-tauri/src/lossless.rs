@@ -0,0 +1,274 @@
+use crate::decode;
+use realfft::RealFftPlanner;
+use std::f32::consts::PI;
+
+/// STFT length for the averaged spectrum; about 10 Hz resolution at 44.1 kHz.
+const FFT_SIZE: usize = 4096;
+
+/// Width of the frequency slices the averaged spectrum is reduced to.
+const SLICE_HZ: f32 = 250.0;
+
+/// Drop over the last kilohertz below the cutoff that marks an encoder lowpass
+/// rather than the gradual roll-off of real recordings.
+const CLIFF_DB: f32 = 25.0;
+
+/// Tracks whose midrange stays below this level are treated as silence.
+const SILENCE_DB: f32 = -90.0;
+
+/// Highest lowpass typical of lossy encoders (LAME at 320 kbps stops around 20 kHz).
+const LOSSY_CUTOFF_HZ: f32 = 20500.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Verdict {
+    Genuine,
+    /// Lossy source re-encoded to a lossless format.
+    LossyTranscode,
+    /// 44.1 or 48 kHz material resampled to a higher rate.
+    Upsampled,
+    /// 16-bit audio stored with more bits, the extra ones all zero.
+    PaddedBitDepth,
+    /// Too quiet or too short to tell.
+    Inconclusive,
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct LosslessReport {
+    pub path: String,
+    pub verdict: Verdict,
+    /// How sure the verdict is, from 0 to 1.
+    pub confidence: f32,
+    pub sample_rate: u32,
+    pub bits_per_sample: Option<u32>,
+    /// Bits actually used by the samples, for 17 to 24-bit files.
+    pub effective_bits: Option<u32>,
+    /// Frequency of the lowpass found, above which the spectrum holds only noise.
+    pub cutoff_hz: Option<f32>,
+    pub notes: Vec<String>,
+}
+
+/// Averaged spectrum of the track in `SLICE_HZ` slices, in dB.
+struct SpectrumSummary {
+    slices: Vec<f32>,
+    slice_hz: f32,
+}
+
+impl SpectrumSummary {
+    fn level(&self, frequency: f32) -> f32 {
+        let index = ((frequency / self.slice_hz) as usize).min(self.slices.len() - 1);
+        self.slices[index]
+    }
+}
+
+/// Inspects a file for the traces lossy encoding, upsampling or bit-depth
+/// padding leave behind.
+pub fn analyze(path: &str) -> Result<LosslessReport, String> {
+    let fft = RealFftPlanner::<f32>::new().plan_fft_forward(FFT_SIZE);
+    let window: Vec<f32> = (0..FFT_SIZE).map(|i| 0.5 - 0.5 * (2.0 * PI * i as f32 / FFT_SIZE as f32).cos()).collect();
+    let mut input = fft.make_input_vec();
+    let mut spectrum = fft.make_output_vec();
+    let mut scratch = fft.make_scratch_vec();
+    let mut power = vec![0f64; FFT_SIZE / 2 + 1];
+    let mut windows = 0u64;
+    let mut filled = 0usize;
+    let mut used_bits = 0i64;
+
+    let info = decode::decode_interleaved(path, |info, samples| {
+        let scale = info.bits_per_sample.filter(|bits| (17..=24).contains(bits)).map(|bits| (1i64 << (bits - 1)) as f32);
+
+        for frame in samples.chunks_exact(info.channels) {
+            if let Some(scale) = scale {
+                for sample in frame {
+                    used_bits |= (sample * scale).round() as i64;
+                }
+            }
+
+            input[filled] = frame.iter().sum::<f32>() / info.channels as f32 * window[filled];
+            filled += 1;
+            if filled == FFT_SIZE {
+                let _ = fft.process_with_scratch(&mut input, &mut spectrum, &mut scratch);
+                for (total, bin) in power.iter_mut().zip(&spectrum) {
+                    *total += bin.norm_sqr() as f64;
+                }
+                windows += 1;
+                filled = 0;
+            }
+        }
+        true
+    })?;
+
+    let mut report = LosslessReport {
+        path: path.to_string(),
+        verdict: Verdict::Inconclusive,
+        confidence: 0.0,
+        sample_rate: info.sample_rate,
+        bits_per_sample: info.bits_per_sample,
+        effective_bits: None,
+        cutoff_hz: None,
+        notes: Vec::new(),
+    };
+
+    if windows == 0 {
+        report.notes.push("Track is too short to analyze".to_string());
+        return Ok(report);
+    }
+
+    let summary = summarize(&power, windows, info.sample_rate as f32);
+    judge_spectrum(&summary, info.sample_rate as f32, &mut report);
+
+    // Padding is exact: if no sample ever sets the low bits, they were never there
+    if let Some(bits) = info.bits_per_sample.filter(|bits| (17..=24).contains(bits)) {
+        if used_bits != 0 {
+            let effective = bits - used_bits.trailing_zeros().min(bits);
+            report.effective_bits = Some(effective);
+            if effective <= 16 {
+                report.notes.push(format!("Only {} of {} bits are used", effective, bits));
+                if report.verdict == Verdict::Genuine {
+                    report.verdict = Verdict::PaddedBitDepth;
+                    report.confidence = 0.99;
+                }
+            }
+        }
+    }
+
+    Ok(report)
+}
+
+fn summarize(power: &[f64], windows: u64, sample_rate: f32) -> SpectrumSummary {
+    let bin_hz = sample_rate / FFT_SIZE as f32;
+    let bins_per_slice = ((SLICE_HZ / bin_hz).round() as usize).max(1);
+    let window_gain = (FFT_SIZE as f64 / 2.0).powi(2);
+
+    let slices = power
+        .chunks(bins_per_slice)
+        .map(|bins| {
+            let mean = bins.iter().sum::<f64>() / bins.len() as f64 / windows as f64 / window_gain;
+            (10.0 * mean.max(1e-20).log10()) as f32
+        })
+        .collect();
+
+    SpectrumSummary {
+        slices,
+        slice_hz: bins_per_slice as f32 * bin_hz,
+    }
+}
+
+fn judge_spectrum(summary: &SpectrumSummary, sample_rate: f32, report: &mut LosslessReport) {
+    let nyquist = sample_rate / 2.0;
+    let slices = &summary.slices;
+
+    let reference = summary.level(1000.0).max(summary.level(4000.0));
+    if reference < SILENCE_DB {
+        report.notes.push("Track is silent".to_string());
+        return;
+    }
+
+    // Find the steepest edge above 10 kHz: the level a kilohertz below it
+    // against the loudest slice anywhere above it
+    let below = (1000.0 / summary.slice_hz).round() as usize;
+    let first = ((10000.0 / summary.slice_hz) as usize).max(below);
+    let drops: Vec<(usize, f32)> = (first..slices.len().saturating_sub(1))
+        .map(|edge| {
+            let above = slices[edge + 1..].iter().copied().fold(f32::MIN, f32::max);
+            (edge, slices[edge - below] - above)
+        })
+        .collect();
+
+    // Edges up to a kilohertz past the real one still compare content against
+    // noise, so take the lowest edge that comes close to the steepest drop
+    let steepest = drops.iter().map(|(_, drop)| *drop).fold(f32::MIN, f32::max);
+    let cliff = drops.iter().copied().find(|(_, drop)| *drop >= steepest - 3.0);
+
+    let confidence = |drop: f32| (0.6 + (drop - CLIFF_DB) / 75.0).clamp(0.6, 0.99);
+
+    match cliff {
+        Some((edge, drop)) if drop >= CLIFF_DB && ((edge + 1) as f32 * summary.slice_hz) < nyquist - 1000.0 => {
+            let cutoff = (edge + 1) as f32 * summary.slice_hz;
+            report.cutoff_hz = Some(cutoff);
+            report.notes.push(format!("Sharp lowpass at {:.1} kHz ({:.0} dB drop)", cutoff / 1000.0, drop));
+
+            if sample_rate > 48000.0 && (19000.0..=24500.0).contains(&cutoff) {
+                report.verdict = Verdict::Upsampled;
+                report.confidence = confidence(drop);
+                report.notes.push("No content above the range of a 44.1/48 kHz source".to_string());
+            } else if cutoff <= LOSSY_CUTOFF_HZ {
+                report.verdict = Verdict::LossyTranscode;
+                report.confidence = confidence(drop);
+            } else {
+                // A lowpass this high is more likely a mastering choice than an encoder
+                report.verdict = Verdict::Genuine;
+                report.confidence = 0.6;
+            }
+        }
+        Some((_, drop)) => {
+            report.verdict = Verdict::Genuine;
+            report.confidence = if drop < CLIFF_DB / 2.0 { 0.9 } else { 0.75 };
+        }
+        None => report.notes.push("Sample rate too low to judge".to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::write_wav_bits;
+
+    /// Deterministic white noise in [-amplitude, amplitude).
+    fn noise(frames: usize, amplitude: f32) -> Vec<f32> {
+        let mut state = 0x2545_f491u32;
+        (0..frames)
+            .map(|_| {
+                state = state.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+                amplitude * ((state >> 8) as f32 / (1 << 24) as f32 * 2.0 - 1.0)
+            })
+            .collect()
+    }
+
+    fn analyze_wav(name: &str, sample_rate: u32, bits: u16, samples: &[f32]) -> LosslessReport {
+        let path = write_wav_bits(name, sample_rate, 1, bits, samples);
+        let report = analyze(path.to_str().unwrap());
+        std::fs::remove_file(&path).unwrap();
+        report.unwrap()
+    }
+
+    #[test]
+    fn lowpassed_audio_is_a_lossy_transcode() {
+        // Sines every 100 Hz up to 16 kHz with scattered phases, like an encoder's lowpassed output
+        let samples: Vec<f32> = (0..44100)
+            .map(|i| {
+                let t = i as f32 / 44100.0;
+                (1..=160).map(|k| 0.004 * (2.0 * PI * 100.0 * k as f32 * t + k as f32 * 2.4).sin()).sum()
+            })
+            .collect();
+
+        let report = analyze_wav("lossless-lowpass", 44100, 16, &samples);
+        assert_eq!(report.verdict, Verdict::LossyTranscode, "{:?}", report);
+        let cutoff = report.cutoff_hz.unwrap();
+        assert!((16000.0..=16250.0).contains(&cutoff), "{}", cutoff);
+    }
+
+    #[test]
+    fn full_band_noise_is_genuine() {
+        let report = analyze_wav("lossless-genuine", 44100, 24, &noise(44100, 0.5));
+        assert_eq!(report.verdict, Verdict::Genuine, "{:?}", report);
+        assert_eq!(report.cutoff_hz, None);
+        assert_eq!(report.bits_per_sample, Some(24));
+        assert_eq!(report.effective_bits, Some(24));
+    }
+
+    #[test]
+    fn padded_16_bit_audio_is_flagged() {
+        // Quantize to 16 bits, then store with 24
+        let samples: Vec<f32> = noise(44100, 0.5).iter().map(|s| (s * 32768.0).round() / 32768.0).collect();
+
+        let report = analyze_wav("lossless-padded", 44100, 24, &samples);
+        assert_eq!(report.verdict, Verdict::PaddedBitDepth, "{:?}", report);
+        assert_eq!(report.effective_bits, Some(16));
+    }
+
+    #[test]
+    fn short_tracks_are_inconclusive() {
+        let report = analyze_wav("lossless-short", 44100, 16, &noise(FFT_SIZE - 1, 0.5));
+        assert_eq!(report.verdict, Verdict::Inconclusive);
+    }
+}
@@ -0,0 +1,189 @@
+use crate::decode;
+use realfft::{RealFftPlanner, RealToComplex};
+use std::f32::consts::PI;
+use std::sync::Arc;
+
+/// Largest spacing of the STFT bins; fine enough to separate semitones down to `MIN_FREQUENCY`.
+const BIN_HZ: f32 = 1.5;
+
+/// Range of the spectrum folded into the chromagram, roughly A1 to C7.
+const MIN_FREQUENCY: f32 = 55.0;
+const MAX_FREQUENCY: f32 = 2100.0;
+
+/// Windows whose strongest pitch class has less power than this (about
+/// -75 dBFS) are skipped.
+const SILENCE: f32 = 1e-8;
+
+/// Krumhansl-Kessler key profiles, starting on the tonic.
+const MAJOR_PROFILE: [f32; 12] = [6.35, 2.23, 3.48, 2.33, 4.38, 4.09, 2.52, 5.19, 2.39, 3.66, 2.29, 2.88];
+const MINOR_PROFILE: [f32; 12] = [6.33, 2.68, 3.52, 5.38, 2.60, 3.53, 2.54, 4.75, 3.98, 2.69, 3.34, 3.17];
+
+/// Pitch class names as DJ software spells them, starting on C.
+const PITCH_NAMES: [&str; 12] = ["C", "Db", "D", "Eb", "E", "F", "F#", "G", "Ab", "A", "Bb", "B"];
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct KeyEstimate {
+    /// Short standard notation, such as "F#m" or "Eb"; the form written to tags.
+    pub key: String,
+    /// Position on the Camelot wheel, such as "11A".
+    pub camelot: String,
+    /// Correlation of the chromagram with the key's profile, from 0 to 1.
+    pub confidence: f32,
+}
+
+impl KeyEstimate {
+    fn new(tonic: usize, minor: bool, confidence: f32) -> Self {
+        // The wheel steps by fifths; a minor key shares the number of its relative major
+        let major = if minor { (tonic + 3) % 12 } else { tonic };
+        let number = (major * 7 + 7) % 12 + 1;
+        Self {
+            key: format!("{}{}", PITCH_NAMES[tonic], if minor { "m" } else { "" }),
+            camelot: format!("{}{}", number, if minor { 'A' } else { 'B' }),
+            confidence,
+        }
+    }
+}
+
+/// Folds half-overlapping STFT windows of a mono signal into pitch classes.
+struct ChromaAnalyzer {
+    fft: Arc<dyn RealToComplex<f32>>,
+    window: Vec<f32>,
+    input: Vec<f32>,
+    spectrum: Vec<realfft::num_complex::Complex<f32>>,
+    scratch: Vec<realfft::num_complex::Complex<f32>>,
+    /// Pitch class of each FFT bin, for bins inside the analyzed range.
+    classes: Vec<Option<usize>>,
+    buffer: Vec<f32>,
+}
+
+impl ChromaAnalyzer {
+    fn new(sample_rate: u32) -> Self {
+        let size = (sample_rate as f32 / BIN_HZ) as usize;
+        let size = size.next_power_of_two();
+        let fft = RealFftPlanner::<f32>::new().plan_fft_forward(size);
+        let bin_hz = sample_rate as f32 / size as f32;
+        let window_gain = size as f32 / 2.0;
+
+        let classes = (0..=size / 2)
+            .map(|bin| {
+                let frequency = bin as f32 * bin_hz;
+                (MIN_FREQUENCY..=MAX_FREQUENCY).contains(&frequency).then(|| {
+                    let midi = 69.0 + 12.0 * (frequency / 440.0).log2();
+                    (midi.round() as i32).rem_euclid(12) as usize
+                })
+            })
+            .collect();
+
+        Self {
+            window: (0..size).map(|i| (0.5 - 0.5 * (2.0 * PI * i as f32 / size as f32).cos()) / window_gain).collect(),
+            input: fft.make_input_vec(),
+            spectrum: fft.make_output_vec(),
+            scratch: fft.make_scratch_vec(),
+            classes,
+            buffer: Vec::with_capacity(size),
+            fft,
+        }
+    }
+
+    /// Adds a sample; returns the normalized chroma of each window that
+    /// completes, unless it was silent.
+    fn push(&mut self, sample: f32) -> Option<[f32; 12]> {
+        self.buffer.push(sample);
+        if self.buffer.len() < self.window.len() {
+            return None;
+        }
+
+        for ((input, sample), weight) in self.input.iter_mut().zip(&self.buffer).zip(&self.window) {
+            *input = sample * weight;
+        }
+        self.buffer.drain(..self.window.len() / 2);
+        let _ = self.fft.process_with_scratch(&mut self.input, &mut self.spectrum, &mut self.scratch);
+
+        let mut chroma = [0.0f32; 12];
+        for (bin, class) in self.spectrum.iter().zip(&self.classes) {
+            if let Some(class) = class {
+                chroma[*class] += bin.norm_sqr();
+            }
+        }
+
+        // Each window counts the same, so loud passages don't outweigh quiet ones
+        let peak = chroma.iter().copied().fold(0.0, f32::max);
+        if peak < SILENCE {
+            return None;
+        }
+        chroma.iter_mut().for_each(|value| *value /= peak);
+        Some(chroma)
+    }
+}
+
+/// Pearson correlation of `chroma` with `profile` transposed to `tonic`.
+fn correlate(chroma: &[f32; 12], profile: &[f32; 12], tonic: usize) -> f32 {
+    let chroma_mean = chroma.iter().sum::<f32>() / 12.0;
+    let profile_mean = profile.iter().sum::<f32>() / 12.0;
+    let (mut product, mut chroma_power, mut profile_power) = (0.0, 0.0, 0.0);
+    for (pitch, value) in chroma.iter().enumerate() {
+        let a = value - chroma_mean;
+        let b = profile[(pitch + 12 - tonic) % 12] - profile_mean;
+        product += a * b;
+        chroma_power += a * a;
+        profile_power += b * b;
+    }
+    if chroma_power <= 0.0 {
+        0.0
+    } else {
+        product / (chroma_power * profile_power).sqrt()
+    }
+}
+
+/// Estimates the key of `path` by matching its average chromagram against
+/// the major and minor key profiles in all twelve transpositions.
+pub fn detect_key(path: &str) -> Result<KeyEstimate, String> {
+    let mut chroma = [0.0f32; 12];
+    let mut windows = 0usize;
+    let mut analyzer: Option<ChromaAnalyzer> = None;
+
+    decode::decode_interleaved(path, |info, samples| {
+        let analyzer = analyzer.get_or_insert_with(|| ChromaAnalyzer::new(info.sample_rate));
+        for frame in samples.chunks_exact(info.channels) {
+            if let Some(window) = analyzer.push(frame.iter().sum::<f32>() / info.channels as f32) {
+                for (total, value) in chroma.iter_mut().zip(window) {
+                    *total += value;
+                }
+                windows += 1;
+            }
+        }
+        true
+    })?;
+
+    if windows == 0 {
+        return Err("No tonal content found".to_string());
+    }
+
+    (0..12)
+        .flat_map(|tonic| {
+            [
+                (tonic, false, correlate(&chroma, &MAJOR_PROFILE, tonic)),
+                (tonic, true, correlate(&chroma, &MINOR_PROFILE, tonic)),
+            ]
+        })
+        .max_by(|a, b| a.2.total_cmp(&b.2))
+        .map(|(tonic, minor, correlation)| KeyEstimate::new(tonic, minor, correlation.clamp(0.0, 1.0)))
+        .ok_or_else(|| "No tonal content found".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn camelot_wheel_follows_the_circle_of_fifths() {
+        let majors = ["8B", "3B", "10B", "5B", "12B", "7B", "2B", "9B", "4B", "11B", "6B", "1B"];
+        let minors = ["5A", "12A", "7A", "2A", "9A", "4A", "11A", "6A", "1A", "8A", "3A", "10A"];
+        for tonic in 0..12 {
+            assert_eq!(KeyEstimate::new(tonic, false, 1.0).camelot, majors[tonic], "{}", PITCH_NAMES[tonic]);
+            assert_eq!(KeyEstimate::new(tonic, true, 1.0).camelot, minors[tonic], "{}m", PITCH_NAMES[tonic]);
+        }
+        assert_eq!(KeyEstimate::new(6, true, 1.0).key, "F#m");
+        assert_eq!(KeyEstimate::new(3, false, 1.0).key, "Eb");
+    }
+}
@@ -0,0 +1,65 @@
+use crate::audio_new::VolumeCurve;
+use crate::dsp::{ChannelMixerParams, CompressorParams, ConvolverParams, CrossfeedParams, LimiterParams, LoudnessParams, SpectrumSettings};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+
+/// Playback settings owned by the backend, as opposed to the UI state kept in
+/// localStorage.
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    pub volume_curve: VolumeCurve,
+    pub channels: ChannelMixerParams,
+    /// Crossfeed settings per output device name; devices not listed play without crossfeed.
+    pub crossfeed: BTreeMap<String, CrossfeedParams>,
+    /// `None` leaves the compressor bypassed.
+    pub compressor: Option<CompressorParams>,
+    pub limiter: LimiterParams,
+    /// `None` leaves loudness compensation off.
+    pub loudness: Option<LoudnessParams>,
+    /// `None` leaves the convolver out of the chain.
+    pub convolver: Option<ConvolverParams>,
+    pub spectrum: SpectrumSettings,
+}
+
+/// Settings persisted as JSON in the app config directory.
+#[derive(Default)]
+pub struct SettingsStore {
+    path: Option<PathBuf>,
+    settings: Settings,
+}
+
+impl SettingsStore {
+    pub fn load(&mut self, path: PathBuf) {
+        if let Ok(content) = fs::read_to_string(&path) {
+            match serde_json::from_str(&content) {
+                Ok(settings) => self.settings = settings,
+                Err(e) => eprintln!("Failed to read settings: {}", e),
+            }
+        }
+        self.path = Some(path);
+    }
+
+    pub fn get(&self) -> &Settings {
+        &self.settings
+    }
+
+    /// Applies `change` and writes the result to disk.
+    pub fn update<R>(&mut self, change: impl FnOnce(&mut Settings) -> R) -> Result<R, String> {
+        let result = change(&mut self.settings);
+        self.save()?;
+        Ok(result)
+    }
+
+    fn save(&self) -> Result<(), String> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).map_err(|e| format!("Failed to create settings directory: {}", e))?;
+        }
+        let content = serde_json::to_string_pretty(&self.settings).map_err(|e| format!("Failed to serialize settings: {}", e))?;
+        fs::write(path, content).map_err(|e| format!("Failed to save settings: {}", e))
+    }
+}
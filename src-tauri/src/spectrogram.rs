@@ -0,0 +1,302 @@
+use crate::decode;
+use base64::{engine::general_purpose, Engine as _};
+use realfft::RealFftPlanner;
+use std::f32::consts::PI;
+use std::fs;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ColorMap {
+    Magma,
+    Inferno,
+    Viridis,
+    Grayscale,
+}
+
+impl ColorMap {
+    /// Evenly spaced gradient stops, approximating the matplotlib color maps.
+    fn stops(self) -> &'static [[u8; 3]] {
+        match self {
+            ColorMap::Magma => &[
+                [0, 0, 4], [28, 16, 68], [79, 18, 123], [129, 37, 129], [181, 54, 122],
+                [229, 80, 100], [251, 135, 97], [254, 194, 135], [252, 253, 191],
+            ],
+            ColorMap::Inferno => &[
+                [0, 0, 4], [31, 12, 72], [85, 15, 109], [136, 34, 106], [186, 54, 85],
+                [227, 89, 51], [249, 140, 10], [249, 201, 50], [252, 255, 164],
+            ],
+            ColorMap::Viridis => &[
+                [68, 1, 84], [72, 40, 120], [62, 74, 137], [49, 104, 142], [38, 130, 142],
+                [31, 158, 137], [53, 183, 121], [109, 205, 89], [180, 222, 44], [253, 231, 37],
+            ],
+            ColorMap::Grayscale => &[[0, 0, 0], [255, 255, 255]],
+        }
+    }
+
+    /// Color for `value` between 0 and 1.
+    fn color(self, value: f32) -> [u8; 3] {
+        let stops = self.stops();
+        let position = value.clamp(0.0, 1.0) * (stops.len() - 1) as f32;
+        let index = (position as usize).min(stops.len() - 2);
+        let fraction = position - index as f32;
+
+        let mut color = [0u8; 3];
+        for (channel, value) in color.iter_mut().enumerate() {
+            let from = stops[index][channel] as f32;
+            let to = stops[index + 1][channel] as f32;
+            *value = (from + (to - from) * fraction).round() as u8;
+        }
+        color
+    }
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct SpectrogramOptions {
+    pub width: u32,
+    pub height: u32,
+    /// STFT window length in samples; a power of two.
+    pub fft_size: usize,
+    /// Logarithmic frequency axis starting at `min_frequency`, instead of a linear one from 0 Hz.
+    pub log_frequency: bool,
+    pub min_frequency: f32,
+    /// Level shown as the darkest color; louder content fills the rest of the map up to 0 dBFS.
+    pub floor_db: f32,
+    pub color_map: ColorMap,
+}
+
+impl Default for SpectrogramOptions {
+    fn default() -> Self {
+        Self {
+            width: 1200,
+            height: 600,
+            fft_size: 4096,
+            log_frequency: false,
+            min_frequency: 20.0,
+            floor_db: -120.0,
+            color_map: ColorMap::Magma,
+        }
+    }
+}
+
+impl SpectrogramOptions {
+    fn validate(&self) -> Result<(), String> {
+        if !(16..=8192).contains(&self.width) || !(16..=4096).contains(&self.height) {
+            return Err("Spectrogram size must be between 16x16 and 8192x4096".to_string());
+        }
+        if !self.fft_size.is_power_of_two() || !(256..=32768).contains(&self.fft_size) {
+            return Err("FFT size must be a power of two between 256 and 32768".to_string());
+        }
+        if self.log_frequency && self.min_frequency <= 0.0 {
+            return Err("Minimum frequency must be positive".to_string());
+        }
+        if self.floor_db >= 0.0 {
+            return Err("Floor must be below 0 dBFS".to_string());
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct Spectrogram {
+    pub width: u32,
+    pub height: u32,
+    pub sample_rate: u32,
+    pub duration: f64,
+    /// Base64-encoded PNG, left out when the image was written to a file.
+    pub data: Option<String>,
+}
+
+/// Range of FFT bins covered by each image row, top row first.
+fn row_bins(options: &SpectrogramOptions, sample_rate: f32) -> Vec<(usize, usize)> {
+    let nyquist = sample_rate / 2.0;
+    let bin_width = sample_rate / options.fft_size as f32;
+    let last_bin = options.fft_size / 2;
+    let height = options.height as f32;
+
+    let frequency = |position: f32| {
+        if options.log_frequency {
+            let low = options.min_frequency.min(nyquist / 2.0);
+            low * (nyquist / low).powf(position)
+        } else {
+            nyquist * position
+        }
+    };
+
+    (0..options.height)
+        .map(|row| {
+            // Rows run from the top of the image, so the highest frequency comes first
+            let top = 1.0 - row as f32 / height;
+            let bottom = 1.0 - (row + 1) as f32 / height;
+            let low = ((frequency(bottom) / bin_width).round() as usize).min(last_bin);
+            let high = ((frequency(top) / bin_width).round() as usize).clamp(low, last_bin);
+            (low, high)
+        })
+        .collect()
+}
+
+/// Renders the STFT of a whole track as a PNG. The track is streamed through
+/// the decoder, so memory use doesn't grow with its length.
+pub fn render_spectrogram(path: &str, options: &SpectrogramOptions) -> Result<(Spectrogram, Vec<u8>), String> {
+    options.validate()?;
+
+    // Column positions depend on the track length; count the frames first when the container doesn't say
+    let frames = match decode::open_track(path)?.1.codec_params().n_frames {
+        Some(frames) if frames > 0 => frames,
+        _ => {
+            let mut frames = 0u64;
+            decode::decode_interleaved(path, |info, samples| {
+                frames += (samples.len() / info.channels) as u64;
+                true
+            })?;
+            frames.max(1)
+        }
+    };
+
+    let width = options.width as usize;
+    let height = options.height as usize;
+    let size = options.fft_size;
+    let fft = RealFftPlanner::<f32>::new().plan_fft_forward(size);
+    let window: Vec<f32> = (0..size).map(|i| 0.5 - 0.5 * (2.0 * PI * i as f32 / size as f32).cos()).collect();
+    let window_gain = window.iter().sum::<f32>() / 2.0;
+    let mut input = fft.make_input_vec();
+    let mut spectrum = fft.make_output_vec();
+    let mut scratch = fft.make_scratch_vec();
+
+    // Column c analyzes the window starting at frame start(c), spreading the windows over the track
+    let span = frames.saturating_sub(size as u64);
+    let start = |column: usize| if width > 1 { span * column as u64 / (width - 1) as u64 } else { 0 };
+
+    let mut history = vec![0.0f32; size];
+    let mut position = 0u64;
+    let mut column = 0usize;
+    let mut rows: Option<Vec<(usize, usize)>> = None;
+    let mut levels = vec![0.0f32; width * height];
+
+    let mut push = |sample: f32, sample_rate: u32| {
+        history[(position % size as u64) as usize] = sample;
+        position += 1;
+
+        while column < width && start(column) + size as u64 == position {
+            let offset = (position % size as u64) as usize;
+            for (i, value) in input.iter_mut().enumerate() {
+                *value = history[(offset + i) % size] * window[i];
+            }
+            let _ = fft.process_with_scratch(&mut input, &mut spectrum, &mut scratch);
+
+            let rows = rows.get_or_insert_with(|| row_bins(options, sample_rate as f32));
+            for (row, (low, high)) in rows.iter().enumerate() {
+                let magnitude = spectrum[*low..=*high].iter().map(|bin| bin.norm()).fold(0.0, f32::max);
+                let db = 20.0 * (magnitude / window_gain).max(1e-9).log10();
+                levels[row * width + column] = 1.0 - db / options.floor_db;
+            }
+            column += 1;
+        }
+        column == width
+    };
+
+    let info = decode::decode_interleaved(path, |info, samples| {
+        for frame in samples.chunks_exact(info.channels) {
+            push(frame.iter().sum::<f32>() / info.channels as f32, info.sample_rate);
+        }
+        true
+    })?;
+    // Pad with silence so windows reaching past the end (or a short track) still get analyzed.
+    // One window is enough; columns past that, where a header overstated the length, stay blank.
+    for _ in 0..size {
+        if push(0.0, info.sample_rate) {
+            break;
+        }
+    }
+
+    let mut pixels = Vec::with_capacity(width * height * 3);
+    for level in &levels {
+        pixels.extend_from_slice(&options.color_map.color(*level));
+    }
+
+    let mut png_data = Vec::new();
+    {
+        let mut encoder = png::Encoder::new(&mut png_data, options.width, options.height);
+        encoder.set_color(png::ColorType::Rgb);
+        encoder.set_depth(png::BitDepth::Eight);
+        let mut writer = encoder.write_header().map_err(|e| format!("Failed to encode image: {}", e))?;
+        writer.write_image_data(&pixels).map_err(|e| format!("Failed to encode image: {}", e))?;
+    }
+
+    let spectrogram = Spectrogram {
+        width: options.width,
+        height: options.height,
+        sample_rate: info.sample_rate,
+        duration: frames as f64 / info.sample_rate as f64,
+        data: None,
+    };
+    Ok((spectrogram, png_data))
+}
+
+/// Renders a spectrogram and writes it to `output_path`, or returns it base64-encoded when no path is given.
+pub fn export_spectrogram(path: &str, options: &SpectrogramOptions, output_path: Option<&str>) -> Result<Spectrogram, String> {
+    let (mut spectrogram, png_data) = render_spectrogram(path, options)?;
+    match output_path {
+        Some(output_path) => fs::write(output_path, png_data).map_err(|e| format!("Failed to save spectrogram: {}", e))?,
+        None => spectrogram.data = Some(general_purpose::STANDARD.encode(png_data)),
+    }
+    Ok(spectrogram)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{sine, write_wav};
+    use std::fs::OpenOptions;
+    use std::io::{Seek, SeekFrom, Write};
+
+    fn options() -> SpectrogramOptions {
+        SpectrogramOptions {
+            width: 32,
+            height: 64,
+            fft_size: 1024,
+            color_map: ColorMap::Grayscale,
+            ..Default::default()
+        }
+    }
+
+    /// Grayscale brightness of every pixel, row by row.
+    fn brightness(png_data: &[u8]) -> Vec<u8> {
+        let mut reader = png::Decoder::new(png_data).read_info().unwrap();
+        let mut pixels = vec![0; reader.output_buffer_size()];
+        reader.next_frame(&mut pixels).unwrap();
+        pixels.chunks_exact(3).map(|pixel| pixel[0]).collect()
+    }
+
+    #[test]
+    fn sine_shows_up_in_its_row() {
+        let path = write_wav("spectrogram-sine", 48000, 1, &sine(6000.0, 48000, 1.0, 0.5));
+        let (spectrogram, png_data) = render_spectrogram(path.to_str().unwrap(), &options()).unwrap();
+        assert_eq!((spectrogram.width, spectrogram.height), (32, 64));
+        assert!((spectrogram.duration - 1.0).abs() < 1e-6);
+
+        // 6 kHz is a quarter of Nyquist, so three quarters down the linear axis
+        let pixels = brightness(&png_data);
+        let column: Vec<u8> = (0..64).map(|row| pixels[row * 32 + 16]).collect();
+        let brightest = (0..64).max_by_key(|row| column[*row]).unwrap();
+        assert!(brightest.abs_diff(48) <= 1, "brightest row {}", brightest);
+        assert!(column[10] < column[brightest] / 2);
+    }
+
+    #[test]
+    fn overstated_length_pads_only_one_window() {
+        let path = write_wav("spectrogram-truncated", 48000, 1, &sine(1000.0, 48000, 0.5, 0.5));
+        // Claim about 2 GB of audio in the RIFF and data chunk headers
+        let mut file = OpenOptions::new().write(true).open(&path).unwrap();
+        file.seek(SeekFrom::Start(4)).unwrap();
+        file.write_all(&(0x7FFF_0000u32 + 36).to_le_bytes()).unwrap();
+        file.seek(SeekFrom::Start(40)).unwrap();
+        file.write_all(&0x7FFF_0000u32.to_le_bytes()).unwrap();
+        drop(file);
+
+        let (_, png_data) = render_spectrogram(path.to_str().unwrap(), &options()).unwrap();
+        let pixels = brightness(&png_data);
+        assert!(pixels.chunks(32).any(|row| row[0] > 0), "the decoded part is analyzed");
+        assert!(pixels.chunks(32).all(|row| row[31] == 0), "last column should stay blank");
+    }
+}
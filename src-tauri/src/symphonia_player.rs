@@ -1,16 +1,23 @@
+use crate::decode;
+use crate::dsp::{
+    AudioEffect, ChannelMixer, ChannelMixerParams, Compressor, CompressorParams, Convolver, ConvolverParams, Crossfeed, CrossfeedParams, EffectChain,
+    Karaoke, KaraokeParams, Limiter, LimiterParams, Loudness, LoudnessParams, ParametricEq, ParametricEqParams,
+    ChannelLevels, LevelMeter, SpectrumAnalyzer, SpectrumFrame, SpectrumSettings, StereoWidth, StereoWidthParams,
+};
 use cpal::{
     traits::{DeviceTrait, HostTrait, StreamTrait},
     Device,
 };
-use ringbuf::HeapRb;
+use ringbuf::{HeapConsumer, HeapProducer, HeapRb};
+use std::f32::consts::{FRAC_1_SQRT_2, FRAC_PI_2};
 use std::fs::File;
 use std::path::Path;
-use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicUsize, Ordering};
 use std::sync::{Arc, Mutex};
 use std::thread;
 use std::time::{Duration, Instant};
-use symphonia::core::audio::{AudioBufferRef, Signal};
-use symphonia::core::codecs::{Decoder, DecoderOptions, CODEC_TYPE_NULL};
+use symphonia::core::audio::{AudioBufferRef, Channels, SampleBuffer};
+use symphonia::core::codecs::{Decoder, CODEC_TYPE_NULL};
 use symphonia::core::errors::Error;
 use symphonia::core::formats::{FormatOptions, FormatReader, SeekMode, SeekTo};
 use symphonia::core::io::MediaSourceStream;
@@ -18,7 +25,7 @@ use symphonia::core::meta::{MetadataOptions, StandardTagKey};
 use symphonia::core::probe::Hint;
 use symphonia::core::units::Time;
 
-#[derive(Debug, Clone, serde::Serialize)]
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub struct TrackMetadata {
     pub title: Option<String>,
     pub artist: Option<String>,
@@ -31,6 +38,10 @@ pub struct TrackMetadata {
     pub sample_rate: Option<u32>,
     pub channels: Option<String>,
     pub bits_per_sample: Option<u32>,
+    pub bpm: Option<f32>,
+    /// Initial key as tagged, in whatever notation the tagger used.
+    pub key: Option<String>,
+    #[serde(default)]
     pub has_artwork: bool,
 }
 
@@ -40,6 +51,310 @@ pub struct AlbumArtwork {
     pub mime_type: String,
 }
 
+/// Amount of decoded audio buffered ahead of the output callback.
+///
+/// Effects run before the buffer, so this is also how long a parameter change
+/// takes to be heard; a quarter second is enough to ride out scheduling
+/// hiccups because the decode thread blocks while the buffer is full instead
+/// of dropping samples.
+const RING_BUFFER_SECS: f32 = 0.25;
+
+/// Length of the crossfade applied where the loop end joins the loop start.
+const AB_LOOP_CROSSFADE_SECS: f64 = 0.015;
+
+/// Length of the fades applied on pause, resume, stop and seek.
+const OUTPUT_FADE_SECS: f32 = 0.01;
+
+/// Time constant of the per-sample volume smoothing.
+const VOLUME_SMOOTHING_SECS: f32 = 0.02;
+
+/// Amount of output audio the analysis tap can hold before it drops samples.
+const TAP_BUFFER_SECS: f32 = 0.5;
+
+/// Limiter changes queued for the output callback. The stream never pauses, so
+/// the callback drains the queue every buffer and this only has to cover a
+/// burst of slider moves.
+const LIMITER_QUEUE_LEN: usize = 16;
+
+/// Longest the playback thread waits for the output to fade out before flushing anyway.
+const FLUSH_TIMEOUT: Duration = Duration::from_millis(100);
+
+/// Maps the volume slider position (0–1) onto a dB range, so equal slider
+/// steps sound like equal loudness steps.
+#[derive(Debug, Clone, Copy, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct VolumeCurve {
+    pub min_db: f32,
+    pub max_db: f32,
+}
+
+impl Default for VolumeCurve {
+    fn default() -> Self {
+        Self {
+            min_db: -60.0,
+            max_db: 0.0,
+        }
+    }
+}
+
+impl VolumeCurve {
+    fn validate(&self) -> Result<(), String> {
+        if !(-120.0..=12.0).contains(&self.min_db) || !(-120.0..=12.0).contains(&self.max_db) {
+            return Err("Volume range must lie between -120 dB and +12 dB".to_string());
+        }
+        if self.max_db - self.min_db < 6.0 {
+            return Err("Volume range must span at least 6 dB".to_string());
+        }
+        Ok(())
+    }
+
+    /// Linear gain for a slider position; the bottom of the slider is silence.
+    pub fn gain(&self, position: f32) -> f32 {
+        if position <= 0.0 {
+            return 0.0;
+        }
+        let db = self.min_db + (self.max_db - self.min_db) * position.min(1.0);
+        10f32.powf(db / 20.0)
+    }
+}
+
+/// Per-frame output gain: the smoothed volume times the fade applied on
+/// pause, resume, stop and seek.
+struct OutputRamp {
+    fade_step: f32,
+    smoothing: f32,
+    fade: f32,
+    volume: f32,
+}
+
+impl OutputRamp {
+    fn new(sample_rate: f32, volume: f32) -> Self {
+        Self {
+            fade_step: 1.0 / (OUTPUT_FADE_SECS * sample_rate),
+            smoothing: (-1.0 / (VOLUME_SMOOTHING_SECS * sample_rate)).exp(),
+            fade: 0.0,
+            volume,
+        }
+    }
+
+    /// Gain for the next frame, or `None` once fully faded out while muted.
+    fn next(&mut self, target_volume: f32, muted: bool) -> Option<f32> {
+        if muted && self.fade == 0.0 {
+            return None;
+        }
+        self.fade = if muted {
+            (self.fade - self.fade_step).max(0.0)
+        } else {
+            (self.fade + self.fade_step).min(1.0)
+        };
+        self.volume = target_volume + (self.volume - target_volume) * self.smoothing;
+        Some(self.volume * self.fade)
+    }
+
+    /// Drops straight to silence, fading back in on the next frames.
+    fn silence(&mut self) {
+        self.fade = 0.0;
+    }
+
+    fn is_silent(&self) -> bool {
+        self.fade == 0.0
+    }
+}
+
+/// Lock-free controls read by the output callback.
+struct OutputControl {
+    /// Target linear gain, stored as f32 bits.
+    volume: AtomicU32,
+    paused: AtomicBool,
+    /// Set by the playback thread to have the callback fade out and discard
+    /// buffered audio; cleared by the callback once that has happened.
+    flush: AtomicBool,
+}
+
+impl OutputControl {
+    fn new() -> Self {
+        Self {
+            volume: AtomicU32::new(1f32.to_bits()),
+            paused: AtomicBool::new(false),
+            flush: AtomicBool::new(false),
+        }
+    }
+
+    fn volume(&self) -> f32 {
+        f32::from_bits(self.volume.load(Ordering::Relaxed))
+    }
+
+    fn set_volume(&self, gain: f32) {
+        self.volume.store(gain.to_bits(), Ordering::Relaxed);
+    }
+
+    fn is_muted(&self) -> bool {
+        self.paused.load(Ordering::Relaxed) || self.flush.load(Ordering::Relaxed)
+    }
+
+    /// Fades the output out and drops everything still buffered, waiting for
+    /// the callback to confirm so no stale audio plays after a seek or stop.
+    fn flush_and_wait(&self) {
+        self.flush.store(true, Ordering::Release);
+        let started = Instant::now();
+        while self.flush.load(Ordering::Acquire) {
+            if started.elapsed() > FLUSH_TIMEOUT {
+                // The stream isn't running, so there's nothing audible to fade
+                self.flush.store(false, Ordering::Release);
+                break;
+            }
+            thread::sleep(Duration::from_millis(1));
+        }
+    }
+}
+
+/// Limiter settings for the output callback. The callback owns the limiter
+/// itself, so it never waits for a lock; changes reach it through a queue.
+struct LimiterControl {
+    settings: Mutex<LimiterSettings>,
+    /// Delay of the running limiter in frames, published by the callback.
+    latency: AtomicUsize,
+}
+
+struct LimiterSettings {
+    params: LimiterParams,
+    /// Queue to the callback of the running stream.
+    updates: Option<HeapProducer<LimiterParams>>,
+}
+
+impl LimiterControl {
+    fn new() -> Self {
+        Self {
+            settings: Mutex::new(LimiterSettings {
+                params: LimiterParams::default(),
+                updates: None,
+            }),
+            latency: AtomicUsize::new(0),
+        }
+    }
+
+    fn params(&self) -> LimiterParams {
+        self.settings.lock().unwrap().params.clone()
+    }
+
+    fn set_params(&self, params: LimiterParams) {
+        let mut settings = self.settings.lock().unwrap();
+        settings.params = params.clone();
+        if let Some(updates) = settings.updates.as_mut() {
+            let _ = updates.push(params);
+        }
+    }
+
+    /// A limiter for a new output stream, and the queue its callback takes
+    /// changes from.
+    fn attach(&self, sample_rate: f32, channels: usize) -> (Limiter, HeapConsumer<LimiterParams>) {
+        let (producer, consumer) = HeapRb::new(LIMITER_QUEUE_LEN).split();
+        let mut settings = self.settings.lock().unwrap();
+        settings.updates = Some(producer);
+
+        let mut limiter = Limiter::default();
+        limiter.prepare(sample_rate, channels);
+        limiter.set_limiter_params(settings.params.clone());
+        self.latency.store(limiter.latency(), Ordering::Release);
+        (limiter, consumer)
+    }
+
+    fn latency(&self) -> usize {
+        self.latency.load(Ordering::Acquire)
+    }
+}
+
+/// A region of the current track, in seconds, that repeats until cleared.
+#[derive(Debug, Clone, Copy, serde::Serialize, serde::Deserialize)]
+pub struct AbLoop {
+    pub start: f64,
+    pub end: f64,
+}
+
+/// How a decoded packet spanning `[start, end)` track frames meets the end of
+/// an A–B loop region.
+#[derive(Debug, PartialEq)]
+struct LoopBoundary {
+    /// Frames at the start of the packet that play normally.
+    keep: usize,
+    /// Frames after those that are held back for the seam crossfade.
+    held: usize,
+    /// Whether the packet reaches the loop end, so playback jumps back.
+    restart: bool,
+}
+
+impl AbLoop {
+    fn boundary(&self, sample_rate: f64, start: u64, end: u64) -> LoopBoundary {
+        let start_frame = (self.start * sample_rate) as u64;
+        let end_frame = (self.end * sample_rate) as u64;
+        let fade_frames = (AB_LOOP_CROSSFADE_SECS * sample_rate) as u64;
+        let fade_start = end_frame.saturating_sub(fade_frames).max(start_frame);
+
+        let (keep, held) = if end > fade_start && start < end_frame {
+            let keep = fade_start.saturating_sub(start);
+            (keep as usize, (end_frame.min(end) - start - keep) as usize)
+        } else {
+            ((end - start) as usize, 0)
+        };
+        LoopBoundary {
+            keep,
+            held,
+            restart: end >= end_frame,
+        }
+    }
+}
+
+/// Holds back the last few milliseconds of an A–B loop region so they can be
+/// crossfaded into the audio decoded after jumping back to the loop start.
+#[derive(Default)]
+struct LoopSeam {
+    tail: Vec<f32>,
+    pending: Vec<f32>,
+    position: usize,
+}
+
+impl LoopSeam {
+    fn hold(&mut self, samples: &[f32]) {
+        self.tail.extend_from_slice(samples);
+    }
+
+    fn arm(&mut self) {
+        self.pending = std::mem::take(&mut self.tail);
+        self.position = 0;
+    }
+
+    fn clear(&mut self) {
+        self.tail.clear();
+        self.pending.clear();
+        self.position = 0;
+    }
+
+    fn blend(&mut self, samples: &mut [f32], channels: usize) {
+        if self.pending.is_empty() {
+            return;
+        }
+
+        let total = self.pending.len() / channels;
+        for frame in samples.chunks_exact_mut(channels) {
+            if self.position >= total {
+                break;
+            }
+            // Equal-power curve keeps the perceived level constant across the seam
+            let t = (self.position as f32 + 0.5) / total as f32;
+            let (fade_out, fade_in) = ((t * FRAC_PI_2).cos(), (t * FRAC_PI_2).sin());
+            let tail = &self.pending[self.position * channels..(self.position + 1) * channels];
+            for (sample, held) in frame.iter_mut().zip(tail) {
+                *sample = *held * fade_out + *sample * fade_in;
+            }
+            self.position += 1;
+        }
+
+        if self.position >= total {
+            self.pending.clear();
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct PlayerState {
     pub is_playing: bool,
@@ -48,13 +363,51 @@ pub struct PlayerState {
     pub duration: f64,
     pub volume: f32,
     pub file_path: Option<String>,
+    pub output_device: Option<String>,
+}
+
+/// Called from the playback thread when playback moves to a different output
+/// device, so per-device settings can be applied to the effect chain.
+pub type DeviceListener = Box<dyn Fn(&str, &mut EffectChain) + Send>;
+
+/// Receives spectrum frames from the analysis thread while a track plays.
+pub type SpectrumListener = Box<dyn Fn(&SpectrumFrame) + Send>;
+
+/// Receives per-channel meter readings from the analysis thread while a track plays.
+pub type LevelListener = Box<dyn Fn(&[ChannelLevels]) + Send>;
+
+/// Handles shared between the player and its playback thread.
+#[derive(Clone)]
+struct PlaybackContext {
+    state: Arc<Mutex<PlayerState>>,
+    should_stop: Arc<AtomicBool>,
+    seek_position: Arc<Mutex<Option<f64>>>,
+    output: Arc<OutputControl>,
+    ab_loop: Arc<Mutex<Option<AbLoop>>>,
+    effects: Arc<Mutex<EffectChain>>,
+    limiter: Arc<LimiterControl>,
+    device_listener: Arc<Mutex<Option<DeviceListener>>>,
+    spectrum_settings: Arc<Mutex<SpectrumSettings>>,
+    spectrum_listener: Arc<Mutex<Option<SpectrumListener>>>,
+    meter: Arc<Mutex<LevelMeter>>,
+    level_listener: Arc<Mutex<Option<LevelListener>>>,
 }
 
 pub struct SymphoniaPlayer {
     state: Arc<Mutex<PlayerState>>,
     should_stop: Arc<AtomicBool>,
     seek_position: Arc<Mutex<Option<f64>>>,
-    volume: Arc<Mutex<f32>>,
+    output: Arc<OutputControl>,
+    volume_curve: Mutex<VolumeCurve>,
+    ab_loop: Arc<Mutex<Option<AbLoop>>>,
+    effects: Arc<Mutex<EffectChain>>,
+    /// Output stage after volume, so neither EQ boosts nor volume above unity can clip.
+    limiter: Arc<LimiterControl>,
+    device_listener: Arc<Mutex<Option<DeviceListener>>>,
+    spectrum_settings: Arc<Mutex<SpectrumSettings>>,
+    spectrum_listener: Arc<Mutex<Option<SpectrumListener>>>,
+    meter: Arc<Mutex<LevelMeter>>,
+    level_listener: Arc<Mutex<Option<LevelListener>>>,
     player_thread: Option<thread::JoinHandle<()>>,
 }
 
@@ -67,13 +420,23 @@ impl SymphoniaPlayer {
             duration: 0.0,
             volume: 1.0,
             file_path: None,
+            output_device: None,
         }));
 
         Ok(SymphoniaPlayer {
             state,
             should_stop: Arc::new(AtomicBool::new(false)),
             seek_position: Arc::new(Mutex::new(None)),
-            volume: Arc::new(Mutex::new(1.0)),
+            output: Arc::new(OutputControl::new()),
+            volume_curve: Mutex::new(VolumeCurve::default()),
+            ab_loop: Arc::new(Mutex::new(None)),
+            effects: Arc::new(Mutex::new(EffectChain::default())),
+            limiter: Arc::new(LimiterControl::new()),
+            device_listener: Arc::new(Mutex::new(None)),
+            spectrum_settings: Arc::new(Mutex::new(SpectrumSettings::default())),
+            spectrum_listener: Arc::new(Mutex::new(None)),
+            meter: Arc::new(Mutex::new(LevelMeter::new(44100.0, 2))),
+            level_listener: Arc::new(Mutex::new(None)),
             player_thread: None,
         })
     }
@@ -96,19 +459,19 @@ impl SymphoniaPlayer {
             state.is_paused = false;
         }
 
-        // Reset seek position and stop flag
+        // Reset seek position, loop region and stop flag
         *self.seek_position.lock().unwrap() = None;
+        *self.ab_loop.lock().unwrap() = None;
         self.should_stop.store(false, Ordering::Relaxed);
+        self.output.paused.store(false, Ordering::Relaxed);
+        self.output.flush.store(false, Ordering::Relaxed);
 
         // Start playback thread
         let file_path = file_path.to_string();
-        let state = Arc::clone(&self.state);
-        let should_stop = Arc::clone(&self.should_stop);
-        let seek_position = Arc::clone(&self.seek_position);
-        let volume = Arc::clone(&self.volume);
+        let context = self.playback_context();
 
         let handle = thread::spawn(move || {
-            if let Err(e) = Self::play_file_thread(file_path, state, should_stop, seek_position, volume) {
+            if let Err(e) = Self::play_file_thread(file_path, context) {
                 eprintln!("Playback error: {}", e);
             }
         });
@@ -118,56 +481,33 @@ impl SymphoniaPlayer {
         Ok(duration)
     }
 
-    fn play_file_thread(
-        file_path: String,
-        state: Arc<Mutex<PlayerState>>,
-        should_stop: Arc<AtomicBool>,
-        seek_position: Arc<Mutex<Option<f64>>>,
-        volume: Arc<Mutex<f32>>,
-    ) -> Result<(), String> {
-        // Open the file
-        let file = File::open(&file_path).map_err(|e| format!("Failed to open file: {}", e))?;
-        let media_source = MediaSourceStream::new(Box::new(file), Default::default());
-
-        // Create a probe hint using the file's extension
-        let mut hint = Hint::new();
-        if let Some(extension) = Path::new(&file_path).extension() {
-            if let Some(ext_str) = extension.to_str() {
-                hint.with_extension(ext_str);
-            }
+    fn playback_context(&self) -> PlaybackContext {
+        PlaybackContext {
+            state: Arc::clone(&self.state),
+            should_stop: Arc::clone(&self.should_stop),
+            seek_position: Arc::clone(&self.seek_position),
+            output: Arc::clone(&self.output),
+            ab_loop: Arc::clone(&self.ab_loop),
+            effects: Arc::clone(&self.effects),
+            limiter: Arc::clone(&self.limiter),
+            device_listener: Arc::clone(&self.device_listener),
+            spectrum_settings: Arc::clone(&self.spectrum_settings),
+            spectrum_listener: Arc::clone(&self.spectrum_listener),
+            meter: Arc::clone(&self.meter),
+            level_listener: Arc::clone(&self.level_listener),
         }
+    }
 
-        // Use the default options for metadata and format
-        let meta_opts: MetadataOptions = Default::default();
-        let fmt_opts: FormatOptions = Default::default();
-
-        // Probe the media source
-        let probed = symphonia::default::get_probe()
-            .format(&hint, media_source, &fmt_opts, &meta_opts)
-            .map_err(|e| format!("Unsupported format: {}", e))?;
-
-        let mut format = probed.format;
-
-        // Find the first audio track
-        let track = format
-            .tracks()
-            .iter()
-            .find(|t| t.codec_params.codec != CODEC_TYPE_NULL)
-            .ok_or("No supported audio tracks")?;
-
-        let track_id = track.id;
-
-        // Create a decoder for the track
-        let dec_opts: DecoderOptions = Default::default();
-        let mut decoder = symphonia::default::get_codecs()
-            .make(&track.codec_params, &dec_opts)
-            .map_err(|e| format!("Unsupported codec: {}", e))?;
+    fn play_file_thread(file_path: String, context: PlaybackContext) -> Result<(), String> {
+        let (mut format, mut decoder, track_id) = decode::open_track(&file_path)?;
 
         // Get the audio output device
         let host = cpal::default_host();
         let device = host
             .default_output_device()
             .ok_or("Failed to get default output device")?;
+        let device_name = device.name().unwrap_or_else(|_| "Unknown device".to_string());
+        Self::notify_device_change(&context, &device_name);
 
         // Create audio stream
         Self::create_audio_stream(
@@ -175,39 +515,81 @@ impl SymphoniaPlayer {
             &mut format,
             &mut decoder,
             track_id,
-            state,
-            should_stop,
-            seek_position,
-            volume,
+            context,
         )
     }
 
+    fn notify_device_change(context: &PlaybackContext, device_name: &str) {
+        let changed = {
+            let mut state = context.state.lock().unwrap();
+            let changed = state.output_device.as_deref() != Some(device_name);
+            state.output_device = Some(device_name.to_string());
+            changed
+        };
+
+        if changed {
+            if let Some(listener) = context.device_listener.lock().unwrap().as_ref() {
+                listener(device_name, &mut context.effects.lock().unwrap());
+            }
+        }
+    }
+
     fn create_audio_stream(
         device: Device,
         format: &mut Box<dyn FormatReader>,
         decoder: &mut Box<dyn Decoder>,
         track_id: u32,
-        state: Arc<Mutex<PlayerState>>,
-        should_stop: Arc<AtomicBool>,
-        seek_position: Arc<Mutex<Option<f64>>>,
-        volume: Arc<Mutex<f32>>,
+        context: PlaybackContext,
     ) -> Result<(), String> {
+        let analysis_context = context.clone();
+        let PlaybackContext {
+            state,
+            should_stop,
+            seek_position,
+            output,
+            ab_loop,
+            effects,
+            limiter,
+            ..
+        } = context;
+
         let config = device
             .default_output_config()
             .map_err(|e| format!("Failed to get default output config: {}", e))?;
 
         let sample_rate = config.sample_rate().0 as f32;
+        // Positions, seeks and loop points count frames of the track itself
+        let track_rate = format
+            .tracks()
+            .iter()
+            .find(|track| track.id == track_id)
+            .and_then(|track| track.codec_params.sample_rate)
+            .map_or(sample_rate as f64, |rate| rate as f64);
         let channels = config.channels() as usize;
 
         // Create a ring buffer for audio data
-        let ring_buffer = HeapRb::<f32>::new(sample_rate as usize * channels * 2); // 2 seconds buffer
+        let ring_buffer = HeapRb::<f32>::new((sample_rate * RING_BUFFER_SECS) as usize * channels);
         let (mut producer, mut consumer) = ring_buffer.split();
 
+        // The output callback copies what it plays into the tap for the analysis thread
+        let tap = HeapRb::<f32>::new((sample_rate * TAP_BUFFER_SECS) as usize * channels);
+        let (mut tap_producer, tap_consumer) = tap.split();
+        let tap_done = Arc::new(AtomicBool::new(false));
+        let tap_thread = {
+            let tap_done = Arc::clone(&tap_done);
+            thread::spawn(move || Self::analysis_thread(tap_consumer, sample_rate, channels, analysis_context, tap_done))
+        };
+
+        effects.lock().unwrap().prepare(sample_rate, channels);
+        let (mut output_limiter, mut limiter_updates) = limiter.attach(sample_rate, channels);
+        let limiter_control = Arc::clone(&limiter);
+
         // Clone for the audio thread
         let state_clone = Arc::clone(&state);
         let _should_stop_clone = Arc::clone(&should_stop);
         let _seek_position_clone = Arc::clone(&seek_position);
-        let volume_clone = Arc::clone(&volume);
+        let output_clone = Arc::clone(&output);
+        let mut ramp = OutputRamp::new(sample_rate, output.volume());
 
         // Start audio output stream
         let stream = match config.sample_format() {
@@ -215,23 +597,45 @@ impl SymphoniaPlayer {
                 .build_output_stream(
                     &config.into(),
                     move |data: &mut [f32], _: &cpal::OutputCallbackInfo| {
-                        let vol = *volume_clone.lock().unwrap();
+                        let target_volume = output_clone.volume();
+                        let muted = output_clone.is_muted();
                         // Inline audio callback to avoid generic type issues
                         let available = consumer.len();
                         let needed = data.len();
 
                         if available >= needed {
                             // Read from ring buffer
-                            for sample in data.iter_mut() {
-                                if let Some(audio_sample) = consumer.pop() {
-                                    *sample = audio_sample * vol;
-                                } else {
-                                    *sample = 0.0;
+                            for frame in data.chunks_exact_mut(channels) {
+                                // Once faded out, leave the buffered audio for when playback resumes
+                                let Some(gain) = ramp.next(target_volume, muted) else {
+                                    frame.fill(0.0);
+                                    continue;
+                                };
+                                for sample in frame {
+                                    *sample = consumer.pop().unwrap_or(0.0) * gain;
                                 }
                             }
                         } else {
-                            // Not enough data, fill with silence
+                            // Not enough data, fill with silence and fade back in once it arrives
                             data.fill(0.0);
+                            ramp.silence();
+                        }
+
+                        if ramp.is_silent() && output_clone.flush.load(Ordering::Acquire) {
+                            consumer.clear();
+                            output_clone.flush.store(false, Ordering::Release);
+                        }
+
+                        if let Some(params) = limiter_updates.pop_iter().last() {
+                            output_limiter.set_limiter_params(params);
+                            limiter_control.latency.store(output_limiter.latency(), Ordering::Release);
+                        }
+                        output_limiter.process(data, channels);
+
+                        // Tapped after the limiter so meters and clip counters show what reaches the device.
+                        // Skipped while the analysis thread is behind, keeping the tap frame-aligned.
+                        if tap_producer.free_len() >= data.len() {
+                            tap_producer.push_slice(data);
                         }
                     },
                     |err| eprintln!("Audio stream error: {}", err),
@@ -246,6 +650,8 @@ impl SymphoniaPlayer {
         // Decode and feed audio data
         let _start_time = Instant::now();
         let mut frames_decoded = 0u64;
+        let mut skip_frames = 0u64;
+        let mut loop_seam = LoopSeam::default();
 
         loop {
             if should_stop.load(Ordering::Relaxed) {
@@ -253,14 +659,18 @@ impl SymphoniaPlayer {
             }
 
             // Check for seek request
-            if let Some(seek_pos) = seek_position.lock().unwrap().take() {
-                if Self::seek_to_position(format, decoder, track_id, seek_pos).is_ok() {
+            let seek_request = seek_position.lock().unwrap().take();
+            if let Some(seek_pos) = seek_request {
+                output.flush_and_wait();
+                if let Ok(overshoot) = Self::seek_to_position(format, decoder, track_id, seek_pos) {
                     // Update state and reset timing
                     {
                         let mut state = state.lock().unwrap();
                         state.current_time = seek_pos;
                     }
-                    frames_decoded = (seek_pos * sample_rate as f64) as u64;
+                    frames_decoded = (seek_pos * track_rate) as u64;
+                    skip_frames = (overshoot * track_rate) as u64;
+                    loop_seam.clear();
                 }
             }
 
@@ -279,47 +689,59 @@ impl SymphoniaPlayer {
 
                     match decoder.decode(&packet) {
                         Ok(audio_buf) => {
-                            // Convert to f32 and push to ring buffer - inlined to avoid generics
-                            match &audio_buf {
-                                AudioBufferRef::F32(buf) => {
-                                    // Interleave channels
-                                    for frame in 0..buf.frames() {
-                                        for ch in 0..buf.spec().channels.count() {
-                                            if let Some(sample) = buf.chan(ch).get(frame) {
-                                                let _ = producer.push(*sample);
-                                            }
-                                        }
-                                    }
+                            let mut samples = Self::interleave(&audio_buf, channels);
+
+                            // Drop the part of the packet that precedes an accurate seek target
+                            if skip_frames > 0 {
+                                let skipped = skip_frames.min((samples.len() / channels) as u64);
+                                samples.drain(..skipped as usize * channels);
+                                skip_frames -= skipped;
+                            }
+
+                            let packet_start = frames_decoded;
+                            frames_decoded += (samples.len() / channels) as u64;
+
+                            // Hold back the end of the loop region and jump back once it is reached
+                            let mut loop_restart = None;
+                            if let Some(region) = *ab_loop.lock().unwrap() {
+                                let boundary = region.boundary(track_rate, packet_start, frames_decoded);
+                                if boundary.held > 0 {
+                                    let (keep, held) = (boundary.keep, boundary.keep + boundary.held);
+                                    loop_seam.hold(&samples[keep * channels..held * channels]);
+                                    samples.truncate(keep * channels);
                                 }
-                                AudioBufferRef::S32(buf) => {
-                                    for frame in 0..buf.frames() {
-                                        for ch in 0..buf.spec().channels.count() {
-                                            if let Some(sample) = buf.chan(ch).get(frame) {
-                                                let f32_sample = *sample as f32 / i32::MAX as f32;
-                                                let _ = producer.push(f32_sample);
-                                            }
-                                        }
-                                    }
+                                if boundary.restart {
+                                    loop_restart = Some(region.start);
                                 }
-                                AudioBufferRef::S16(buf) => {
-                                    for frame in 0..buf.frames() {
-                                        for ch in 0..buf.spec().channels.count() {
-                                            if let Some(sample) = buf.chan(ch).get(frame) {
-                                                let f32_sample = *sample as f32 / i16::MAX as f32;
-                                                let _ = producer.push(f32_sample);
-                                            }
-                                        }
+                            }
+
+                            loop_seam.blend(&mut samples, channels);
+
+                            let latency = {
+                                let mut effects = effects.lock().unwrap();
+                                effects.process(&mut samples);
+                                (effects.latency() + limiter.latency()) as u64
+                            };
+                            Self::push_samples(&mut producer, &samples, &should_stop);
+
+                            if let Some(loop_start) = loop_restart {
+                                match Self::seek_to_position(format, decoder, track_id, loop_start) {
+                                    Ok(overshoot) => {
+                                        frames_decoded = (loop_start * track_rate) as u64;
+                                        skip_frames = (overshoot * track_rate) as u64;
+                                        loop_seam.arm();
+                                    }
+                                    Err(e) => {
+                                        eprintln!("A-B loop seek failed: {}", e);
+                                        loop_seam.clear();
                                     }
-                                }
-                                _ => {
-                                    // Handle other formats if needed
-                                    eprintln!("Unsupported audio buffer format");
                                 }
                             }
-                            frames_decoded += audio_buf.frames() as u64;
 
-                            // Update current time
-                            let current_time = frames_decoded as f64 / sample_rate as f64;
+                            // Update current time, accounting for the delay added by the effect chain and
+                            // limiter and for the audio still waiting in the ring buffer
+                            let buffered = (producer.len() / channels) as u64;
+                            let current_time = frames_decoded.saturating_sub(latency + buffered) as f64 / track_rate;
                             state.lock().unwrap().current_time = current_time;
                         }
                         Err(Error::DecodeError(err)) => {
@@ -350,8 +772,15 @@ impl SymphoniaPlayer {
                 }
             }
 
-            // Small sleep to prevent busy waiting
-            thread::sleep(Duration::from_millis(1));
+        }
+
+        if should_stop.load(Ordering::Relaxed) {
+            output.flush_and_wait();
+        } else {
+            // Let the output play what is still buffered before closing the stream
+            while !producer.is_empty() && !should_stop.load(Ordering::Relaxed) {
+                thread::sleep(Duration::from_millis(5));
+            }
         }
 
         // Update state when finished
@@ -362,45 +791,183 @@ impl SymphoniaPlayer {
         }
 
         drop(stream);
+        tap_done.store(true, Ordering::Relaxed);
+        let _ = tap_thread.join();
         Ok(())
     }
 
+    /// Feeds the tapped output to the spectrum analyzer and level meter, and
+    /// hands their readings to the listeners at the spectrum frame rate until
+    /// `done` is set.
+    fn analysis_thread(mut tap: HeapConsumer<f32>, sample_rate: f32, channels: usize, context: PlaybackContext, done: Arc<AtomicBool>) {
+        let mut analyzer: Option<SpectrumAnalyzer> = None;
+        let mut buffer = vec![0.0f32; 4096 * channels];
+        *context.meter.lock().unwrap() = LevelMeter::new(sample_rate, channels);
+
+        while !done.load(Ordering::Relaxed) {
+            let settings = context.spectrum_settings.lock().unwrap().clone();
+            thread::sleep(Duration::from_secs_f32(1.0 / settings.fps as f32));
+
+            let mut spectrum = match &mut analyzer {
+                _ if !settings.enabled => None,
+                Some(analyzer) if *analyzer.settings() == settings => Some(analyzer),
+                _ => Some(analyzer.insert(SpectrumAnalyzer::new(settings, sample_rate, channels))),
+            };
+
+            let mut meter = context.meter.lock().unwrap();
+            loop {
+                let count = tap.pop_slice(&mut buffer);
+                if count == 0 {
+                    break;
+                }
+                meter.push(&buffer[..count]);
+                if let Some(analyzer) = spectrum.as_mut() {
+                    analyzer.push(&buffer[..count]);
+                }
+            }
+            let levels = meter.update().to_vec();
+            drop(meter);
+
+            if let Some(listener) = context.level_listener.lock().unwrap().as_ref() {
+                listener(&levels);
+            }
+
+            if context.state.lock().unwrap().is_paused {
+                continue;
+            }
+            if let (Some(analyzer), Some(listener)) = (spectrum, context.spectrum_listener.lock().unwrap().as_ref()) {
+                listener(&analyzer.frame());
+            }
+        }
+    }
+
+    /// Converts a decoded buffer to interleaved f32 samples laid out for the
+    /// output device's channel count.
+    fn interleave(audio_buf: &AudioBufferRef, out_channels: usize) -> Vec<f32> {
+        let spec = *audio_buf.spec();
+        let mut sample_buf = SampleBuffer::<f32>::new(audio_buf.capacity() as u64, spec);
+        sample_buf.copy_interleaved_ref(audio_buf.clone());
+        Self::map_channels(sample_buf.samples(), spec.channels, out_channels)
+    }
+
+    /// Maps interleaved frames in `layout` onto `out_channels` outputs.
+    ///
+    /// Mono feeds the front pair, surround folds down to stereo with the usual
+    /// -3 dB centre and surround weights (LFE dropped) scaled so a full-scale
+    /// channel can't clip, and anything down to mono is averaged. Other
+    /// layouts keep the channels both sides share and silence the rest.
+    fn map_channels(samples: &[f32], layout: Channels, out_channels: usize) -> Vec<f32> {
+        let in_channels = layout.count();
+        if in_channels == out_channels || in_channels == 0 {
+            return samples.to_vec();
+        }
+
+        let weights: Vec<[f32; 2]> = layout
+            .iter()
+            .map(|channel| {
+                if channel == Channels::LFE1 || channel == Channels::LFE2 {
+                    [0.0, 0.0]
+                } else if channel == Channels::FRONT_LEFT {
+                    [1.0, 0.0]
+                } else if channel == Channels::FRONT_RIGHT {
+                    [0.0, 1.0]
+                } else if channel
+                    .intersects(Channels::FRONT_LEFT_CENTRE | Channels::REAR_LEFT | Channels::SIDE_LEFT | Channels::FRONT_LEFT_WIDE)
+                {
+                    [FRAC_1_SQRT_2, 0.0]
+                } else if channel.intersects(
+                    Channels::FRONT_RIGHT_CENTRE | Channels::REAR_RIGHT | Channels::SIDE_RIGHT | Channels::FRONT_RIGHT_WIDE,
+                ) {
+                    [0.0, FRAC_1_SQRT_2]
+                } else {
+                    [FRAC_1_SQRT_2, FRAC_1_SQRT_2]
+                }
+            })
+            .collect();
+        let scale = 1.0 / weights.iter().map(|weight| weight[0]).sum::<f32>().max(1.0);
+
+        let mut output = Vec::with_capacity(samples.len() / in_channels * out_channels);
+        for frame in samples.chunks_exact(in_channels) {
+            match (in_channels, out_channels) {
+                (1, _) => output.extend((0..out_channels).map(|ch| if ch < 2 { frame[0] } else { 0.0 })),
+                (_, 1) => output.push(frame.iter().sum::<f32>() / in_channels as f32),
+                (_, 2) => {
+                    let (left, right) = frame
+                        .iter()
+                        .zip(&weights)
+                        .fold((0.0, 0.0), |(l, r), (sample, weight)| (l + sample * weight[0], r + sample * weight[1]));
+                    output.extend([left * scale, right * scale]);
+                }
+                _ => output.extend((0..out_channels).map(|ch| frame.get(ch).copied().unwrap_or(0.0))),
+            }
+        }
+        output
+    }
+
+    /// Blocks until all samples fit into the ring buffer, so nothing is dropped
+    /// while the output callback catches up.
+    fn push_samples(producer: &mut HeapProducer<f32>, samples: &[f32], should_stop: &AtomicBool) {
+        let mut written = 0;
+        while written < samples.len() {
+            if should_stop.load(Ordering::Relaxed) {
+                return;
+            }
+            written += producer.push_slice(&samples[written..]);
+            if written < samples.len() {
+                thread::sleep(Duration::from_millis(2));
+            }
+        }
+    }
+
 
     fn seek_to_position(
         format: &mut Box<dyn FormatReader>,
         decoder: &mut Box<dyn Decoder>,
         track_id: u32,
         position: f64,
-    ) -> Result<(), String> {
+    ) -> Result<f64, String> {
         // Get the track to determine time base
-        let _track = format
+        let time_base = format
             .tracks()
             .iter()
             .find(|t| t.id == track_id)
-            .ok_or("Track not found")?;
+            .ok_or("Track not found")?
+            .codec_params
+            .time_base;
 
         // Calculate the seek time in seconds
         let seek_time = Time::new(position as u64, position.fract());
 
         // Perform the seek
-        format
+        let seeked_to = format
             .seek(SeekMode::Accurate, SeekTo::Time { time: seek_time, track_id: Some(track_id) })
             .map_err(|e| format!("Seek failed: {}", e))?;
 
         // Reset the decoder
         decoder.reset();
 
-        Ok(())
+        // The reader lands on a packet boundary; report how far before the
+        // requested position it is so the caller can discard those samples
+        let overshoot = time_base
+            .map(|tb| {
+                let time = tb.calc_time(seeked_to.required_ts.saturating_sub(seeked_to.actual_ts));
+                time.seconds as f64 + time.frac
+            })
+            .unwrap_or(0.0);
+
+        Ok(overshoot)
     }
 
     pub fn pause(&mut self) {
         let mut state = self.state.lock().unwrap();
         state.is_paused = true;
+        self.output.paused.store(true, Ordering::Relaxed);
     }
 
     pub fn resume(&mut self) {
         let mut state = self.state.lock().unwrap();
         state.is_paused = false;
+        self.output.paused.store(false, Ordering::Relaxed);
     }
 
     pub fn stop(&mut self) {
@@ -422,12 +989,251 @@ impl SymphoniaPlayer {
         Ok(())
     }
 
+    pub fn set_ab_loop(&self, start: f32, end: f32) -> Result<(), String> {
+        let (start, end) = (start as f64, end as f64);
+        if start < 0.0 || end - start < AB_LOOP_CROSSFADE_SECS * 2.0 {
+            return Err("Loop end must come after loop start".to_string());
+        }
+
+        let (current_time, duration) = {
+            let state = self.state.lock().unwrap();
+            (state.current_time, state.duration)
+        };
+        if duration > 0.0 && end > duration {
+            return Err("Loop end is past the end of the track".to_string());
+        }
+
+        *self.ab_loop.lock().unwrap() = Some(AbLoop { start, end });
+
+        // Jump into the region right away if playback is outside of it
+        if current_time < start || current_time >= end {
+            *self.seek_position.lock().unwrap() = Some(start);
+        }
+        Ok(())
+    }
+
+    pub fn clear_ab_loop(&self) {
+        *self.ab_loop.lock().unwrap() = None;
+    }
+
+    pub fn set_channel_mixer(&self, params: ChannelMixerParams) {
+        let mut effects = self.effects.lock().unwrap();
+        effects.first_or_insert_with(ChannelMixer::default).set_mixer_params(params);
+    }
+
+    pub fn get_channel_mixer(&self) -> ChannelMixerParams {
+        let effects = self.effects.lock().unwrap();
+        match effects.first::<ChannelMixer>() {
+            Some(mixer) => mixer.mixer_params().clone(),
+            None => ChannelMixer::default().mixer_params().clone(),
+        }
+    }
+
+    /// Enables crossfeed with the given settings, or bypasses it with `None`.
+    pub fn set_crossfeed(&self, params: Option<CrossfeedParams>) -> Result<(), String> {
+        self.with_effects(|effects| Self::apply_crossfeed(effects, params))
+    }
+
+    pub fn apply_crossfeed(effects: &mut EffectChain, params: Option<CrossfeedParams>) -> Result<(), String> {
+        let enabled = params.is_some();
+        if let Some(params) = params {
+            effects.first_or_insert_with(Crossfeed::default).set_crossfeed_params(params);
+        }
+        match effects.first_id::<Crossfeed>() {
+            Some(id) => effects.set_bypass(id, !enabled),
+            None => Ok(()),
+        }
+    }
+
+    /// Enables the compressor with the given settings, or bypasses it with `None`.
+    pub fn set_compressor(&self, params: Option<CompressorParams>) {
+        let mut effects = self.effects.lock().unwrap();
+        let enabled = params.is_some();
+        if let Some(params) = params {
+            effects.first_or_insert_with(Compressor::default).set_compressor_params(params);
+        }
+        if let Some(id) = effects.first_id::<Compressor>() {
+            let _ = effects.set_bypass(id, !enabled);
+        }
+    }
+
+    pub fn get_compressor(&self) -> Option<CompressorParams> {
+        let effects = self.effects.lock().unwrap().effects();
+        effects
+            .into_iter()
+            .find(|info| !info.bypassed && info.kind == "compressor")
+            .and_then(|info| serde_json::from_value(info.params).ok())
+    }
+
+    /// Enables loudness compensation with the given settings, or bypasses it with `None`.
+    pub fn set_loudness(&self, params: Option<LoudnessParams>) {
+        let volume = self.output.volume();
+        let mut effects = self.effects.lock().unwrap();
+        let enabled = params.is_some();
+        if let Some(params) = params {
+            let loudness = effects.first_or_insert_with(Loudness::default);
+            loudness.set_loudness_params(params);
+            loudness.set_volume(volume);
+        }
+        if let Some(id) = effects.first_id::<Loudness>() {
+            let _ = effects.set_bypass(id, !enabled);
+        }
+    }
+
+    pub fn get_loudness(&self) -> Option<LoudnessParams> {
+        let effects = self.effects.lock().unwrap().effects();
+        effects
+            .into_iter()
+            .find(|info| !info.bypassed && info.kind == "loudness")
+            .and_then(|info| serde_json::from_value(info.params).ok())
+    }
+
+    /// Enables vocal reduction with the given settings, or bypasses it with `None`.
+    pub fn set_karaoke(&self, params: Option<KaraokeParams>) {
+        let mut effects = self.effects.lock().unwrap();
+        let enabled = params.is_some();
+        if let Some(params) = params {
+            effects.first_or_insert_with(Karaoke::default).set_karaoke_params(params);
+        }
+        if let Some(id) = effects.first_id::<Karaoke>() {
+            let _ = effects.set_bypass(id, !enabled);
+        }
+    }
+
+    pub fn get_karaoke(&self) -> Option<KaraokeParams> {
+        let effects = self.effects.lock().unwrap().effects();
+        effects
+            .into_iter()
+            .find(|info| !info.bypassed && info.kind == "karaoke")
+            .and_then(|info| serde_json::from_value(info.params).ok())
+    }
+
+    /// Enables the mid/side stage with the given settings, or bypasses it with `None`.
+    pub fn set_stereo_width(&self, params: Option<StereoWidthParams>) {
+        let mut effects = self.effects.lock().unwrap();
+        let enabled = params.is_some();
+        if let Some(params) = params {
+            effects.first_or_insert_with(StereoWidth::default).set_width_params(params);
+        }
+        if let Some(id) = effects.first_id::<StereoWidth>() {
+            let _ = effects.set_bypass(id, !enabled);
+        }
+    }
+
+    pub fn get_stereo_width(&self) -> Option<StereoWidthParams> {
+        let effects = self.effects.lock().unwrap().effects();
+        effects
+            .into_iter()
+            .find(|info| !info.bypassed && info.kind == "stereo_width")
+            .and_then(|info| serde_json::from_value(info.params).ok())
+    }
+
+    /// Loads an impulse response into the convolver, or removes it with `None`.
+    pub fn set_convolver(&self, params: Option<ConvolverParams>) -> Result<(), String> {
+        // Read and partition the IR before taking the lock so playback isn't held up
+        let mut convolver = params.map(Convolver::with_params).transpose()?;
+        if let Some(convolver) = &mut convolver {
+            let (sample_rate, channels) = self.effects.lock().unwrap().format();
+            convolver.preload(sample_rate, channels);
+        }
+
+        let mut effects = self.effects.lock().unwrap();
+        if let Some(id) = effects.first_id::<Convolver>() {
+            effects.remove(id)?;
+        }
+        if let Some(convolver) = convolver {
+            effects.add_stage(Box::new(convolver));
+        }
+        Ok(())
+    }
+
+    pub fn get_convolver(&self) -> Option<ConvolverParams> {
+        let effects = self.effects.lock().unwrap().effects();
+        effects
+            .into_iter()
+            .find(|info| !info.bypassed && info.kind == "convolver")
+            .and_then(|info| serde_json::from_value(info.params).ok())
+    }
+
+    pub fn set_limiter(&self, params: LimiterParams) {
+        self.limiter.set_params(params);
+    }
+
+    pub fn get_limiter(&self) -> LimiterParams {
+        self.limiter.params()
+    }
+
+    pub fn set_device_listener(&self, listener: impl Fn(&str, &mut EffectChain) + Send + 'static) {
+        *self.device_listener.lock().unwrap() = Some(Box::new(listener));
+    }
+
+    pub fn set_spectrum_listener(&self, listener: impl Fn(&SpectrumFrame) + Send + 'static) {
+        *self.spectrum_listener.lock().unwrap() = Some(Box::new(listener));
+    }
+
+    pub fn set_spectrum_settings(&self, settings: SpectrumSettings) -> Result<(), String> {
+        settings.validate()?;
+        *self.spectrum_settings.lock().unwrap() = settings;
+        Ok(())
+    }
+
+    pub fn get_spectrum_settings(&self) -> SpectrumSettings {
+        self.spectrum_settings.lock().unwrap().clone()
+    }
+
+    pub fn set_level_listener(&self, listener: impl Fn(&[ChannelLevels]) + Send + 'static) {
+        *self.level_listener.lock().unwrap() = Some(Box::new(listener));
+    }
+
+    /// Latest meter reading per output channel. Clip counters restart with each track.
+    pub fn get_levels(&self) -> Vec<ChannelLevels> {
+        self.meter.lock().unwrap().levels().to_vec()
+    }
+
+    pub fn reset_clip_counters(&self) {
+        self.meter.lock().unwrap().reset_clips();
+    }
+
+    pub fn get_output_device(&self) -> Option<String> {
+        self.state.lock().unwrap().output_device.clone()
+    }
+
+    pub fn output_devices() -> Result<Vec<String>, String> {
+        let host = cpal::default_host();
+        let devices = host
+            .output_devices()
+            .map_err(|e| format!("Failed to list output devices: {}", e))?;
+        Ok(devices.filter_map(|device| device.name().ok()).collect())
+    }
+
+    pub fn with_effects<R>(&self, f: impl FnOnce(&mut EffectChain) -> R) -> R {
+        f(&mut self.effects.lock().unwrap())
+    }
+
+    /// Sets the volume from a slider position between 0 and 1.
     pub fn set_volume(&self, volume: f32) {
-        *self.volume.lock().unwrap() = volume.clamp(0.0, 2.0);
+        let volume = volume.clamp(0.0, 1.0);
+        let gain = self.volume_curve.lock().unwrap().gain(volume);
+        self.output.set_volume(gain);
+        if let Some(loudness) = self.effects.lock().unwrap().first_mut::<Loudness>() {
+            loudness.set_volume(gain);
+        }
         let mut state = self.state.lock().unwrap();
         state.volume = volume;
     }
 
+    pub fn set_volume_curve(&self, curve: VolumeCurve) -> Result<(), String> {
+        curve.validate()?;
+        *self.volume_curve.lock().unwrap() = curve;
+        let volume = self.state.lock().unwrap().volume;
+        self.set_volume(volume);
+        Ok(())
+    }
+
+    pub fn get_volume_curve(&self) -> VolumeCurve {
+        *self.volume_curve.lock().unwrap()
+    }
+
     pub fn get_current_time(&self) -> f32 {
         self.state.lock().unwrap().current_time as f32
     }
@@ -441,6 +1247,10 @@ impl SymphoniaPlayer {
         state.is_playing && !state.is_paused
     }
 
+    /// Tags and codec details of `path`. Tags come from both the metadata
+    /// found while probing (ID3v2 and the like) and the container's own
+    /// (FLAC Vorbis comments, MP4 atoms); where both set a field, the
+    /// container's value wins. Artwork in either counts.
     pub fn get_track_metadata(path: &str) -> Result<TrackMetadata, String> {
         let file = File::open(path).map_err(|e| format!("Failed to open file: {}", e))?;
         let mss = MediaSourceStream::new(Box::new(file), Default::default());
@@ -459,7 +1269,7 @@ impl SymphoniaPlayer {
             .format(&hint, mss, &fmt_opts, &meta_opts)
             .map_err(|e| format!("Failed to probe format: {}", e))?;
 
-        let format = probed.format;
+        let mut format = probed.format;
         let mut metadata = probed.metadata;
 
         // Find the audio track
@@ -481,6 +1291,8 @@ impl SymphoniaPlayer {
             sample_rate: track.codec_params.sample_rate,
             channels: track.codec_params.channels.map(|c| c.to_string()),
             bits_per_sample: track.codec_params.bits_per_sample,
+            bpm: None,
+            key: None,
             has_artwork: false,
         };
 
@@ -489,9 +1301,12 @@ impl SymphoniaPlayer {
             meta.duration = n_frames as f64 / sample_rate as f64;
         }
 
-        // Extract metadata
-        if let Some(metadata) = metadata.get() {
-            if let Some(metadata_rev) = metadata.current() {
+        // Extract metadata: tags found while probing (such as ID3v2) and those
+        // kept by the container itself (such as FLAC Vorbis comments)
+        let probed_metadata = metadata.get();
+        let container_metadata = format.metadata();
+        let revisions = probed_metadata.iter().chain([&container_metadata]).filter_map(|m| m.current());
+        for metadata_rev in revisions {
             for tag in metadata_rev.tags() {
                 match tag.std_key {
                     Some(StandardTagKey::TrackTitle) => meta.title = Some(tag.value.to_string()),
@@ -504,13 +1319,17 @@ impl SymphoniaPlayer {
                         meta.year = tag.value.to_string().parse().ok()
                     }
                     Some(StandardTagKey::Genre) => meta.genre = Some(tag.value.to_string()),
+                    Some(StandardTagKey::Bpm) => meta.bpm = tag.value.to_string().trim().parse().ok(),
+                    // Symphonia has no standard key for the initial key
+                    None if tag.key.eq_ignore_ascii_case("TKEY") || tag.key.eq_ignore_ascii_case("INITIALKEY") => {
+                        meta.key = Some(tag.value.to_string())
+                    }
                     _ => {}
                 }
             }
 
             // Check for artwork
-            meta.has_artwork = !metadata_rev.visuals().is_empty();
-            }
+            meta.has_artwork |= !metadata_rev.visuals().is_empty();
         }
 
         Ok(meta)
@@ -552,19 +1371,201 @@ impl SymphoniaPlayer {
         Ok(None)
     }
 
-    // Equalizer methods (stub for now)
-    pub fn set_equalizer_band(&self, _frequency: u32, _gain: f32) -> Result<(), String> {
-        // TODO: Implement equalizer
-        Ok(())
+    // Equalizer methods, backed by the parametric EQ stage of the effect chain
+    fn with_equalizer<R>(&self, f: impl FnOnce(&mut ParametricEq) -> R) -> R {
+        let mut effects = self.effects.lock().unwrap();
+        f(effects.first_or_insert_with(ParametricEq::default))
     }
 
-    pub fn set_equalizer_preset(&self, _gains: Vec<f32>) -> Result<(), String> {
-        // TODO: Implement equalizer
-        Ok(())
+    pub fn set_equalizer_band(&self, frequency: u32, gain: f32) -> Result<(), String> {
+        self.with_equalizer(|eq| eq.set_graphic_band(frequency, gain))
     }
 
-    pub fn enable_equalizer(&self, _enabled: bool) -> Result<(), String> {
-        // TODO: Implement equalizer
-        Ok(())
+    pub fn set_equalizer_preset(&self, gains: Vec<f32>) -> Result<(), String> {
+        self.with_equalizer(|eq| eq.set_graphic_preset(&gains))
+    }
+
+    pub fn enable_equalizer(&self, enabled: bool) -> Result<(), String> {
+        let mut effects = self.effects.lock().unwrap();
+        effects.first_or_insert_with(ParametricEq::default);
+        match effects.first_id::<ParametricEq>() {
+            Some(id) => effects.set_bypass(id, !enabled),
+            None => Err("Equalizer not available".to_string()),
+        }
+    }
+
+    pub fn set_parametric_eq(&self, params: ParametricEqParams) -> Result<(), String> {
+        self.with_equalizer(|eq| eq.set_eq_params(params))
+    }
+
+    pub fn get_parametric_eq(&self) -> ParametricEqParams {
+        let effects = self.effects.lock().unwrap();
+        match effects.first::<ParametricEq>() {
+            Some(eq) => eq.params().clone(),
+            None => ParametricEq::default().params().clone(),
+        }
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn volume_curve_maps_the_slider_onto_decibels() {
+        let curve = VolumeCurve::default();
+        assert_eq!(curve.gain(0.0), 0.0);
+        assert!((curve.gain(1.0) - 1.0).abs() < 1e-6);
+        assert!((curve.gain(0.5) - 10f32.powf(-30.0 / 20.0)).abs() < 1e-6);
+        assert!((curve.gain(2.0) - 1.0).abs() < 1e-6);
+
+        assert!(VolumeCurve { min_db: -40.0, max_db: 6.0 }.validate().is_ok());
+        assert!(VolumeCurve { min_db: -3.0, max_db: 0.0 }.validate().is_err());
+        assert!(VolumeCurve { min_db: -200.0, max_db: 0.0 }.validate().is_err());
+        assert!(VolumeCurve { min_db: -20.0, max_db: 20.0 }.validate().is_err());
+    }
+
+    #[test]
+    fn output_ramp_fades_in_and_out() {
+        let sample_rate = 48000.0;
+        let fade_frames = (OUTPUT_FADE_SECS * sample_rate) as usize;
+        let mut ramp = OutputRamp::new(sample_rate, 1.0);
+
+        let gains: Vec<f32> = (0..fade_frames).map(|_| ramp.next(1.0, false).unwrap()).collect();
+        assert!(gains[0] > 0.0 && gains[0] < 0.01);
+        assert!(gains.windows(2).all(|pair| pair[1] > pair[0]));
+        assert!((gains[fade_frames - 1] - 1.0).abs() < 1e-3);
+
+        // Muting ramps down, then reports the frames as held back
+        let mut steps = 0usize;
+        while ramp.next(1.0, true).is_some() {
+            steps += 1;
+        }
+        assert!(steps.abs_diff(fade_frames) <= 1, "{} frames", steps);
+        assert!(ramp.is_silent());
+        assert_eq!(ramp.next(1.0, true), None);
+    }
+
+    #[test]
+    fn output_ramp_smooths_volume_changes() {
+        let sample_rate = 48000.0;
+        let mut ramp = OutputRamp::new(sample_rate, 1.0);
+        for _ in 0..4800 {
+            ramp.next(1.0, false);
+        }
+
+        let first = ramp.next(0.0, false).unwrap();
+        assert!(first > 0.99, "no jump: {}", first);
+        let settle = (VOLUME_SMOOTHING_SECS * sample_rate * 5.0) as usize;
+        let last = (0..settle).map(|_| ramp.next(0.0, false).unwrap()).last().unwrap();
+        assert!(last < 0.01);
+
+        // An underrun drops to silence and fades back in from there
+        ramp.silence();
+        assert!(ramp.next(0.5, false).unwrap() < 0.01);
+    }
+
+    #[test]
+    fn channels_fold_down_instead_of_dropping() {
+        let stereo = Channels::FRONT_LEFT | Channels::FRONT_RIGHT;
+        assert_eq!(SymphoniaPlayer::map_channels(&[0.5, 0.1], stereo, 1), [0.3]);
+        assert_eq!(SymphoniaPlayer::map_channels(&[0.4], Channels::FRONT_LEFT, 2), [0.4, 0.4]);
+        assert_eq!(SymphoniaPlayer::map_channels(&[0.4, -0.2], stereo, 4), [0.4, -0.2, 0.0, 0.0]);
+
+        // 5.1 in symphonia order: FL FR FC LFE RL RR
+        let surround = stereo | Channels::FRONT_CENTRE | Channels::LFE1 | Channels::REAR_LEFT | Channels::REAR_RIGHT;
+        let scale = 1.0 / (1.0 + 2.0 * FRAC_1_SQRT_2);
+        let centre = SymphoniaPlayer::map_channels(&[0.0, 0.0, 1.0, 0.0, 0.0, 0.0], surround, 2);
+        assert!(centre.iter().all(|s| (s - FRAC_1_SQRT_2 * scale).abs() < 1e-6), "{:?}", centre);
+        let lfe = SymphoniaPlayer::map_channels(&[0.0, 0.0, 0.0, 1.0, 0.0, 0.0], surround, 2);
+        assert_eq!(lfe, [0.0, 0.0]);
+
+        // All channels at full scale stay within full scale
+        let full = SymphoniaPlayer::map_channels(&[1.0; 6], surround, 2);
+        assert!(full.iter().all(|s| (s - 1.0).abs() < 1e-6), "{:?}", full);
+        let rear = SymphoniaPlayer::map_channels(&[0.0, 0.0, 0.0, 0.0, 0.0, 1.0], surround, 2);
+        assert_eq!(rear[0], 0.0);
+        assert!(rear[1] > 0.0);
+    }
+
+    #[test]
+    fn loop_boundary_holds_back_the_crossfade() {
+        let region = AbLoop { start: 1.0, end: 2.0 };
+        // 15 ms crossfade at 48 kHz: 720 frames before the loop end at 96000
+        let cases = [
+            ((0, 1024), (1024, 0, false)),
+            ((94000, 95000), (1000, 0, false)),
+            ((95000, 96024), (280, 720, true)),
+            ((95000, 95800), (280, 520, false)),
+            ((95800, 96800), (0, 200, true)),
+            ((96000, 97024), (1024, 0, true)),
+        ];
+        for ((start, end), (keep, held, restart)) in cases {
+            assert_eq!(region.boundary(48000.0, start, end), LoopBoundary { keep, held, restart }, "packet {}..{}", start, end);
+        }
+    }
+
+    #[test]
+    fn loop_boundary_uses_the_track_rate() {
+        let region = AbLoop { start: 1.0, end: 2.0 };
+        // The loop ends at frame 88200 of a 44.1 kHz track, whatever the device rate
+        assert_eq!(region.boundary(44100.0, 87000, 88000), LoopBoundary { keep: 539, held: 461, restart: false });
+        assert_eq!(region.boundary(44100.0, 88000, 89024), LoopBoundary { keep: 0, held: 200, restart: true });
+    }
+
+    #[test]
+    fn loop_boundary_never_holds_before_the_loop_start() {
+        // Shorter than the crossfade after the start: everything from the start is held
+        let region = AbLoop { start: 1.0, end: 1.01 };
+        assert_eq!(region.boundary(48000.0, 47000, 49000), LoopBoundary { keep: 1000, held: 480, restart: true });
+    }
+
+    #[test]
+    fn loop_seam_crossfades_with_equal_power() {
+        let mut seam = LoopSeam::default();
+        seam.hold(&[1.0; 4]);
+        seam.hold(&[1.0; 4]);
+        seam.arm();
+
+        // Stereo, 4 held frames blended over two packets
+        let mut first = vec![0.0; 4];
+        let mut second = vec![0.0; 8];
+        seam.blend(&mut first, 2);
+        seam.blend(&mut second, 2);
+
+        let blended: Vec<f32> = first.iter().chain(&second).copied().collect();
+        for (frame, samples) in blended.chunks(2).enumerate() {
+            let expected = if frame < 4 { (((frame as f32 + 0.5) / 4.0) * FRAC_PI_2).cos() } else { 0.0 };
+            assert!((samples[0] - expected).abs() < 1e-6 && samples[0] == samples[1], "frame {}: {:?}", frame, samples);
+        }
+
+        // Finished: later audio passes untouched
+        let mut later = vec![0.5; 4];
+        seam.blend(&mut later, 2);
+        assert_eq!(later, [0.5; 4]);
+    }
+
+    #[test]
+    fn loop_seam_keeps_a_steady_level_on_correlated_audio() {
+        let mut seam = LoopSeam::default();
+        seam.hold(&[0.5; 16]);
+        seam.arm();
+        let mut samples = vec![0.5; 16];
+        seam.blend(&mut samples, 1);
+        // cos + sin stays between 1 and sqrt(2) across the fade
+        for sample in samples {
+            assert!((0.5..=0.5 * 2f32.sqrt() + 1e-6).contains(&sample), "{}", sample);
+        }
+    }
+
+    #[test]
+    fn cleared_seam_does_nothing() {
+        let mut seam = LoopSeam::default();
+        seam.hold(&[1.0; 8]);
+        seam.clear();
+        seam.arm();
+        let mut samples = vec![0.25; 8];
+        seam.blend(&mut samples, 2);
+        assert_eq!(samples, [0.25; 8]);
+    }
+}
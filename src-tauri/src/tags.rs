@@ -0,0 +1,269 @@
+use id3::TagLike;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+
+/// FLAC metadata block type holding the Vorbis comments.
+const VORBIS_COMMENT: u8 = 4;
+
+/// FLAC metadata block type reserving space for metadata to grow into.
+const PADDING: u8 = 1;
+
+/// Size of a FLAC metadata block header.
+const BLOCK_HEADER_LEN: usize = 4;
+
+/// Largest metadata block FLAC can describe (24-bit length).
+const MAX_BLOCK_LEN: usize = (1 << 24) - 1;
+
+/// Tag fields the analysis results can be written to.
+#[derive(Debug, Clone, Copy)]
+pub enum TagField {
+    Bpm,
+    InitialKey,
+}
+
+impl TagField {
+    fn id3_frame(self) -> &'static str {
+        match self {
+            TagField::Bpm => "TBPM",
+            TagField::InitialKey => "TKEY",
+        }
+    }
+
+    fn vorbis_key(self) -> &'static str {
+        match self {
+            TagField::Bpm => "BPM",
+            TagField::InitialKey => "INITIALKEY",
+        }
+    }
+}
+
+/// Sets a text tag in place, replacing any existing value. MP3, WAV and AIFF
+/// files get an ID3v2.4 frame, FLAC files a Vorbis comment.
+pub fn write_tag(path: &str, field: TagField, value: &str) -> Result<(), String> {
+    let extension = Path::new(path)
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(|e| e.to_lowercase())
+        .unwrap_or_default();
+
+    match extension.as_str() {
+        "mp3" | "wav" | "aif" | "aiff" => write_id3(path, field, value),
+        "flac" => write_flac_comment(path, field.vorbis_key(), value),
+        _ => Err(format!("Writing tags to .{} files is not supported", extension)),
+    }
+}
+
+/// The id3 crate finds the tag inside WAV and AIFF chunks on its own.
+fn write_id3(path: &str, field: TagField, value: &str) -> Result<(), String> {
+    let mut tag = id3::no_tag_ok(id3::Tag::read_from_path(path))
+        .map_err(|e| format!("Failed to read tags: {}", e))?
+        .unwrap_or_default();
+    tag.set_text(field.id3_frame(), value);
+    tag.write_to_path(path, id3::Version::Id3v24).map_err(|e| format!("Failed to write tags: {}", e))
+}
+
+fn read_u32_le(data: &[u8], offset: &mut usize) -> Result<usize, String> {
+    let bytes = data.get(*offset..*offset + 4).ok_or("Malformed Vorbis comment block")?;
+    *offset += 4;
+    Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as usize)
+}
+
+/// Vendor string and comments of a VORBIS_COMMENT block.
+fn parse_comments(data: &[u8]) -> Result<(Vec<u8>, Vec<Vec<u8>>), String> {
+    let mut offset = 0;
+    let vendor_len = read_u32_le(data, &mut offset)?;
+    let vendor = data.get(offset..offset + vendor_len).ok_or("Malformed Vorbis comment block")?.to_vec();
+    offset += vendor_len;
+
+    let count = read_u32_le(data, &mut offset)?;
+    let mut comments = Vec::new();
+    for _ in 0..count {
+        let len = read_u32_le(data, &mut offset)?;
+        comments.push(data.get(offset..offset + len).ok_or("Malformed Vorbis comment block")?.to_vec());
+        offset += len;
+    }
+    Ok((vendor, comments))
+}
+
+fn serialize_comments(vendor: &[u8], comments: &[Vec<u8>]) -> Vec<u8> {
+    let mut data = Vec::new();
+    data.extend_from_slice(&(vendor.len() as u32).to_le_bytes());
+    data.extend_from_slice(vendor);
+    data.extend_from_slice(&(comments.len() as u32).to_le_bytes());
+    for comment in comments {
+        data.extend_from_slice(&(comment.len() as u32).to_le_bytes());
+        data.extend_from_slice(comment);
+    }
+    data
+}
+
+/// Writes metadata blocks in order, flagging the last one.
+fn write_blocks(writer: &mut impl Write, blocks: &[(u8, Vec<u8>)]) -> io::Result<()> {
+    for (index, (kind, data)) in blocks.iter().enumerate() {
+        let last = if index == blocks.len() - 1 { 0x80 } else { 0 };
+        let len = (data.len() as u32).to_be_bytes();
+        writer.write_all(&[kind | last, len[1], len[2], len[3]])?;
+        writer.write_all(data)?;
+    }
+    Ok(())
+}
+
+/// Total size of metadata blocks, headers included.
+fn blocks_len(blocks: &[(u8, Vec<u8>)]) -> usize {
+    blocks.iter().map(|(_, data)| BLOCK_HEADER_LEN + data.len()).sum()
+}
+
+/// Replaces the `key` comment of a FLAC file. When the change fits in the
+/// file's PADDING block, the padding shrinks or grows to match and only the
+/// metadata is rewritten in place. Otherwise the whole file is rewritten
+/// next to the original and moved over it, so a failure never leaves it
+/// half written.
+fn write_flac_comment(path: &str, key: &str, value: &str) -> Result<(), String> {
+    let mut reader = BufReader::new(File::open(path).map_err(|e| format!("Failed to open file: {}", e))?);
+
+    let mut marker = [0u8; 4];
+    reader.read_exact(&mut marker).map_err(|e| format!("Failed to read file: {}", e))?;
+    if &marker != b"fLaC" {
+        return Err("Not a FLAC file".to_string());
+    }
+
+    let mut blocks: Vec<(u8, Vec<u8>)> = Vec::new();
+    let mut metadata_len = 0;
+    loop {
+        let mut header = [0u8; 4];
+        reader.read_exact(&mut header).map_err(|e| format!("Failed to read metadata: {}", e))?;
+        let len = u32::from_be_bytes([0, header[1], header[2], header[3]]) as usize;
+        let mut data = vec![0u8; len];
+        reader.read_exact(&mut data).map_err(|e| format!("Failed to read metadata: {}", e))?;
+        metadata_len += BLOCK_HEADER_LEN + len;
+        blocks.push((header[0] & 0x7f, data));
+        if header[0] & 0x80 != 0 {
+            break;
+        }
+    }
+
+    let (vendor, mut comments) = match blocks.iter().find(|(kind, _)| *kind == VORBIS_COMMENT) {
+        Some((_, data)) => parse_comments(data)?,
+        None => (b"reference libFLAC".to_vec(), Vec::new()),
+    };
+    let prefix = format!("{}=", key);
+    comments.retain(|comment| !comment.get(..prefix.len()).is_some_and(|name| name.eq_ignore_ascii_case(prefix.as_bytes())));
+    comments.push(format!("{}{}", prefix, value).into_bytes());
+
+    let block = serialize_comments(&vendor, &comments);
+    if block.len() > MAX_BLOCK_LEN {
+        return Err("Vorbis comments too large".to_string());
+    }
+    match blocks.iter_mut().find(|(kind, _)| *kind == VORBIS_COMMENT) {
+        Some((_, data)) => *data = block,
+        // STREAMINFO always comes first
+        None => blocks.insert(1.min(blocks.len()), (VORBIS_COMMENT, block)),
+    }
+
+    // Keep the metadata size, and so the audio offset, by resizing the padding
+    let unpadded = blocks_len(&blocks) - blocks.iter().find(|(kind, _)| *kind == PADDING).map_or(0, |(_, data)| data.len());
+    if let Some(padding) = metadata_len.checked_sub(unpadded) {
+        if let Some((_, data)) = blocks.iter_mut().find(|(kind, _)| *kind == PADDING) {
+            if padding <= MAX_BLOCK_LEN {
+                data.resize(padding, 0);
+                drop(reader);
+                let result = (|| -> io::Result<()> {
+                    let mut file = OpenOptions::new().write(true).open(path)?;
+                    file.seek(SeekFrom::Start(4))?;
+                    let mut writer = BufWriter::new(file);
+                    write_blocks(&mut writer, &blocks)?;
+                    writer.into_inner().map_err(|e| e.into_error())?.sync_all()
+                })();
+                return result.map_err(|e| format!("Failed to write tags: {}", e));
+            }
+        }
+    }
+
+    let temp_path = format!("{}.tmp", path);
+    let result = (|| -> io::Result<()> {
+        let mut writer = BufWriter::new(File::create(&temp_path)?);
+        writer.write_all(b"fLaC")?;
+        write_blocks(&mut writer, &blocks)?;
+        io::copy(&mut reader, &mut writer)?;
+        writer.into_inner().map_err(|e| e.into_error())?.sync_all()
+    })();
+
+    match result.and_then(|_| fs::rename(&temp_path, path)) {
+        Ok(()) => Ok(()),
+        Err(e) => {
+            let _ = fs::remove_file(&temp_path);
+            Err(format!("Failed to write tags: {}", e))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A FLAC file with zeroed STREAMINFO, the given comments, `padding` bytes
+    /// of PADDING (if any) and a few bytes standing in for the audio.
+    fn write_flac(name: &str, comments: &[&str], padding: Option<usize>) -> String {
+        let comments: Vec<Vec<u8>> = comments.iter().map(|c| c.as_bytes().to_vec()).collect();
+        let mut blocks = vec![(0, vec![0u8; 34]), (VORBIS_COMMENT, serialize_comments(b"test", &comments))];
+        if let Some(padding) = padding {
+            blocks.push((PADDING, vec![0u8; padding]));
+        }
+        let mut file = b"fLaC".to_vec();
+        write_blocks(&mut file, &blocks).unwrap();
+        file.extend_from_slice(b"AUDIO");
+
+        let path = std::env::temp_dir().join(format!("crate-test-{}-{}.flac", std::process::id(), name));
+        fs::write(&path, file).unwrap();
+        path.to_string_lossy().into_owned()
+    }
+
+    /// Comments of a FLAC file and the bytes after its metadata.
+    fn read_flac(path: &str) -> (Vec<String>, Vec<u8>) {
+        let file = fs::read(path).unwrap();
+        let mut offset = 4;
+        let mut comments = Vec::new();
+        loop {
+            let header = &file[offset..offset + BLOCK_HEADER_LEN];
+            let len = u32::from_be_bytes([0, header[1], header[2], header[3]]) as usize;
+            let data = &file[offset + BLOCK_HEADER_LEN..offset + BLOCK_HEADER_LEN + len];
+            if header[0] & 0x7f == VORBIS_COMMENT {
+                comments = parse_comments(data).unwrap().1.into_iter().map(|c| String::from_utf8(c).unwrap()).collect();
+            }
+            offset += BLOCK_HEADER_LEN + len;
+            if header[0] & 0x80 != 0 {
+                break;
+            }
+        }
+        (comments, file[offset..].to_vec())
+    }
+
+    #[test]
+    fn comment_change_uses_the_padding() {
+        let path = write_flac("padded", &["TITLE=Song", "bpm=90"], Some(64));
+        let size = fs::metadata(&path).unwrap().len();
+
+        write_flac_comment(&path, "BPM", "128").unwrap();
+        let (comments, audio) = read_flac(&path);
+        assert_eq!(comments, ["TITLE=Song", "BPM=128"]);
+        assert_eq!(audio, b"AUDIO");
+        assert_eq!(fs::metadata(&path).unwrap().len(), size);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn comment_change_without_room_rewrites_the_file() {
+        for (name, padding) in [("unpadded", None), ("small-padding", Some(2))] {
+            let path = write_flac(name, &["TITLE=Song"], padding);
+            let size = fs::metadata(&path).unwrap().len();
+
+            write_flac_comment(&path, "INITIALKEY", "8A").unwrap();
+            let (comments, audio) = read_flac(&path);
+            assert_eq!(comments, ["TITLE=Song", "INITIALKEY=8A"]);
+            assert_eq!(audio, b"AUDIO");
+            assert!(fs::metadata(&path).unwrap().len() > size);
+            fs::remove_file(&path).unwrap();
+        }
+    }
+}
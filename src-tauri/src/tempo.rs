@@ -0,0 +1,249 @@
+use crate::decode;
+use realfft::{RealFftPlanner, RealToComplex};
+use std::f32::consts::PI;
+use std::sync::Arc;
+
+/// Onset envelope frames per second; about a 256-sample hop at 44.1 kHz.
+const ENVELOPE_RATE: f32 = 172.0;
+
+const MIN_BPM: f32 = 50.0;
+const MAX_BPM: f32 = 220.0;
+
+/// Center of the prior that settles half and double tempo ambiguity.
+const PRIOR_BPM: f32 = 120.0;
+
+/// Width of that prior, in octaves.
+const PRIOR_OCTAVES: f32 = 1.0;
+
+/// Multiples of the beat period scored together for each candidate tempo.
+const HARMONICS: usize = 4;
+
+/// Step of the candidate beat periods, in envelope frames.
+const LAG_STEP: f32 = 0.02;
+
+/// Span of the moving average removed from the onset envelope.
+const MEAN_WINDOW_SECS: f32 = 0.5;
+
+/// Upper edge of the band whose onsets (mostly kick drums) place the beats.
+const BASS_HZ: f32 = 150.0;
+
+/// Delay between a note onset and the envelope frame where it peaks, in
+/// hops: the onset has to travel into the analysis window first.
+const ONSET_DELAY: f32 = 3.0;
+
+/// Shortest track worth estimating, in beat periods at the slowest tempo.
+const MIN_PERIODS: usize = 8;
+
+#[derive(Debug, Clone, Copy, serde::Serialize, serde::Deserialize)]
+pub struct TempoEstimate {
+    pub bpm: f32,
+    /// Strength of the beat periodicity, from 0 to 1.
+    pub confidence: f32,
+    /// Position of the first beat, in seconds; later beats follow every 60 / `bpm` seconds.
+    /// Missing from estimates stored before beat positions were analyzed.
+    pub offset: Option<f32>,
+}
+
+/// Spectral flux of a mono signal, one value per hop, over the whole
+/// spectrum and over the bass band.
+struct OnsetDetector {
+    hop: usize,
+    bass_bins: usize,
+    fft: Arc<dyn RealToComplex<f32>>,
+    window: Vec<f32>,
+    input: Vec<f32>,
+    spectrum: Vec<realfft::num_complex::Complex<f32>>,
+    scratch: Vec<realfft::num_complex::Complex<f32>>,
+    previous: Vec<f32>,
+    buffer: Vec<f32>,
+    envelope: Vec<f32>,
+    bass: Vec<f32>,
+}
+
+impl OnsetDetector {
+    fn new(sample_rate: u32) -> Self {
+        let hop = ((sample_rate as f32 / ENVELOPE_RATE).round() as usize).max(1);
+        let size = hop * 4;
+        let fft = RealFftPlanner::<f32>::new().plan_fft_forward(size);
+        let window_gain = size as f32 / 4.0;
+        Self {
+            hop,
+            bass_bins: ((BASS_HZ * size as f32 / sample_rate as f32) as usize).max(1) + 1,
+            window: (0..size).map(|i| (0.5 - 0.5 * (2.0 * PI * i as f32 / size as f32).cos()) / window_gain).collect(),
+            input: fft.make_input_vec(),
+            spectrum: fft.make_output_vec(),
+            scratch: fft.make_scratch_vec(),
+            previous: vec![0.0; size / 2 + 1],
+            buffer: Vec::with_capacity(size + hop),
+            envelope: Vec::new(),
+            bass: Vec::new(),
+            fft,
+        }
+    }
+
+    fn push(&mut self, sample: f32) {
+        self.buffer.push(sample);
+        if self.buffer.len() < self.window.len() {
+            return;
+        }
+
+        for ((input, sample), weight) in self.input.iter_mut().zip(&self.buffer).zip(&self.window) {
+            *input = sample * weight;
+        }
+        let _ = self.fft.process_with_scratch(&mut self.input, &mut self.spectrum, &mut self.scratch);
+
+        // Log compression keeps loud sustained notes from drowning out quieter attacks
+        let (mut flux, mut bass) = (0.0, 0.0);
+        for (index, (bin, previous)) in self.spectrum.iter().zip(self.previous.iter_mut()).enumerate() {
+            let magnitude = (1.0 + 1000.0 * bin.norm()).ln();
+            let rise = (magnitude - *previous).max(0.0);
+            flux += rise;
+            if index < self.bass_bins {
+                bass += rise;
+            }
+            *previous = magnitude;
+        }
+        self.envelope.push(flux);
+        self.bass.push(bass);
+        self.buffer.drain(..self.hop);
+    }
+
+    /// Both envelopes with their local mean removed, keeping only the rises above it.
+    fn finish(self, rate: f32) -> (Vec<f32>, Vec<f32>) {
+        let radius = (MEAN_WINDOW_SECS * rate / 2.0) as usize;
+        (rectify(&self.envelope, radius), rectify(&self.bass, radius))
+    }
+}
+
+fn rectify(envelope: &[f32], radius: usize) -> Vec<f32> {
+    let mut sums = Vec::with_capacity(envelope.len() + 1);
+    sums.push(0.0f64);
+    for value in envelope {
+        sums.push(sums.last().unwrap() + *value as f64);
+    }
+
+    (0..envelope.len())
+        .map(|i| {
+            let start = i.saturating_sub(radius);
+            let end = (i + radius + 1).min(envelope.len());
+            let mean = (sums[end] - sums[start]) / (end - start) as f64;
+            (envelope[i] - mean as f32).max(0.0)
+        })
+        .collect()
+}
+
+/// Value of `values` at a fractional index, by linear interpolation.
+fn interpolate(values: &[f32], index: f32) -> f32 {
+    let low = index as usize;
+    let fraction = index - low as f32;
+    match (values.get(low), values.get(low + 1)) {
+        (Some(a), Some(b)) => a + (b - a) * fraction,
+        (Some(a), None) => *a,
+        _ => 0.0,
+    }
+}
+
+/// Estimates the tempo of `path` from the autocorrelation of its onset envelope.
+pub fn detect_tempo(path: &str) -> Result<TempoEstimate, String> {
+    let mut detector: Option<OnsetDetector> = None;
+    let info = decode::decode_interleaved(path, |info, samples| {
+        let detector = detector.get_or_insert_with(|| OnsetDetector::new(info.sample_rate));
+        for frame in samples.chunks_exact(info.channels) {
+            detector.push(frame.iter().sum::<f32>() / info.channels as f32);
+        }
+        true
+    })?;
+
+    let detector = detector.ok_or("Track is empty")?;
+    let rate = info.sample_rate as f32 / detector.hop as f32;
+    let (envelope, bass) = detector.finish(rate);
+
+    let min_lag = 60.0 * rate / MAX_BPM;
+    let max_lag = 60.0 * rate / MIN_BPM;
+    if envelope.len() < max_lag as usize * MIN_PERIODS {
+        return Err("Track is too short to estimate its tempo".to_string());
+    }
+
+    // Unbiased autocorrelation, far enough out to cover every harmonic of the slowest tempo
+    let lags = ((max_lag * HARMONICS as f32).ceil() as usize + 2).min(envelope.len() - 1);
+    let autocorrelation: Vec<f32> = (0..lags)
+        .map(|lag| {
+            let sum: f64 = envelope.iter().zip(&envelope[lag..]).map(|(a, b)| (*a * *b) as f64).sum();
+            (sum / (envelope.len() - lag) as f64) as f32
+        })
+        .collect();
+
+    if autocorrelation[0] <= f32::EPSILON {
+        return Err("No rhythmic content found".to_string());
+    }
+
+    // A beat period repeats at its multiples, so score each candidate on all of them
+    let score = |lag: f32| (1..=HARMONICS).map(|k| interpolate(&autocorrelation, lag * k as f32)).sum::<f32>() / HARMONICS as f32;
+    let prior = |lag: f32| {
+        let octaves = (60.0 * rate / lag / PRIOR_BPM).log2() / PRIOR_OCTAVES;
+        (-0.5 * octaves * octaves).exp()
+    };
+
+    let steps = ((max_lag - min_lag) / LAG_STEP) as usize;
+    let (lag, strength) = (0..=steps)
+        .map(|step| min_lag + step as f32 * LAG_STEP)
+        .map(|lag| (lag, score(lag)))
+        .max_by(|a, b| (a.1 * prior(a.0)).total_cmp(&(b.1 * prior(b.0))))
+        .ok_or("No tempo candidates")?;
+
+    // Place the beat grid where it collects the most onset strength, preferring
+    // the bass band so off-beat hi-hats don't pull it off the kicks
+    let grid = if bass.iter().any(|value| *value > 0.0) { &bass } else { &envelope };
+    let phase = (0..lag.ceil() as usize)
+        .map(|start| {
+            let beats = ((grid.len() - start) as f32 / lag) as usize;
+            let strength: f32 = (0..beats).map(|beat| interpolate(grid, start as f32 + beat as f32 * lag)).sum();
+            (start, strength)
+        })
+        .max_by(|a, b| a.1.total_cmp(&b.1))
+        .map(|(start, _)| start)
+        .unwrap_or(0);
+
+    Ok(TempoEstimate {
+        bpm: 60.0 * rate / lag,
+        confidence: (strength / autocorrelation[0]).clamp(0.0, 1.0),
+        offset: Some(((phase as f32 + ONSET_DELAY) / rate) % (lag / rate)),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::write_wav;
+
+    /// Decaying 60 Hz thumps every beat from `offset` seconds on.
+    fn click_track(bpm: f32, offset: f32, seconds: f32, sample_rate: u32) -> Vec<f32> {
+        let period = 60.0 / bpm;
+        (0..(seconds * sample_rate as f32) as usize)
+            .map(|i| {
+                let t = i as f32 / sample_rate as f32 - offset;
+                if t < 0.0 {
+                    return 0.0;
+                }
+                let since = t % period;
+                0.8 * (-since * 40.0).exp() * (2.0 * PI * 60.0 * since).sin()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn click_track_tempo_and_first_beat() {
+        for (name, bpm, offset) in [("clicks-128", 128.0f32, 0.2f32), ("clicks-93", 93.0, 0.45)] {
+            let path = write_wav(name, 44100, 1, &click_track(bpm, offset, 30.0, 44100));
+            let tempo = detect_tempo(path.to_str().unwrap()).unwrap();
+            std::fs::remove_file(&path).unwrap();
+
+            assert!((tempo.bpm - bpm).abs() < 0.5, "{} BPM detected as {}", bpm, tempo.bpm);
+            // The grid may start a whole period early or late, and lands within a
+            // few envelope hops of the clicks
+            let period = 60.0 / bpm;
+            let error = (tempo.offset.unwrap() - offset).rem_euclid(period);
+            assert!(error.min(period - error) < 0.03, "first beat at {} instead of {}", tempo.offset.unwrap(), offset);
+        }
+    }
+}
@@ -0,0 +1,47 @@
+use std::f32::consts::PI;
+use std::path::PathBuf;
+
+/// Writes interleaved samples as a 16-bit PCM WAV file in the temp
+/// directory and returns its path. `name` keeps concurrent tests apart.
+pub fn write_wav(name: &str, sample_rate: u32, channels: u16, samples: &[f32]) -> PathBuf {
+    write_wav_bits(name, sample_rate, channels, 16, samples)
+}
+
+/// Like `write_wav`, with 16, 24 or 32-bit samples. Full scale is
+/// 2^(bits - 1), so a sample of k / 32768 is stored as exactly k shifted up.
+pub fn write_wav_bits(name: &str, sample_rate: u32, channels: u16, bits_per_sample: u16, samples: &[f32]) -> PathBuf {
+    let bytes = bits_per_sample as usize / 8;
+    let scale = (1i64 << (bits_per_sample - 1)) as f64;
+    let mut data = Vec::with_capacity(samples.len() * bytes);
+    for sample in samples {
+        let value = (*sample as f64 * scale).round().clamp(-scale, scale - 1.0) as i32;
+        data.extend_from_slice(&value.to_le_bytes()[..bytes]);
+    }
+
+    let block_align = channels * bytes as u16;
+    let mut wav = Vec::with_capacity(44 + data.len());
+    wav.extend_from_slice(b"RIFF");
+    wav.extend_from_slice(&(36 + data.len() as u32).to_le_bytes());
+    wav.extend_from_slice(b"WAVEfmt ");
+    wav.extend_from_slice(&16u32.to_le_bytes());
+    wav.extend_from_slice(&1u16.to_le_bytes());
+    wav.extend_from_slice(&channels.to_le_bytes());
+    wav.extend_from_slice(&sample_rate.to_le_bytes());
+    wav.extend_from_slice(&(sample_rate * block_align as u32).to_le_bytes());
+    wav.extend_from_slice(&block_align.to_le_bytes());
+    wav.extend_from_slice(&bits_per_sample.to_le_bytes());
+    wav.extend_from_slice(b"data");
+    wav.extend_from_slice(&(data.len() as u32).to_le_bytes());
+    wav.extend_from_slice(&data);
+
+    let path = std::env::temp_dir().join(format!("crate-test-{}-{}.wav", std::process::id(), name));
+    std::fs::write(&path, wav).unwrap();
+    path
+}
+
+/// A mono sine at `frequency`, `seconds` long.
+pub fn sine(frequency: f32, sample_rate: u32, seconds: f32, amplitude: f32) -> Vec<f32> {
+    (0..(seconds * sample_rate as f32) as usize)
+        .map(|i| amplitude * (2.0 * PI * frequency * i as f32 / sample_rate as f32).sin())
+        .collect()
+}
@@ -0,0 +1,289 @@
+use rodio::source::SeekError;
+use rodio::Source;
+use std::f32::consts::PI;
+use std::sync::atomic::{AtomicU32, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Length of the overlapping segments, in seconds.
+const SEGMENT_SECS: f32 = 0.046;
+
+/// How far a segment may move from its nominal position to line up with the
+/// previous one, in seconds.
+const SEARCH_SECS: f32 = 0.010;
+
+/// Step of the coarse similarity search, in frames; the best match is then refined frame by frame.
+const COARSE_STEP: usize = 4;
+
+/// Playback rate of a `TimeStretch`, shared with the thread that steers it,
+/// and how far into its source the stretched output has got.
+#[derive(Clone)]
+pub struct StretchControl {
+    rate: Arc<AtomicU32>,
+    /// Source position in seconds, stored as f64 bits.
+    position: Arc<AtomicU64>,
+}
+
+impl StretchControl {
+    pub fn new(rate: f32) -> Self {
+        Self {
+            rate: Arc::new(AtomicU32::new(rate.to_bits())),
+            position: Arc::new(AtomicU64::new(0f64.to_bits())),
+        }
+    }
+
+    /// Sets the tempo relative to the original; 1.05 plays 5% faster at the same pitch.
+    pub fn set_rate(&self, rate: f32) {
+        self.rate.store(rate.to_bits(), Ordering::Relaxed);
+    }
+
+    pub fn rate(&self) -> f32 {
+        f32::from_bits(self.rate.load(Ordering::Relaxed))
+    }
+
+    /// Position in the source of the audio produced so far, in seconds. Unlike
+    /// the output time, this follows the source through seeks and tempo changes.
+    pub fn position(&self) -> f64 {
+        f64::from_bits(self.position.load(Ordering::Relaxed))
+    }
+
+    fn set_position(&self, seconds: f64) {
+        self.position.store(seconds.to_bits(), Ordering::Relaxed);
+    }
+}
+
+/// Changes the tempo of a source without changing its pitch, using WSOLA:
+/// segments are taken from the input at the stretched rate, each shifted
+/// slightly to where it best continues the previous one, and overlap-added.
+pub struct TimeStretch<S: Source<Item = f32>> {
+    source: S,
+    control: StretchControl,
+    channels: usize,
+    sample_rate: u32,
+    /// Segment length in frames; segments overlap by half.
+    segment: usize,
+    search: usize,
+    window: Vec<f32>,
+    /// Interleaved input not yet consumed.
+    input: Vec<f32>,
+    /// Nominal position of the next segment, in frames from the start of `input`.
+    position: f64,
+    /// Where the previous segment continues naturally, in frames from the start of `input`.
+    natural: Option<usize>,
+    /// Overlap-add accumulator, one segment long.
+    output: Vec<f32>,
+    /// Finished samples at the front of `output` and the read position in them.
+    ready: usize,
+    cursor: usize,
+    /// Frames of `input` holding actual audio, once the source has ended; the rest is padding.
+    end: Option<usize>,
+    /// Source frames covered by the output produced so far.
+    played: f64,
+}
+
+impl<S: Source<Item = f32>> TimeStretch<S> {
+    pub fn new(source: S, control: StretchControl) -> Self {
+        let channels = source.channels().max(1) as usize;
+        let sample_rate = source.sample_rate();
+        let segment = ((SEGMENT_SECS * sample_rate as f32) as usize / 2 * 2).max(64);
+        Self {
+            channels,
+            sample_rate,
+            segment,
+            search: (SEARCH_SECS * sample_rate as f32) as usize,
+            // Periodic Hann windows at 50% overlap sum to exactly one
+            window: (0..segment).map(|i| 0.5 - 0.5 * (2.0 * PI * i as f32 / segment as f32).cos()).collect(),
+            input: Vec::new(),
+            position: 0.0,
+            natural: None,
+            output: vec![0.0; segment * channels],
+            ready: 0,
+            cursor: 0,
+            end: None,
+            played: 0.0,
+            source,
+            control,
+        }
+    }
+
+    fn input_frames(&self) -> usize {
+        self.input.len() / self.channels
+    }
+
+    /// Buffers `frames` frames of input, padding with silence past the end of the source.
+    fn fill(&mut self, frames: usize) {
+        while self.end.is_none() && self.input_frames() < frames {
+            match self.source.next() {
+                Some(sample) => self.input.push(sample),
+                None => self.end = Some(self.input_frames()),
+            }
+        }
+        self.input.resize(self.input.len().max(frames * self.channels), 0.0);
+    }
+
+    /// Similarity of the mono mixes of the segments starting at frames `a` and `b`.
+    fn similarity(&self, a: usize, b: usize) -> f32 {
+        let overlap = self.segment / 2;
+        let frames = |start: usize| self.input[start * self.channels..(start + overlap) * self.channels].chunks_exact(self.channels);
+        frames(a).zip(frames(b)).map(|(x, y)| x.iter().sum::<f32>() * y.iter().sum::<f32>()).sum()
+    }
+
+    /// Produces the next half segment of output. Returns false once the source
+    /// has been played out.
+    fn process(&mut self) -> bool {
+        let half = self.segment / 2;
+        let rate = self.control.rate().clamp(0.5, 2.0) as f64;
+
+        let start = match self.natural {
+            None => self.position as usize,
+            // At the original tempo the natural continuation is exact
+            Some(natural) if rate == 1.0 => natural,
+            Some(natural) => {
+                let nominal = self.position as usize;
+                let low = nominal.saturating_sub(self.search);
+                let high = nominal + self.search;
+                self.fill(high.max(natural) + self.segment);
+
+                let best = |candidates: &mut dyn Iterator<Item = usize>| {
+                    candidates
+                        .map(|candidate| (candidate, self.similarity(candidate, natural)))
+                        .max_by(|a, b| a.1.total_cmp(&b.1))
+                        .map(|(candidate, _)| candidate)
+                        .unwrap_or(nominal)
+                };
+                let coarse = best(&mut (low..=high).step_by(COARSE_STEP));
+                best(&mut (coarse.saturating_sub(COARSE_STEP - 1).max(low)..=(coarse + COARSE_STEP - 1).min(high)))
+            }
+        };
+
+        self.fill(start + self.segment);
+        if self.end.is_some_and(|end| start >= end) {
+            return false;
+        }
+
+        // Shift out the half that was played and add the new segment
+        self.output.copy_within(half * self.channels.., 0);
+        self.output[half * self.channels..].fill(0.0);
+        let segment = &self.input[start * self.channels..(start + self.segment) * self.channels];
+        for (i, frame) in segment.chunks_exact(self.channels).enumerate() {
+            for (channel, sample) in frame.iter().enumerate() {
+                self.output[i * self.channels + channel] += sample * self.window[i];
+            }
+        }
+        self.ready = half * self.channels;
+        self.cursor = 0;
+        self.played += half as f64 * rate;
+        self.control.set_position(self.played / self.sample_rate as f64);
+
+        self.position = if rate == 1.0 { (start + half) as f64 } else { self.position + half as f64 * rate };
+        let natural = start + half;
+        self.natural = Some(natural);
+
+        // Drop input no later segment can reach
+        let keep_from = natural.min((self.position as usize).saturating_sub(self.search));
+        if keep_from > 0 {
+            self.input.drain(..keep_from * self.channels);
+            self.position -= keep_from as f64;
+            self.natural = Some(natural - keep_from);
+            self.end = self.end.map(|end| end.saturating_sub(keep_from));
+        }
+        true
+    }
+}
+
+impl<S: Source<Item = f32>> Iterator for TimeStretch<S> {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        if self.cursor == self.ready && !self.process() {
+            return None;
+        }
+        let sample = self.output[self.cursor];
+        self.cursor += 1;
+        Some(sample)
+    }
+}
+
+impl<S: Source<Item = f32>> Source for TimeStretch<S> {
+    fn current_frame_len(&self) -> Option<usize> {
+        None
+    }
+
+    fn channels(&self) -> u16 {
+        self.channels as u16
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        None
+    }
+
+    fn try_seek(&mut self, pos: Duration) -> Result<(), SeekError> {
+        self.source.try_seek(pos)?;
+        self.input.clear();
+        self.output.fill(0.0);
+        self.position = 0.0;
+        self.natural = None;
+        self.ready = 0;
+        self.cursor = 0;
+        self.end = None;
+        self.played = pos.as_secs_f64() * self.sample_rate as f64;
+        self.control.set_position(pos.as_secs_f64());
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rodio::buffer::SamplesBuffer;
+
+    /// Stereo 440 Hz tone, `seconds` long at 44.1 kHz.
+    fn tone(seconds: f32) -> SamplesBuffer<f32> {
+        let samples: Vec<f32> = (0..(seconds * 44100.0) as usize)
+            .flat_map(|i| {
+                let sample = 0.3 * (2.0 * PI * 440.0 * i as f32 / 44100.0).sin();
+                [sample, sample]
+            })
+            .collect();
+        SamplesBuffer::new(2, 44100, samples)
+    }
+
+    #[test]
+    fn output_length_follows_the_rate() {
+        for rate in [0.9f32, 1.0, 1.08] {
+            let control = StretchControl::new(rate);
+            let frames = TimeStretch::new(tone(2.0), control.clone()).count() / 2;
+            let expected = 2.0 * 44100.0 / rate;
+            // Within one segment of the exact length
+            assert!((frames as f32 - expected).abs() < 44100.0 * SEGMENT_SECS, "rate {}: {} frames, expected {}", rate, frames, expected);
+            assert!((control.position() - 2.0).abs() < SEGMENT_SECS as f64, "rate {}: ended at {}", rate, control.position());
+        }
+    }
+
+    #[test]
+    fn unity_rate_reproduces_the_input() {
+        let input: Vec<f32> = tone(1.0).collect();
+        let output: Vec<f32> = TimeStretch::new(tone(1.0), StretchControl::new(1.0)).collect();
+        // The first half segment fades in from the Hann window
+        let skip = 44100.0 * SEGMENT_SECS;
+        for (out, original) in output.iter().zip(&input).skip(skip as usize * 2) {
+            assert!((out - original).abs() < 1e-5);
+        }
+    }
+
+    #[test]
+    fn position_tracks_the_source_through_seeks() {
+        let control = StretchControl::new(1.1);
+        let mut stretch = TimeStretch::new(tone(4.0), control.clone());
+        stretch.try_seek(Duration::from_secs(2)).unwrap();
+        assert_eq!(control.position(), 2.0);
+
+        // One second of output covers 1.1 seconds of the source
+        stretch.by_ref().take(44100 * 2).for_each(drop);
+        assert!((control.position() - 3.1).abs() < SEGMENT_SECS as f64, "at {}", control.position());
+    }
+}
@@ -0,0 +1,99 @@
+use crate::musical_key::KeyEstimate;
+use crate::tempo::TempoEstimate;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+use std::time::UNIX_EPOCH;
+
+/// Analysis results for one file, valid as long as the file is unchanged.
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct TrackAnalysis {
+    /// Modification time of the file when it was analyzed, in milliseconds since the epoch.
+    pub modified: u64,
+    pub tempo: Option<TempoEstimate>,
+    pub key: Option<KeyEstimate>,
+}
+
+/// Modification time of `path` in milliseconds since the epoch.
+pub fn modified_time(path: &str) -> Result<u64, String> {
+    Ok(fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .map_err(|e| format!("Failed to read file: {}", e))?
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0))
+}
+
+/// Per-track analysis results persisted as JSON in the app data directory,
+/// keyed by file path.
+#[derive(Default)]
+pub struct TrackAnalysisStore {
+    path: Option<PathBuf>,
+    tracks: BTreeMap<String, TrackAnalysis>,
+}
+
+impl TrackAnalysisStore {
+    pub fn load(&mut self, path: PathBuf) {
+        if let Ok(content) = fs::read_to_string(&path) {
+            match serde_json::from_str(&content) {
+                Ok(tracks) => self.tracks = tracks,
+                Err(e) => eprintln!("Failed to read track analysis: {}", e),
+            }
+        }
+        self.path = Some(path);
+    }
+
+    pub fn save(&self) -> Result<(), String> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).map_err(|e| format!("Failed to create analysis directory: {}", e))?;
+        }
+        let content = serde_json::to_string(&self.tracks).map_err(|e| format!("Failed to serialize track analysis: {}", e))?;
+        fs::write(path, content).map_err(|e| format!("Failed to save track analysis: {}", e))
+    }
+
+    /// Stored results for `path`, unless the file changed since it was analyzed.
+    pub fn get(&self, path: &str) -> Option<TrackAnalysis> {
+        let analysis = self.tracks.get(path)?;
+        (modified_time(path).ok()? == analysis.modified).then(|| analysis.clone())
+    }
+
+    /// Results for the current version of `path`; those from an older
+    /// version of the file are dropped.
+    fn entry(&mut self, path: &str) -> Result<&mut TrackAnalysis, String> {
+        let modified = modified_time(path)?;
+        let analysis = self.tracks.entry(path.to_string()).or_default();
+        if analysis.modified != modified {
+            *analysis = TrackAnalysis {
+                modified,
+                ..Default::default()
+            };
+        }
+        Ok(analysis)
+    }
+
+    /// Records the tempo of `path` without saving.
+    pub fn set_tempo(&mut self, path: &str, tempo: TempoEstimate) -> Result<(), String> {
+        self.entry(path)?.tempo = Some(tempo);
+        Ok(())
+    }
+
+    /// Records the key of `path` without saving.
+    pub fn set_key(&mut self, path: &str, key: KeyEstimate) -> Result<(), String> {
+        self.entry(path)?.key = Some(key);
+        Ok(())
+    }
+
+    /// Takes note of a tag write to `path`, which changes its modification
+    /// time but not the audio the results describe.
+    pub fn refresh(&mut self, path: &str) -> Result<(), String> {
+        let modified = modified_time(path)?;
+        if let Some(analysis) = self.tracks.get_mut(path) {
+            analysis.modified = modified;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,170 @@
+use crate::decode;
+use symphonia::core::codecs::DecoderOptions;
+use symphonia::core::errors::Error;
+
+/// Decode errors listed per file; further ones are only counted.
+const MAX_REPORTED_ERRORS: usize = 20;
+
+/// Shortfall tolerated, as a fraction of the length in the header, since MP3
+/// headers only estimate it.
+const LENGTH_TOLERANCE: f64 = 0.005;
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct DecodeFault {
+    /// Position in the track, in seconds.
+    pub position: f64,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct VerifyReport {
+    pub path: String,
+    pub ok: bool,
+    pub decode_errors: usize,
+    /// The first decode errors, with their positions.
+    pub errors: Vec<DecodeFault>,
+    /// Error that stopped decoding early, or that kept the file from opening.
+    pub fatal: Option<DecodeFault>,
+    pub decoded_frames: u64,
+    pub expected_frames: Option<u64>,
+    /// Result of the embedded MD5 check, for FLAC files that carry one.
+    pub md5_ok: Option<bool>,
+}
+
+impl VerifyReport {
+    fn new(path: &str) -> Self {
+        Self {
+            path: path.to_string(),
+            ok: false,
+            decode_errors: 0,
+            errors: Vec::new(),
+            fatal: None,
+            decoded_frames: 0,
+            expected_frames: None,
+            md5_ok: None,
+        }
+    }
+}
+
+/// Decodes `path` from start to end and records everything that went wrong.
+pub fn verify_file(path: &str) -> VerifyReport {
+    let mut report = VerifyReport::new(path);
+
+    let (mut format, mut decoder, track_id) = match decode::open_track_with_options(path, &DecoderOptions { verify: true }) {
+        Ok(track) => track,
+        Err(e) => {
+            report.fatal = Some(DecodeFault { position: 0.0, message: e });
+            return report;
+        }
+    };
+
+    let params = decoder.codec_params().clone();
+    let sample_rate = params.sample_rate.unwrap_or(44100) as f64;
+    report.expected_frames = params.n_frames;
+    let position = |frames: u64| frames as f64 / sample_rate;
+
+    loop {
+        let packet = match format.next_packet() {
+            Ok(packet) => packet,
+            Err(Error::IoError(err)) if err.kind() == std::io::ErrorKind::UnexpectedEof => break,
+            Err(Error::ResetRequired) => break,
+            Err(err) => {
+                report.fatal = Some(DecodeFault {
+                    position: position(report.decoded_frames),
+                    message: format!("Failed to read packet: {}", err),
+                });
+                break;
+            }
+        };
+        if packet.track_id() != track_id {
+            continue;
+        }
+
+        match decoder.decode(&packet) {
+            Ok(audio_buf) => report.decoded_frames += audio_buf.frames() as u64,
+            Err(Error::DecodeError(err)) => {
+                report.decode_errors += 1;
+                if report.errors.len() < MAX_REPORTED_ERRORS {
+                    let at = params.time_base.map(|base| {
+                        let time = base.calc_time(packet.ts());
+                        time.seconds as f64 + time.frac
+                    });
+                    report.errors.push(DecodeFault {
+                        position: at.unwrap_or_else(|| position(report.decoded_frames)),
+                        message: err.to_string(),
+                    });
+                }
+            }
+            Err(err) => {
+                report.fatal = Some(DecodeFault {
+                    position: position(report.decoded_frames),
+                    message: format!("Decoder error: {}", err),
+                });
+                break;
+            }
+        }
+    }
+
+    // A file cut short usually just ends early, so compare against the length the header promises
+    if let Some(expected) = report.expected_frames {
+        let tolerance = (expected as f64 * LENGTH_TOLERANCE) as u64;
+        if report.fatal.is_none() && report.decoded_frames + tolerance < expected {
+            report.fatal = Some(DecodeFault {
+                position: position(report.decoded_frames),
+                message: format!("File ends after {} of {} frames", report.decoded_frames, expected),
+            });
+        }
+    }
+
+    // The MD5 covers the whole stream, so it only means something after a complete decode
+    if report.fatal.is_none() && report.decode_errors == 0 {
+        report.md5_ok = decoder.finalize().verify_ok;
+    }
+
+    report.ok = report.fatal.is_none() && report.decode_errors == 0 && report.md5_ok != Some(false);
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{sine, write_wav};
+    use std::fs::OpenOptions;
+
+    #[test]
+    fn intact_file_passes() {
+        let path = write_wav("verify-intact", 44100, 1, &sine(440.0, 44100, 1.0, 0.5));
+        let report = verify_file(path.to_str().unwrap());
+        std::fs::remove_file(&path).unwrap();
+
+        assert!(report.ok, "{:?}", report);
+        assert_eq!(report.decoded_frames, 44100);
+        assert_eq!(report.expected_frames, Some(44100));
+    }
+
+    #[test]
+    fn truncated_file_is_reported() {
+        let path = write_wav("verify-truncated", 44100, 1, &sine(440.0, 44100, 1.0, 0.5));
+        // Cut the data chunk in half, leaving the header's length alone
+        OpenOptions::new().write(true).open(&path).unwrap().set_len(44 + 44100).unwrap();
+        let report = verify_file(path.to_str().unwrap());
+        std::fs::remove_file(&path).unwrap();
+
+        assert!(!report.ok);
+        assert_eq!(report.decoded_frames, 22050);
+        let fatal = report.fatal.unwrap();
+        assert!(fatal.message.contains("22050 of 44100"), "{}", fatal.message);
+        assert!((fatal.position - 0.5).abs() < 1e-9);
+    }
+
+    #[test]
+    fn unreadable_file_is_fatal() {
+        let path = std::env::temp_dir().join(format!("crate-test-{}-verify-garbage.wav", std::process::id()));
+        std::fs::write(&path, b"not audio at all").unwrap();
+        let report = verify_file(path.to_str().unwrap());
+        std::fs::remove_file(&path).unwrap();
+
+        assert!(!report.ok);
+        assert_eq!(report.fatal.unwrap().position, 0.0);
+    }
+}
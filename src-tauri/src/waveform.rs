@@ -0,0 +1,322 @@
+use crate::decode;
+use std::collections::hash_map::DefaultHasher;
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::UNIX_EPOCH;
+use tokio::sync::Semaphore;
+
+const MIN_RESOLUTION: usize = 16;
+const MAX_RESOLUTION: usize = 65536;
+
+/// Frames per block when the track length is unknown; blocks are spread over
+/// the buckets once decoding finishes.
+const BLOCK_FRAMES: usize = 1024;
+
+/// Number of partial updates sent while a waveform is computed.
+const PROGRESS_STEPS: usize = 50;
+
+/// Min/max/RMS overview of a track, one value per bucket, across all channels.
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
+pub struct Waveform {
+    pub duration: f64,
+    pub min: Vec<f32>,
+    pub max: Vec<f32>,
+    pub rms: Vec<f32>,
+}
+
+/// Buckets `offset..offset + min.len()` of a waveform still being computed.
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct WaveformChunk {
+    pub path: String,
+    pub resolution: usize,
+    pub offset: usize,
+    pub min: Vec<f32>,
+    pub max: Vec<f32>,
+    pub rms: Vec<f32>,
+}
+
+#[derive(Clone, Copy)]
+struct Bucket {
+    min: f32,
+    max: f32,
+    sum_squares: f64,
+    samples: u64,
+}
+
+impl Default for Bucket {
+    fn default() -> Self {
+        Self {
+            min: f32::INFINITY,
+            max: f32::NEG_INFINITY,
+            sum_squares: 0.0,
+            samples: 0,
+        }
+    }
+}
+
+impl Bucket {
+    fn add(&mut self, sample: f32) {
+        self.min = self.min.min(sample);
+        self.max = self.max.max(sample);
+        self.sum_squares += (sample as f64).powi(2);
+        self.samples += 1;
+    }
+
+    fn merge(&mut self, other: &Bucket) {
+        self.min = self.min.min(other.min);
+        self.max = self.max.max(other.max);
+        self.sum_squares += other.sum_squares;
+        self.samples += other.samples;
+    }
+
+    /// Lowest sample, or 0 for a bucket no samples fell into.
+    fn low(&self) -> f32 {
+        if self.samples == 0 {
+            0.0
+        } else {
+            self.min
+        }
+    }
+
+    fn high(&self) -> f32 {
+        if self.samples == 0 {
+            0.0
+        } else {
+            self.max
+        }
+    }
+
+    fn rms(&self) -> f32 {
+        if self.samples == 0 {
+            0.0
+        } else {
+            (self.sum_squares / self.samples as f64).sqrt() as f32
+        }
+    }
+}
+
+/// Merges equal-length blocks covering a whole track into `buckets`. A block
+/// spanning several buckets goes into each of them, so short tracks leave no
+/// bucket empty.
+fn spread_blocks(blocks: &[Bucket], buckets: &mut [Bucket]) {
+    let (count, resolution) = (blocks.len(), buckets.len());
+    for (index, block) in blocks.iter().enumerate() {
+        let start = index * resolution / count;
+        let end = ((index + 1) * resolution / count).max(start + 1);
+        buckets[start..end].iter_mut().for_each(|bucket| bucket.merge(block));
+    }
+}
+
+fn chunk(path: &str, resolution: usize, offset: usize, buckets: &[Bucket]) -> WaveformChunk {
+    WaveformChunk {
+        path: path.to_string(),
+        resolution,
+        offset,
+        min: buckets.iter().map(Bucket::low).collect(),
+        max: buckets.iter().map(Bucket::high).collect(),
+        rms: buckets.iter().map(Bucket::rms).collect(),
+    }
+}
+
+/// Decodes `path` and reduces it to `resolution` buckets. When the track
+/// length is known up front, finished buckets are handed to `on_chunk` as
+/// decoding progresses; otherwise bucket boundaries aren't known until the
+/// end, and all buckets are handed over at once.
+pub fn compute_waveform(path: &str, resolution: usize, mut on_chunk: impl FnMut(WaveformChunk)) -> Result<Waveform, String> {
+    let mut buckets = vec![Bucket::default(); resolution];
+    let mut blocks: Vec<Bucket> = Vec::new();
+    let mut frame = 0u64;
+    let mut sent = 0usize;
+    let step = (resolution / PROGRESS_STEPS).max(1);
+
+    let info = decode::decode_interleaved(path, |info, samples| {
+        for values in samples.chunks_exact(info.channels) {
+            let bucket = match info.frames {
+                Some(total) if total > 0 => &mut buckets[((frame * resolution as u64 / total) as usize).min(resolution - 1)],
+                _ => {
+                    if frame.is_multiple_of(BLOCK_FRAMES as u64) {
+                        blocks.push(Bucket::default());
+                    }
+                    blocks.last_mut().unwrap()
+                }
+            };
+            values.iter().for_each(|sample| bucket.add(*sample));
+            frame += 1;
+        }
+
+        if let Some(total) = info.frames.filter(|total| *total > 0) {
+            let finished = ((frame * resolution as u64 / total) as usize).min(resolution);
+            if finished >= sent + step {
+                on_chunk(chunk(path, resolution, sent, &buckets[sent..finished]));
+                sent = finished;
+            }
+        }
+        true
+    })?;
+
+    if !blocks.is_empty() {
+        spread_blocks(&blocks, &mut buckets);
+    }
+    if sent < resolution {
+        on_chunk(chunk(path, resolution, sent, &buckets[sent..]));
+    }
+
+    let finished = chunk(path, resolution, 0, &buckets);
+    Ok(Waveform {
+        duration: frame as f64 / info.sample_rate as f64,
+        min: finished.min,
+        max: finished.max,
+        rms: finished.rms,
+    })
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
+struct CachedWaveform {
+    path: String,
+    modified: u64,
+    resolution: usize,
+    waveform: Waveform,
+}
+
+/// Computes waveforms on a bounded pool of blocking workers and caches them on
+/// disk, keyed by path, modification time and resolution.
+pub struct WaveformService {
+    cache_dir: Mutex<Option<PathBuf>>,
+    workers: Semaphore,
+}
+
+impl Default for WaveformService {
+    fn default() -> Self {
+        let workers = std::thread::available_parallelism().map(|n| n.get()).unwrap_or(2);
+        Self {
+            cache_dir: Mutex::new(None),
+            workers: Semaphore::new(workers),
+        }
+    }
+}
+
+impl WaveformService {
+    pub fn set_cache_dir(&self, dir: PathBuf) {
+        *self.cache_dir.lock().unwrap() = Some(dir);
+    }
+
+    fn cache_file(&self, path: &str, modified: u64, resolution: usize) -> Option<PathBuf> {
+        let mut hasher = DefaultHasher::new();
+        (path, modified, resolution).hash(&mut hasher);
+        let dir = self.cache_dir.lock().unwrap().clone()?;
+        Some(dir.join(format!("{:016x}.json", hasher.finish())))
+    }
+
+    fn load(&self, file: &Path, path: &str, modified: u64, resolution: usize) -> Option<Waveform> {
+        let content = fs::read_to_string(file).ok()?;
+        let cached: CachedWaveform = serde_json::from_str(&content).ok()?;
+        // Guard against hash collisions
+        (cached.path == path && cached.modified == modified && cached.resolution == resolution).then_some(cached.waveform)
+    }
+
+    fn store(file: &Path, cached: &CachedWaveform) -> Result<(), String> {
+        if let Some(parent) = file.parent() {
+            fs::create_dir_all(parent).map_err(|e| format!("Failed to create waveform cache: {}", e))?;
+        }
+        let content = serde_json::to_string(cached).map_err(|e| format!("Failed to serialize waveform: {}", e))?;
+        fs::write(file, content).map_err(|e| format!("Failed to cache waveform: {}", e))
+    }
+
+    /// Returns the waveform of `path`, from the cache when the file hasn't
+    /// changed since it was computed.
+    pub async fn get(
+        &self,
+        path: String,
+        resolution: usize,
+        on_chunk: impl FnMut(WaveformChunk) + Send + 'static,
+    ) -> Result<Waveform, String> {
+        if !(MIN_RESOLUTION..=MAX_RESOLUTION).contains(&resolution) {
+            return Err(format!("Resolution must be between {} and {}", MIN_RESOLUTION, MAX_RESOLUTION));
+        }
+
+        let modified = fs::metadata(&path)
+            .and_then(|metadata| metadata.modified())
+            .map_err(|e| format!("Failed to read file: {}", e))?
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis() as u64)
+            .unwrap_or(0);
+
+        let cache_file = self.cache_file(&path, modified, resolution);
+        if let Some(waveform) = cache_file.as_ref().and_then(|file| self.load(file, &path, modified, resolution)) {
+            return Ok(waveform);
+        }
+
+        let _worker = self.workers.acquire().await.map_err(|e| format!("Waveform workers unavailable: {}", e))?;
+        let task_path = path.clone();
+        let waveform = tokio::task::spawn_blocking(move || compute_waveform(&task_path, resolution, on_chunk))
+            .await
+            .map_err(|e| format!("Waveform task failed: {}", e))??;
+
+        if let Some(file) = cache_file {
+            let cached = CachedWaveform {
+                path,
+                modified,
+                resolution,
+                waveform,
+            };
+            if let Err(e) = Self::store(&file, &cached) {
+                eprintln!("{}", e);
+            }
+            return Ok(cached.waveform);
+        }
+        Ok(waveform)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::write_wav;
+
+    #[test]
+    fn bucket_range_follows_the_signal_not_zero() {
+        // A positive DC offset with a small ripple, never crossing zero
+        let samples: Vec<f32> = (0..48000).map(|i| 0.5 + 0.1 * (i as f32 * 0.05).sin()).collect();
+        let path = write_wav("waveform-dc", 48000, 1, &samples);
+        let waveform = compute_waveform(path.to_str().unwrap(), 16, |_| {}).unwrap();
+        for (min, max) in waveform.min.iter().zip(&waveform.max) {
+            assert!(*min > 0.35 && *max < 0.65, "bucket spans {}..{}", min, max);
+        }
+    }
+
+    #[test]
+    fn blocks_fill_every_bucket() {
+        let blocks: Vec<Bucket> = [0.1, -0.2, 0.3]
+            .iter()
+            .map(|sample| {
+                let mut block = Bucket::default();
+                block.add(*sample);
+                block
+            })
+            .collect();
+
+        let mut buckets = vec![Bucket::default(); 16];
+        spread_blocks(&blocks, &mut buckets);
+        assert!(buckets.iter().all(|bucket| bucket.samples > 0));
+        assert_eq!(buckets[0].high(), 0.1);
+        assert_eq!(buckets[8].low(), -0.2);
+        assert_eq!(buckets[15].high(), 0.3);
+
+        // More blocks than buckets still lands every block somewhere
+        let mut buckets = vec![Bucket::default(); 2];
+        spread_blocks(&blocks, &mut buckets);
+        assert_eq!(buckets.iter().map(|bucket| bucket.samples).sum::<u64>(), 3);
+    }
+
+    #[test]
+    fn empty_buckets_report_zero() {
+        let mut buckets = vec![Bucket::default(); 2];
+        buckets[0].add(-0.25);
+        let chunk = chunk("test", 2, 0, &buckets);
+        assert_eq!(chunk.min, vec![-0.25, 0.0]);
+        assert_eq!(chunk.max, vec![-0.25, 0.0]);
+        assert_eq!(chunk.rms, vec![0.25, 0.0]);
+    }
+}
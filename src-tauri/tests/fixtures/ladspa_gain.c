@@ -0,0 +1,116 @@
+/* Minimal LADSPA plugin used by the host tests: a mono gain with a latency
+ * output port. Declares the parts of ladspa.h it needs so no SDK is required. */
+
+#include <stdlib.h>
+
+typedef float LADSPA_Data;
+typedef void *LADSPA_Handle;
+
+typedef struct {
+    int HintDescriptor;
+    LADSPA_Data LowerBound;
+    LADSPA_Data UpperBound;
+} LADSPA_PortRangeHint;
+
+typedef struct _LADSPA_Descriptor {
+    unsigned long UniqueID;
+    const char *Label;
+    int Properties;
+    const char *Name;
+    const char *Maker;
+    const char *Copyright;
+    unsigned long PortCount;
+    const int *PortDescriptors;
+    const char *const *PortNames;
+    const LADSPA_PortRangeHint *PortRangeHints;
+    void *ImplementationData;
+    LADSPA_Handle (*instantiate)(const struct _LADSPA_Descriptor *, unsigned long);
+    void (*connect_port)(LADSPA_Handle, unsigned long, LADSPA_Data *);
+    void (*activate)(LADSPA_Handle);
+    void (*run)(LADSPA_Handle, unsigned long);
+    void (*run_adding)(LADSPA_Handle, unsigned long);
+    void (*set_run_adding_gain)(LADSPA_Handle, LADSPA_Data);
+    void (*deactivate)(LADSPA_Handle);
+    void (*cleanup)(LADSPA_Handle);
+} LADSPA_Descriptor;
+
+enum { PORT_GAIN, PORT_INPUT, PORT_OUTPUT, PORT_LATENCY, PORT_COUNT };
+
+/* Latency the plugin reports, in frames */
+#define LATENCY 7
+
+typedef struct {
+    LADSPA_Data *ports[PORT_COUNT];
+    int active;
+} Gain;
+
+static const int port_descriptors[PORT_COUNT] = {
+    0x1 | 0x4, /* control input */
+    0x1 | 0x8, /* audio input */
+    0x2 | 0x8, /* audio output */
+    0x2 | 0x4, /* control output */
+};
+
+static const char *const port_names[PORT_COUNT] = {"Gain", "Input", "Output", "latency"};
+
+static const LADSPA_PortRangeHint port_hints[PORT_COUNT] = {
+    {0x1 | 0x2 | 0x240, 0.0f, 4.0f}, /* bounded 0..4, default 1 */
+    {0, 0.0f, 0.0f},
+    {0, 0.0f, 0.0f},
+    {0, 0.0f, 0.0f},
+};
+
+static LADSPA_Handle instantiate(const LADSPA_Descriptor *descriptor, unsigned long sample_rate) {
+    (void)descriptor;
+    (void)sample_rate;
+    return calloc(1, sizeof(Gain));
+}
+
+static void connect_port(LADSPA_Handle handle, unsigned long port, LADSPA_Data *data) {
+    ((Gain *)handle)->ports[port] = data;
+}
+
+static void activate(LADSPA_Handle handle) {
+    ((Gain *)handle)->active = 1;
+}
+
+static void deactivate(LADSPA_Handle handle) {
+    ((Gain *)handle)->active = 0;
+}
+
+static void run(LADSPA_Handle handle, unsigned long frames) {
+    Gain *gain = handle;
+    /* Silence marks a run on an instance that was never activated */
+    LADSPA_Data factor = gain->active ? *gain->ports[PORT_GAIN] : 0.0f;
+    for (unsigned long i = 0; i < frames; i++) {
+        gain->ports[PORT_OUTPUT][i] = gain->ports[PORT_INPUT][i] * factor;
+    }
+    *gain->ports[PORT_LATENCY] = LATENCY;
+}
+
+static void cleanup(LADSPA_Handle handle) {
+    free(handle);
+}
+
+static const LADSPA_Descriptor descriptor = {
+    .UniqueID = 4242,
+    .Label = "test_gain",
+    .Properties = 0,
+    .Name = "Test Gain",
+    .Maker = "Test",
+    .Copyright = "None",
+    .PortCount = PORT_COUNT,
+    .PortDescriptors = port_descriptors,
+    .PortNames = port_names,
+    .PortRangeHints = port_hints,
+    .instantiate = instantiate,
+    .connect_port = connect_port,
+    .activate = activate,
+    .run = run,
+    .deactivate = deactivate,
+    .cleanup = cleanup,
+};
+
+const LADSPA_Descriptor *ladspa_descriptor(unsigned long index) {
+    return index == 0 ? &descriptor : NULL;
+}
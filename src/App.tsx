@@ -2,7 +2,7 @@ import { useState, useEffect } from "react";
 import { invoke } from "@tauri-apps/api/core";
 import { open } from "@tauri-apps/plugin-dialog";
 import { Music, HelpCircle, X, Minimize2, FolderOpen, FolderSearch, Download, Wand2, Keyboard, Settings } from "lucide-react";
-import { Song, TrackMetadata } from "./types/music";
+import { MusicFileInfo, Song, TrackMetadata } from "./types/music";
 import { useAudioPlayer } from "./hooks/useAudioPlayer";
 import { Playlist } from "./components/Playlist";
 import { NowPlaying } from "./components/NowPlaying";
@@ -41,6 +41,7 @@ function App() {
     clearPlaylist,
     clearHistory,
     addSongsToPlaylist,
+    syncPlaylistSongs,
     reorderPlaylist,
     togglePlaybackMode,
     setSongRating,
@@ -111,27 +112,21 @@ function App() {
     }
   };
 
-  const handleLibraryImport = (importedFiles: any[]) => {
-    const songs: Song[] = importedFiles.map(file => ({
-      path: file.path,
-      name: file.metadata?.title || file.name,
-      metadata: file.metadata ? {
-        ...file.metadata,
-        track_number: null,
-        year: null,
-        genre: null,
-        codec: null,
-        sample_rate: null,
-        channels: null,
-        bits_per_sample: null,
-        has_artwork: false
-      } : undefined
-    }));
+  const fileToSong = (file: MusicFileInfo): Song => ({
+    path: file.path,
+    name: file.metadata?.title || file.name,
+    metadata: file.metadata ?? undefined
+  });
 
-    addSongsToPlaylist(songs);
+  const handleLibraryImport = (importedFiles: MusicFileInfo[]) => {
+    addSongsToPlaylist(importedFiles.map(fileToSong));
     setShowLibraryImport(false);
   };
 
+  const handleLibrarySync = (changedFiles: MusicFileInfo[], removedPaths: string[]) => {
+    syncPlaylistSongs(changedFiles.map(fileToSong), removedPaths);
+  };
+
   const handleEqualizerToggle = async (enabled: boolean) => {
     setEqualizerEnabled(enabled);
     try {
@@ -399,8 +394,6 @@ function App() {
               <PlaybackInfo
                 currentSong={currentSong}
                 isPlaying={isPlaying}
-                currentTime={currentTime}
-                duration={duration}
                 equalizerEnabled={equalizerEnabled}
                 playHistory={playHistory}
                 onEqualizerToggle={handleEqualizerToggle}
@@ -533,7 +526,9 @@ function App() {
       {/* Music Library Import */}
       {showLibraryImport && (
         <MusicLibraryImport
+          libraryPaths={playlist.map(song => song.path)}
           onImport={handleLibraryImport}
+          onSync={handleLibrarySync}
           onClose={() => setShowLibraryImport(false)}
         />
       )}
@@ -1,14 +1,34 @@
 import { useEffect, useRef } from 'react';
+import { listen } from '@tauri-apps/api/event';
 
 interface AudioVisualizerProps {
   isPlaying: boolean;
-  currentTime: number;
-  duration: number;
 }
 
-export function AudioVisualizer({ isPlaying, currentTime, duration }: AudioVisualizerProps) {
+interface SpectrumFrame {
+  frequencies: number[];
+  magnitudes_db: number[];
+  peak: number[];
+  rms: number[];
+}
+
+// Bands at or below this level are drawn as empty
+const FLOOR_DB = -90;
+
+export function AudioVisualizer({ isPlaying }: AudioVisualizerProps) {
   const canvasRef = useRef<HTMLCanvasElement>(null);
   const animationRef = useRef<number>();
+  const frameRef = useRef<SpectrumFrame | null>(null);
+
+  useEffect(() => {
+    const unlisten = listen<SpectrumFrame>('spectrum', (event) => {
+      frameRef.current = event.payload;
+    });
+
+    return () => {
+      unlisten.then((stop) => stop());
+    };
+  }, []);
 
   useEffect(() => {
     if (!canvasRef.current) return;
@@ -17,51 +37,42 @@ export function AudioVisualizer({ isPlaying, currentTime, duration }: AudioVisua
     const ctx = canvas.getContext('2d');
     if (!ctx) return;
 
-    const bars = 64;
-    const barWidth = canvas.width / bars;
-    
     const draw = () => {
       animationRef.current = requestAnimationFrame(draw);
 
       ctx.fillStyle = 'rgba(20, 20, 20, 0.1)';
       ctx.fillRect(0, 0, canvas.width, canvas.height);
 
-      if (!isPlaying) {
+      const frame = frameRef.current;
+      const bars = frame?.magnitudes_db.length ?? 64;
+      const barWidth = canvas.width / bars;
+
+      if (!isPlaying || !frame) {
         for (let i = 0; i < bars; i++) {
           const x = i * barWidth;
           const height = 10;
-          
+
           const gradient = ctx.createLinearGradient(0, canvas.height, 0, 0);
           gradient.addColorStop(0, 'rgba(59, 130, 246, 0.3)');
           gradient.addColorStop(0.5, 'rgba(139, 92, 246, 0.3)');
           gradient.addColorStop(1, 'rgba(236, 72, 153, 0.3)');
-          
+
           ctx.fillStyle = gradient;
           ctx.fillRect(x + 2, canvas.height - height, barWidth - 4, height);
         }
         return;
       }
 
-      const time = Date.now() / 100;
-      const progress = duration > 0 ? currentTime / duration : 0;
-
       for (let i = 0; i < bars; i++) {
         const x = i * barWidth;
-        
-        const frequency = 0.1 + (i / bars) * 0.9;
-        const amplitude = Math.sin(time * frequency + i * 0.5) * 0.3 + 
-                         Math.sin(time * frequency * 2 + i) * 0.2 +
-                         Math.sin(time * frequency * 0.5) * 0.1;
-        
-        const progressMultiplier = 0.3 + (Math.sin(progress * Math.PI * 4 + i * 0.2) * 0.5 + 0.5) * 0.7;
-        const baseHeight = 20 + Math.abs(amplitude) * 100;
-        const height = baseHeight * progressMultiplier;
-        
+        const level = Math.min(Math.max((frame.magnitudes_db[i] - FLOOR_DB) / -FLOOR_DB, 0), 1);
+        const height = Math.max(level * canvas.height, 2);
+
         const gradient = ctx.createLinearGradient(0, canvas.height, 0, canvas.height - height);
         gradient.addColorStop(0, '#3b82f6');
         gradient.addColorStop(0.5, '#8b5cf6');
         gradient.addColorStop(1, '#ec4899');
-        
+
         ctx.fillStyle = gradient;
         ctx.fillRect(x + 2, canvas.height - height, barWidth - 4, height);
       }
@@ -74,7 +85,7 @@ export function AudioVisualizer({ isPlaying, currentTime, duration }: AudioVisua
         cancelAnimationFrame(animationRef.current);
       }
     };
-  }, [isPlaying, currentTime, duration]);
+  }, [isPlaying]);
 
   return (
     <div className="w-full h-full flex items-center justify-center">
@@ -87,4 +98,4 @@ export function AudioVisualizer({ isPlaying, currentTime, duration }: AudioVisua
       />
     </div>
   );
-}
\ No newline at end of file
+}
@@ -8,13 +8,15 @@ import {
   TrendingUp,
   Save,
   Power,
-  PowerOff
+  PowerOff,
+  Activity
 } from "lucide-react";
 
 interface CrossfadeConfig {
   enabled: boolean;
   duration_seconds: number;
   curve_type: "Linear" | "EqualPower" | "Logarithmic" | "SCurve";
+  beat_match: boolean;
 }
 
 interface CrossfadeSettingsProps {
@@ -26,7 +28,8 @@ export function CrossfadeSettings({ onClose, onSettingsChange }: CrossfadeSettin
   const [config, setConfig] = useState<CrossfadeConfig>({
     enabled: false,
     duration_seconds: 3.0,
-    curve_type: "EqualPower"
+    curve_type: "EqualPower",
+    beat_match: false
   });
   const [isLoading, setIsLoading] = useState(true);
   const [isSaving, setIsSaving] = useState(false);
@@ -74,6 +77,18 @@ export function CrossfadeSettings({ onClose, onSettingsChange }: CrossfadeSettin
     }
   };
 
+  const handleBeatMatchChange = async (beatMatch: boolean) => {
+    setIsSaving(true);
+    try {
+      await invoke("set_crossfade_beat_match", { enabled: beatMatch });
+      setConfig(prev => ({ ...prev, beat_match: beatMatch }));
+    } catch (error) {
+      console.error("Failed to set beat matching:", error);
+    } finally {
+      setIsSaving(false);
+    }
+  };
+
   const curveDescriptions = {
     Linear: "Simple linear fade - gradual and predictable",
     EqualPower: "Maintains constant perceived volume - recommended for most music",
@@ -195,6 +210,29 @@ export function CrossfadeSettings({ onClose, onSettingsChange }: CrossfadeSettin
             </div>
           </div>
 
+          {/* Beat Matching */}
+          <div className="mb-6 p-4 border rounded-lg">
+            <div className="flex items-center justify-between">
+              <div>
+                <h3 className="font-medium flex items-center gap-2">
+                  <Activity className="w-4 h-4" />
+                  Beat Matching
+                </h3>
+                <p className="text-sm text-muted-foreground">
+                  Stretch the next track to the current tempo and line up the beats.
+                  Needs detected BPM for both tracks.
+                </p>
+              </div>
+              <input
+                type="checkbox"
+                checked={config.beat_match}
+                onChange={(e) => handleBeatMatchChange(e.target.checked)}
+                disabled={!config.enabled || isSaving}
+                className="w-4 h-4"
+              />
+            </div>
+          </div>
+
           {/* Crossfade Curve Selection */}
           <div className="mb-6 p-4 border rounded-lg">
             <h3 className="font-medium mb-4 flex items-center gap-2">
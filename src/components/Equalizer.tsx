@@ -1,5 +1,6 @@
 import { useState, useEffect } from 'react';
 import { invoke } from '@tauri-apps/api/core';
+import { LevelMeters } from './LevelMeters';
 
 interface EqualizerProps {
   isEnabled: boolean;
@@ -199,6 +200,9 @@ export function Equalizer({ isEnabled, onToggle }: EqualizerProps) {
           }}
         />
       </div>
+
+      {/* Output levels, measured after the EQ and volume */}
+      <LevelMeters />
     </div>
   );
 }
\ No newline at end of file
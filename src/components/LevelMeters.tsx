@@ -0,0 +1,69 @@
+import { useState, useEffect } from 'react';
+import { invoke } from '@tauri-apps/api/core';
+import { listen } from '@tauri-apps/api/event';
+
+interface ChannelLevels {
+  peak_db: number;
+  true_peak_db: number;
+  rms_db: number;
+  clips: number;
+}
+
+// Lowest level shown on the meter scale
+const METER_FLOOR_DB = -60;
+
+const toPercent = (db: number) =>
+  Math.min(Math.max((db - METER_FLOOR_DB) / -METER_FLOOR_DB, 0), 1) * 100;
+
+export function LevelMeters() {
+  const [levels, setLevels] = useState<ChannelLevels[]>([]);
+
+  useEffect(() => {
+    invoke<ChannelLevels[]>('get_levels').then(setLevels).catch(console.error);
+
+    const unlisten = listen<ChannelLevels[]>('levels', (event) => {
+      setLevels(event.payload);
+    });
+
+    return () => {
+      unlisten.then((stop) => stop());
+    };
+  }, []);
+
+  const resetClips = async () => {
+    try {
+      await invoke('reset_clip_counters');
+      setLevels((current) => current.map((channel) => ({ ...channel, clips: 0 })));
+    } catch (error) {
+      console.error('Failed to reset clip counters:', error);
+    }
+  };
+
+  return (
+    <div className="space-y-1">
+      {levels.map((channel, index) => (
+        <div key={index} className="flex items-center gap-2 text-xs text-muted-foreground">
+          <span className="w-4">{levels.length === 2 ? (index === 0 ? 'L' : 'R') : index + 1}</span>
+          <div className="relative flex-1 h-2 bg-muted rounded-full overflow-hidden">
+            <div
+              className="absolute inset-y-0 left-0 bg-gradient-to-r from-blue-500 via-purple-500 to-pink-500"
+              style={{ width: `${toPercent(channel.rms_db)}%` }}
+            />
+            <div
+              className="absolute inset-y-0 w-0.5 bg-white"
+              style={{ left: `${toPercent(channel.true_peak_db)}%` }}
+            />
+          </div>
+          <span className="w-20 text-right">{channel.true_peak_db.toFixed(1)} dBTP</span>
+          <button
+            onClick={resetClips}
+            title="Reset clip counter"
+            className={`w-12 px-1 rounded ${channel.clips > 0 ? 'bg-red-600 text-white' : 'bg-muted'}`}
+          >
+            {channel.clips > 0 ? `${channel.clips} clip` : 'OK'}
+          </button>
+        </div>
+      ))}
+    </div>
+  );
+}
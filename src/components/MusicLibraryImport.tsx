@@ -11,6 +11,7 @@ import {
   FolderSearch,
   FileAudio
 } from "lucide-react";
+import { MusicFileInfo } from "../types/music";
 
 interface ScanProgress {
   current: number;
@@ -18,28 +19,34 @@ interface ScanProgress {
   current_file: string;
 }
 
-interface MusicFileInfo {
-  path: string;
-  name: string;
-  metadata?: {
-    title: string | null;
-    artist: string | null;
-    album: string | null;
-    duration: number;
-  };
+interface ScanDiff {
+  added: MusicFileInfo[];
+  changed: MusicFileInfo[];
+  removed: string[];
+  unchanged: number;
+}
+
+interface ScanSummary {
+  changed: number;
+  removed: number;
+  unchanged: number;
 }
 
 interface MusicLibraryImportProps {
+  // Paths of the songs already in the library
+  libraryPaths: string[];
   onImport: (songs: MusicFileInfo[]) => void;
+  // Called after each scan with the library songs that changed on disk and those that are gone
+  onSync: (changed: MusicFileInfo[], removed: string[]) => void;
   onClose: () => void;
 }
 
-export function MusicLibraryImport({ onImport, onClose }: MusicLibraryImportProps) {
+export function MusicLibraryImport({ libraryPaths, onImport, onSync, onClose }: MusicLibraryImportProps) {
   const [isScanning, setIsScanning] = useState(false);
   const [scanProgress, setScanProgress] = useState<ScanProgress | null>(null);
   const [scannedFiles, setScannedFiles] = useState<MusicFileInfo[]>([]);
   const [selectedFiles, setSelectedFiles] = useState<Set<string>>(new Set());
-  const [isLoadingMetadata, setIsLoadingMetadata] = useState(false);
+  const [scanSummary, setScanSummary] = useState<ScanSummary | null>(null);
   const [currentFolder, setCurrentFolder] = useState<string | null>(null);
 
   const selectFolder = async () => {
@@ -60,6 +67,7 @@ export function MusicLibraryImport({ onImport, onClose }: MusicLibraryImportProp
     setScanProgress(null);
     setScannedFiles([]);
     setSelectedFiles(new Set());
+    setScanSummary(null);
 
     try {
       // Listen for scan progress
@@ -67,26 +75,32 @@ export function MusicLibraryImport({ onImport, onClose }: MusicLibraryImportProp
         setScanProgress(event.payload);
       });
 
-      // Scan the folder
-      const paths = await invoke<string[]>("scan_music_folder", { 
-        folderPath 
+      // Scan the folder; only files new to the library or changed on disk are read
+      const diff = await invoke<ScanDiff>("scan_music_folder", { 
+        folderPath,
+        libraryPaths
       });
 
       unlisten();
 
-      if (paths.length > 0) {
-        setIsLoadingMetadata(true);
-        
-        // Get metadata for all files
-        const filesInfo = await invoke<MusicFileInfo[]>("get_music_files_metadata", {
-          paths
-        });
-
-        setScannedFiles(filesInfo);
-        // Select all files by default
-        setSelectedFiles(new Set(filesInfo.map(f => f.path)));
-        setIsLoadingMetadata(false);
+      // Bring the library up to date before offering the new files
+      onSync(diff.changed, diff.removed);
+      if (diff.removed.length > 0) {
+        try {
+          await invoke("remove_library_tracks", { paths: diff.removed });
+        } catch (error) {
+          console.error("Failed to remove missing files from the library:", error);
+        }
       }
+
+      setScannedFiles(diff.added);
+      // Select all files by default
+      setSelectedFiles(new Set(diff.added.map(f => f.path)));
+      setScanSummary({
+        changed: diff.changed.length,
+        removed: diff.removed.length,
+        unchanged: diff.unchanged
+      });
     } catch (error) {
       console.error("Failed to scan folder:", error);
     } finally {
@@ -113,8 +127,13 @@ export function MusicLibraryImport({ onImport, onClose }: MusicLibraryImportProp
     }
   };
 
-  const importSelected = () => {
+  const importSelected = async () => {
     const filesToImport = scannedFiles.filter(f => selectedFiles.has(f.path));
+    try {
+      await invoke("add_library_tracks", { tracks: filesToImport });
+    } catch (error) {
+      console.error("Failed to add files to the library:", error);
+    }
     onImport(filesToImport);
   };
 
@@ -154,10 +173,20 @@ export function MusicLibraryImport({ onImport, onClose }: MusicLibraryImportProp
           {!isScanning && scannedFiles.length === 0 && (
             <div className="text-center py-12">
               <FolderOpen className="w-16 h-16 text-muted-foreground mx-auto mb-4" />
-              <h3 className="text-lg font-medium mb-2">Select a folder to scan</h3>
+              <h3 className="text-lg font-medium mb-2">
+                {scanSummary ? "No new music files" : "Select a folder to scan"}
+              </h3>
               <p className="text-muted-foreground mb-6">
-                Choose a folder containing your music files.<br />
-                Supported formats: MP3, WAV, OGG, FLAC, M4A, AAC, OPUS, WMA
+                {scanSummary ? (
+                  <>
+                    {scanSummary.unchanged} unchanged, {scanSummary.changed} updated, {scanSummary.removed} removed
+                  </>
+                ) : (
+                  <>
+                    Choose a folder containing your music files.<br />
+                    Supported formats: MP3, WAV, OGG, FLAC, M4A, AAC, OPUS, WMA
+                  </>
+                )}
               </p>
               <button
                 onClick={selectFolder}
@@ -194,23 +223,20 @@ export function MusicLibraryImport({ onImport, onClose }: MusicLibraryImportProp
             </div>
           )}
 
-          {isLoadingMetadata && (
-            <div className="text-center py-12">
-              <Loader2 className="w-16 h-16 text-primary mx-auto mb-4 animate-spin" />
-              <h3 className="text-lg font-medium mb-2">Loading metadata...</h3>
-              <p className="text-muted-foreground">
-                Reading track information from files
-              </p>
-            </div>
-          )}
-
-          {!isScanning && !isLoadingMetadata && scannedFiles.length > 0 && (
+          {!isScanning && scannedFiles.length > 0 && (
             <div>
               <div className="flex items-center justify-between mb-4">
                 <div className="flex items-center gap-4">
-                  <h3 className="font-medium">
-                    Found {scannedFiles.length} music files
-                  </h3>
+                  <div>
+                    <h3 className="font-medium">
+                      Found {scannedFiles.length} new music files
+                    </h3>
+                    {scanSummary && (
+                      <p className="text-xs text-muted-foreground">
+                        {scanSummary.unchanged} unchanged, {scanSummary.changed} updated, {scanSummary.removed} removed
+                      </p>
+                    )}
+                  </div>
                   <button
                     onClick={toggleSelectAll}
                     className="text-sm text-primary hover:underline"
@@ -267,7 +293,7 @@ export function MusicLibraryImport({ onImport, onClose }: MusicLibraryImportProp
         </div>
 
         {/* Footer */}
-        {!isScanning && !isLoadingMetadata && scannedFiles.length > 0 && (
+        {!isScanning && scannedFiles.length > 0 && (
           <div className="p-6 border-t">
             <div className="flex items-center justify-between">
               <p className="text-sm text-muted-foreground">
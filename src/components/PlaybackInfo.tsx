@@ -7,8 +7,6 @@ import { Equalizer } from "./Equalizer";
 interface PlaybackInfoProps {
   currentSong: Song | null;
   isPlaying: boolean;
-  currentTime: number;
-  duration: number;
   equalizerEnabled: boolean;
   playHistory: PlayHistoryEntry[];
   onEqualizerToggle: (enabled: boolean) => void;
@@ -19,8 +17,6 @@ interface PlaybackInfoProps {
 export function PlaybackInfo({
   currentSong,
   isPlaying,
-  currentTime,
-  duration,
   equalizerEnabled,
   playHistory,
   onEqualizerToggle,
@@ -41,11 +37,7 @@ export function PlaybackInfo({
         <>
           <TabsContent value="visualizer" className="mt-6">
             <div className="w-full h-64 bg-black rounded-lg overflow-hidden">
-              <AudioVisualizer 
-                isPlaying={isPlaying} 
-                currentTime={currentTime}
-                duration={duration}
-              />
+              <AudioVisualizer isPlaying={isPlaying} />
             </div>
           </TabsContent>
 
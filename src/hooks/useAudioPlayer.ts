@@ -546,6 +546,19 @@ export function useAudioPlayer() {
     setPlaylist(prev => [...prev, ...songs]);
   }, []);
 
+  // Applies a library rescan: refreshes the metadata of changed songs and
+  // drops songs whose files are gone
+  const syncPlaylistSongs = useCallback((changed: Song[], removed: string[]) => {
+    const updates = new Map(changed.map(song => [song.path, song]));
+    const gone = new Set(removed);
+    setPlaylist(prev => prev
+      .filter(song => !gone.has(song.path))
+      .map(song => {
+        const update = updates.get(song.path);
+        return update ? { ...song, name: update.name, metadata: update.metadata } : song;
+      }));
+  }, []);
+
   const reorderPlaylist = useCallback((fromIndex: number, toIndex: number) => {
     setPlaylist(prev => {
       const newPlaylist = [...prev];
@@ -620,6 +633,7 @@ export function useAudioPlayer() {
     clearPlaylist,
     clearHistory,
     addSongsToPlaylist,
+    syncPlaylistSongs,
     reorderPlaylist,
     togglePlaybackMode,
     setSongRating,
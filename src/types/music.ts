@@ -10,6 +10,8 @@ export interface TrackMetadata {
   sample_rate: number | null;
   channels: string | null;
   bits_per_sample: number | null;
+  bpm: number | null;
+  key: string | null;
   has_artwork: boolean;
 }
 
@@ -26,6 +28,13 @@ export interface Song {
   isFavorite?: boolean;
 }
 
+// A music file as the backend's folder scan reports it
+export interface MusicFileInfo {
+  path: string;
+  name: string;
+  metadata: TrackMetadata | null;
+}
+
 export interface PlayHistoryEntry {
   song: Song;
   playedAt: string;